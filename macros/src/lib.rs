@@ -1,6 +1,6 @@
 use convert_case::{Case, Casing};
 use proc_macro::TokenStream;
-use proc_macro2::{Literal, TokenStream as TokenStream2};
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
 use quote::{quote, quote_spanned};
 use std::{fs, path::Path};
 use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Expr, Fields, Ident, Lit, Type};
@@ -8,14 +8,20 @@ use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Expr, Fields,
 // example:
 // #[derive(CtfEventClass)]
 // #[event_name = "TRACE_START"]
+// #[event_id = 0x01]
 // pub struct TraceStart<'a> {
 //     pub task_handle: i64,
 //     pub task: &'a CStr,
 // }
-#[proc_macro_derive(CtfEventClass, attributes(event_name, event_name_from_event_type))]
+//
+// Integer fields marked #[hex] prefer a hexadecimal display base, fields marked
+// #[unit = "ns"] get a unit user attribute (see crate::units)
+#[proc_macro_derive(
+    CtfEventClass,
+    attributes(event_name, event_id, event_name_from_event_type, hex, unit)
+)]
 pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
-    // TODO generic enum handling, TaskState is an enum
-    let supported_types = ["i64", "u64", "CStr", "TaskState"];
+    let supported_types = ["i64", "u64", "CStr"];
 
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -34,11 +40,34 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
         }
         None
     });
+    let attr_event_id = input.attrs.iter().find_map(|a| {
+        if let Ok(val) = a.meta.require_name_value() {
+            if val.path.is_ident("event_id") {
+                if let Expr::Lit(lit) = &val.value {
+                    if let Lit::Int(i) = &lit.lit {
+                        return i.base10_parse::<u64>().ok();
+                    }
+                }
+            }
+        }
+        None
+    });
     let name_from_event_type = input
         .attrs
         .iter()
         .any(|a| a.meta.path().is_ident("event_name_from_event_type"));
 
+    // Classes named from the event type take their ID from the event code,
+    // everything else must declare a stable ID
+    if !name_from_event_type && attr_event_id.is_none() {
+        return quote_spanned! {
+            type_name.span() => compile_error!(
+                "Deriving CtfEventClass requires an event_id attribute."
+            );
+        }
+        .into();
+    }
+
     let event_name = if let Some(n) = attr_event_name {
         n
     } else {
@@ -70,6 +99,19 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
                     .ident
                     .as_ref()
                     .expect("Failed to get struct field identifier.");
+                let hex = field.attrs.iter().any(|a| a.path().is_ident("hex"));
+                let unit = field.attrs.iter().find_map(|a| {
+                    if let Ok(val) = a.meta.require_name_value() {
+                        if val.path.is_ident("unit") {
+                            if let Expr::Lit(lit) = &val.value {
+                                if let Lit::Str(s) = &lit.lit {
+                                    return Some(s.value());
+                                }
+                            }
+                        }
+                    }
+                    None
+                });
                 match field.ty {
                     Type::Path(t) => {
                         let typ = t
@@ -77,7 +119,9 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
                             .get_ident()
                             .expect("Failed to get struct field type.")
                             .to_string();
-                        if !supported_types.contains(&typ.as_str()) {
+                        if !supported_types.contains(&typ.as_str())
+                            && !ENUM_TYPES.contains(&typ.as_str())
+                        {
                             return quote_spanned! {
                                 type_name.span() => compile_error!(
                                     "Deriving CtfEventClass for the type is not supported."
@@ -85,7 +129,12 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
                             }
                             .into();
                         }
-                        field_class_impls.push(event_class_field_class(field_name, &typ));
+                        field_class_impls.push(event_class_field_class(
+                            field_name,
+                            &typ,
+                            hex,
+                            unit.as_deref(),
+                        ));
                         field_impls.push(event_field(field_index, field_name, &typ));
                     }
                     Type::Reference(t) => {
@@ -110,7 +159,12 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
                             }
                             .into();
                         }
-                        field_class_impls.push(event_class_field_class(field_name, &typ));
+                        field_class_impls.push(event_class_field_class(
+                            field_name,
+                            &typ,
+                            hex,
+                            unit.as_deref(),
+                        ));
                         field_impls.push(event_field(field_index, field_name, &typ));
                     }
                     _ => {
@@ -161,14 +215,14 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
 
     let event_class_impl = if name_from_event_type {
         quote! {
-            pub(crate) fn event_class(event_type: trace_recorder_parser::streaming::event::EventType, stream_class: *mut babeltrace2_sys::ffi::bt_stream_class) -> Result<*mut babeltrace2_sys::ffi::bt_event_class, babeltrace2_sys::Error> {
+            pub(crate) fn event_class(event_type: trace_recorder_parser::streaming::event::EventType, event_id: trace_recorder_parser::streaming::event::EventId, stream_class: *mut babeltrace2_sys::ffi::bt_stream_class) -> Result<*mut babeltrace2_sys::ffi::bt_event_class, babeltrace2_sys::Error> {
                 use babeltrace2_sys::{ffi, BtResultExt};
                 use std::ffi::CString;
 
                 unsafe {
                     let trace_class = ffi::bt_stream_class_borrow_trace_class(stream_class);
 
-                    let event_class = ffi::bt_event_class_create(stream_class);
+                    let event_class = ffi::bt_event_class_create_with_id(stream_class, event_id.0 as u64);
                     let event_name = CString::new(event_type.to_string())?;
                     let ret = ffi::bt_event_class_set_name(event_class, event_name.as_c_str().as_ptr() as _);
                     ret.capi_result()?;
@@ -183,15 +237,26 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
                 }
             }
         }
-    } else {
+    } else if has_payload_field {
+        let event_id = attr_event_id.unwrap_or_default();
         quote! {
             pub(crate) fn event_class(stream_class: *mut babeltrace2_sys::ffi::bt_stream_class) -> Result<*mut babeltrace2_sys::ffi::bt_event_class, babeltrace2_sys::Error> {
+                Self::event_class_with_payload_members(#event_id, stream_class, |_, _| Ok(()))
+            }
+
+            /// Same as `event_class`, with a caller provided ID and additional payload
+            /// members appended after the struct's fields
+            #[allow(dead_code)]
+            pub(crate) fn event_class_with_payload_members<F>(event_id: u64, stream_class: *mut babeltrace2_sys::ffi::bt_stream_class, f: F) -> Result<*mut babeltrace2_sys::ffi::bt_event_class, babeltrace2_sys::Error>
+            where
+                F: FnOnce(*mut babeltrace2_sys::ffi::bt_trace_class, *mut babeltrace2_sys::ffi::bt_field_class) -> Result<(), babeltrace2_sys::Error>,
+            {
                 use babeltrace2_sys::{ffi, BtResultExt};
 
                 unsafe {
                     let trace_class = ffi::bt_stream_class_borrow_trace_class(stream_class);
 
-                    let event_class = ffi::bt_event_class_create(stream_class);
+                    let event_class = ffi::bt_event_class_create_with_id(stream_class, event_id);
                     let ret = ffi::bt_event_class_set_name(event_class, #event_name_raw_str.as_ptr() as _);
                     ret.capi_result()?;
 
@@ -199,8 +264,27 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
 
                     #field_classes
 
+                    f(trace_class, payload_fc)?;
+
                     #payload_fc_end
 
+                    Ok(event_class)
+                }
+            }
+        }
+    } else {
+        let event_id = attr_event_id.unwrap_or_default();
+        quote! {
+            pub(crate) fn event_class(stream_class: *mut babeltrace2_sys::ffi::bt_stream_class) -> Result<*mut babeltrace2_sys::ffi::bt_event_class, babeltrace2_sys::Error> {
+                use babeltrace2_sys::{ffi, BtResultExt};
+
+                unsafe {
+                    let trace_class = ffi::bt_stream_class_borrow_trace_class(stream_class);
+
+                    let event_class = ffi::bt_event_class_create_with_id(stream_class, #event_id);
+                    let ret = ffi::bt_event_class_set_name(event_class, #event_name_raw_str.as_ptr() as _);
+                    ret.capi_result()?;
+
                     Ok(event_class)
                 }
             }
@@ -247,7 +331,23 @@ pub fn derive_ctf_event_class(input: TokenStream) -> TokenStream {
     ts
 }
 
-fn event_class_field_class(field_name: &Ident, typ: &str) -> TokenStream2 {
+/// Enums with `as_ffi`, `as_i64` and an `enum_iterator::Sequence` impl, mapped to
+/// signed enumeration field classes
+const ENUM_TYPES: &[&str] = &[
+    "TaskState",
+    "TaskLifecycleState",
+    "AnomalyKind",
+    "LogLevel",
+    "ObjectClass",
+    "ContextKind",
+];
+
+fn event_class_field_class(
+    field_name: &Ident,
+    typ: &str,
+    hex: bool,
+    unit: Option<&str>,
+) -> TokenStream2 {
     let name_bytes = format!("{}\0", field_name);
     let byte_str = Literal::byte_string(name_bytes.as_bytes());
     let fc_create = match typ {
@@ -267,10 +367,11 @@ fn event_class_field_class(field_name: &Ident, typ: &str) -> TokenStream2 {
             }
         }
         // enums
-        "TaskState" => {
+        t if ENUM_TYPES.contains(&t) => {
+            let enum_type = Ident::new(t, Span::call_site());
             quote! {
                 let fc = ffi::bt_field_class_enumeration_signed_create(trace_class);
-                let variants = enum_iterator::all::<TaskState>().collect::<Vec<_>>();
+                let variants = enum_iterator::all::<#enum_type>().collect::<Vec<_>>();
                 for variant in variants.into_iter() {
                     let variant_rs = ffi::bt_integer_range_set_signed_create();
                     let ret = ffi::bt_integer_range_set_signed_add_range(
@@ -293,8 +394,25 @@ fn event_class_field_class(field_name: &Ident, typ: &str) -> TokenStream2 {
         _ => unreachable!(),
     };
 
+    let fc_display_base = (hex && matches!(typ, "i64" | "u64")).then(|| {
+        quote! {
+            ffi::bt_field_class_integer_set_preferred_display_base(
+                fc,
+                ffi::bt_field_class_integer_preferred_display_base::BT_FIELD_CLASS_INTEGER_PREFERRED_DISPLAY_BASE_HEXADECIMAL,
+            );
+        }
+    });
+
+    let fc_unit = unit.map(|unit| {
+        quote! {
+            crate::units::set_unit(fc, #unit)?;
+        }
+    });
+
     quote! {
         #fc_create
+        #fc_display_base
+        #fc_unit
         let ret = ffi::bt_field_class_structure_append_member(
             payload_fc,
             #byte_str.as_ptr() as _,
@@ -324,7 +442,7 @@ fn event_field(field_index: usize, field_name: &Ident, typ: &str) -> TokenStream
             }
         }
         // enums
-        "TaskState" => {
+        t if ENUM_TYPES.contains(&t) => {
             quote! {
                 ffi::bt_field_integer_signed_set_value(f, self.#field_name.as_i64());
             }
@@ -0,0 +1,126 @@
+use crate::events::AnomalyKind;
+use std::collections::{HashMap, VecDeque};
+use trace_recorder_parser::{streaming::event::EventType, time::Timestamp};
+
+/// Minimum number of events of a type observed before its rate is considered known
+const MIN_SAMPLES: u64 = 16;
+
+/// Number of windows of history needed before bursts are flagged
+const WARMUP_WINDOWS: u64 = 4;
+
+#[derive(Debug)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub event_type: EventType,
+    /// Events observed over the anomalous period
+    pub count: u64,
+    /// Events expected over the anomalous period, given the average rate
+    pub expected: u64,
+    pub duration_ns: u64,
+}
+
+/// Flags per-event-type rate anomalies
+///
+/// A burst is flagged when the number of events of a type within the sliding
+/// window exceeds `burst_factor` times the count expected from its average rate.
+/// A silence is flagged when the gap between two events of a type exceeds both
+/// the window and `silence_factor` times its average interval.
+#[derive(Debug)]
+pub struct AnomalyDetector {
+    timer_frequency: u64,
+    window: u64,
+    burst_factor: f64,
+    silence_factor: f64,
+    rates: HashMap<EventType, EventRate>,
+}
+
+#[derive(Debug)]
+struct EventRate {
+    total: u64,
+    first: u64,
+    last: u64,
+    /// Timestamps of the events within the window
+    window: VecDeque<u64>,
+    in_burst: bool,
+}
+
+impl AnomalyDetector {
+    pub fn new(
+        timer_frequency: u32,
+        window_ms: u64,
+        burst_factor: f64,
+        silence_factor: f64,
+    ) -> Self {
+        let timer_frequency = u64::from(timer_frequency).max(1);
+        Self {
+            timer_frequency,
+            window: (window_ms * timer_frequency / 1000).max(1),
+            burst_factor,
+            silence_factor,
+            rates: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, event_type: EventType, timestamp: Timestamp) -> Vec<Anomaly> {
+        let now = timestamp.ticks();
+        let mut anomalies = Vec::new();
+        let rate = self.rates.entry(event_type).or_insert_with(|| EventRate {
+            total: 0,
+            first: now,
+            last: now,
+            window: VecDeque::new(),
+            in_burst: false,
+        });
+
+        if rate.total >= MIN_SAMPLES {
+            let mean_interval =
+                ((rate.last - rate.first) as f64 / (rate.total - 1) as f64).max(1.0);
+            let gap = now.saturating_sub(rate.last);
+            if gap > self.window && gap as f64 > self.silence_factor * mean_interval {
+                anomalies.push(Anomaly {
+                    kind: AnomalyKind::Silence,
+                    event_type,
+                    count: 0,
+                    expected: (gap as f64 / mean_interval) as u64,
+                    duration_ns: ticks_to_ns(gap, self.timer_frequency),
+                });
+            }
+        }
+
+        rate.total += 1;
+        rate.last = now;
+        rate.window.push_back(now);
+        while rate
+            .window
+            .front()
+            .map(|t| now - *t > self.window)
+            .unwrap_or(false)
+        {
+            rate.window.pop_front();
+        }
+
+        let elapsed = now - rate.first;
+        if rate.total >= MIN_SAMPLES && elapsed >= WARMUP_WINDOWS * self.window {
+            let expected = rate.total as f64 * self.window as f64 / elapsed as f64;
+            let count = rate.window.len() as u64;
+            if !rate.in_burst && count as f64 > self.burst_factor * expected {
+                rate.in_burst = true;
+                anomalies.push(Anomaly {
+                    kind: AnomalyKind::Burst,
+                    event_type,
+                    count,
+                    expected: expected.ceil() as u64,
+                    duration_ns: ticks_to_ns(self.window, self.timer_frequency),
+                });
+            } else if rate.in_burst && count as f64 <= expected {
+                rate.in_burst = false;
+            }
+        }
+
+        anomalies
+    }
+}
+
+fn ticks_to_ns(ticks: u64, timer_frequency: u64) -> u64 {
+    (u128::from(ticks) * 1_000_000_000 / u128::from(timer_frequency)) as u64
+}
@@ -0,0 +1,108 @@
+use crate::event_fields::{event_field, FieldValue};
+use regex::Regex;
+use serde::Deserialize;
+use std::io::Read;
+use trace_recorder_parser::{
+    streaming::{event::Event, Error, RecorderData},
+    time::{Frequency, StreamingInstant, Timestamp},
+};
+use tracing::{debug, warn};
+
+/// Unix seconds with an optional fraction, e.g. 1718000000.123456
+const DEFAULT_PATTERN: &str = r"^\s*(\d+)(?:\.(\d{1,9}))?";
+
+/// Recognizes the user events carrying a host wall-clock timestamp
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockSyncRule {
+    /// User event channel name
+    pub channel: String,
+    /// Regex matched against the formatted string, the `secs` (else first) group is the
+    /// Unix seconds and the optional `frac` (else second) group the fractional seconds
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ClockSync {
+    channel: String,
+    pattern: Regex,
+    /// Host time minus target time of the first correlation, the clock class offset
+    pub offset_ns: Option<i64>,
+}
+
+impl ClockSync {
+    pub fn new(rule: &ClockSyncRule) -> Result<Self, regex::Error> {
+        Ok(Self {
+            channel: rule.channel.clone(),
+            pattern: Regex::new(rule.pattern.as_deref().unwrap_or(DEFAULT_PATTERN))?,
+            offset_ns: None,
+        })
+    }
+
+    /// Host wall-clock time (Unix nanoseconds) carried by the event, if it's a correlation event
+    pub fn host_time_ns(&self, event: &Event) -> Option<u64> {
+        if !matches!(event, Event::User(_)) {
+            return None;
+        }
+        match event_field(event, "channel") {
+            Some(FieldValue::Str(channel)) if channel == self.channel => (),
+            _ => return None,
+        }
+        let formatted_string = match event_field(event, "formatted_string") {
+            Some(FieldValue::Str(s)) => s,
+            _ => return None,
+        };
+        let caps = self.pattern.captures(formatted_string)?;
+        let secs: u64 = caps
+            .name("secs")
+            .or_else(|| caps.get(1))?
+            .as_str()
+            .parse()
+            .ok()?;
+        let nanos: u64 = match caps.name("frac").or_else(|| caps.get(2)) {
+            Some(frac) => format!("{:0<9.9}", frac.as_str()).parse().ok()?,
+            None => 0,
+        };
+        secs.checked_mul(1_000_000_000)?.checked_add(nanos)
+    }
+
+    /// Scan the trace for the first correlation event to determine the clock class offset
+    pub fn find_offset<R: Read>(&mut self, mut r: R, zero_base_time: bool) -> Result<(), Error> {
+        let mut trd = RecorderData::find(&mut r)?;
+        let freq = trd.timestamp_info.timer_frequency;
+        let mut time_tracker = None;
+        let mut time_base = None;
+        while let Some((_, event)) = trd.read_event(&mut r)? {
+            let tracker = time_tracker.get_or_insert_with(|| {
+                StreamingInstant::new(
+                    event.timestamp().ticks() as u32,
+                    trd.timestamp_info.timer_wraparounds,
+                )
+            });
+            let mut timestamp = tracker.elapsed(event.timestamp());
+            if zero_base_time {
+                let time_base = *time_base.get_or_insert(timestamp.ticks());
+                timestamp = Timestamp::from(timestamp.ticks() - time_base);
+            }
+            if let Some(host_time_ns) = self.host_time_ns(&event) {
+                let offset_ns = offset_ns(host_time_ns, timestamp, freq);
+                debug!(host_time_ns, offset_ns, "Found clock correlation");
+                self.offset_ns = Some(offset_ns);
+                return Ok(());
+            }
+        }
+        warn!(
+            channel = self.channel,
+            "No clock correlation events found, the clock isn't aligned to the host"
+        );
+        Ok(())
+    }
+}
+
+/// Host time minus the target time since the start of the trace
+pub fn offset_ns(host_time_ns: u64, timestamp: Timestamp, freq: Frequency) -> i64 {
+    let freq = u128::from(freq.get_raw()).max(1);
+    let target_ns = u128::from(timestamp.ticks()) * 1_000_000_000 / freq;
+    (i128::from(host_time_ns) - target_ns as i128) as i64
+}
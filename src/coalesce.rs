@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use trace_recorder_parser::{
+    streaming::event::{EventCode, EventType},
+    time::Timestamp,
+};
+
+/// Event types driving the converter's context tracking, these can't be coalesced
+const CONTEXT_SWITCH_EVENT_TYPES: &[&str] = &[
+    "TRACE_START",
+    "TASK_SWITCH_ISR_BEGIN",
+    "TASK_SWITCH_ISR_RESUME",
+    "TASK_SWITCH_TASK_BEGIN",
+    "TASK_SWITCH_TASK_RESUME",
+    "TASK_ACTIVATE",
+    "TASK_RESUME",
+];
+
+/// Parse a --coalesce event type name
+pub fn parse_event_type(s: &str) -> Result<String, String> {
+    if CONTEXT_SWITCH_EVENT_TYPES.contains(&s) {
+        Err(format!(
+            "Event type '{s}' can't be coalesced, it drives the task and ISR context tracking"
+        ))
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+/// A run of consecutive events of the same type
+#[derive(Copy, Clone, Debug)]
+pub struct Run {
+    pub event_type: EventType,
+    /// Event code, counter and raw timestamp of the first event, for the common context
+    pub event_code: EventCode,
+    pub event_count: u64,
+    pub raw_timestamp: Timestamp,
+    pub first_timestamp: Timestamp,
+    pub last_timestamp: Timestamp,
+    pub count: u64,
+}
+
+/// Merges runs of consecutive no-payload events of the configured types
+#[derive(Debug, Default)]
+pub struct Coalescer {
+    /// Event type names to coalesce
+    names: Vec<String>,
+    /// Whether each event type seen so far is coalesced, saves formatting the name per event
+    coalesced_types: HashMap<EventType, bool>,
+    run: Option<Run>,
+}
+
+impl Coalescer {
+    pub fn new(names: &[String]) -> Self {
+        Self {
+            names: names.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.names.is_empty()
+    }
+
+    pub fn coalesces(&mut self, event_type: EventType) -> bool {
+        if let Some(coalesced) = self.coalesced_types.get(&event_type) {
+            return *coalesced;
+        }
+        let name = event_type.to_string();
+        let coalesced = self.names.contains(&name);
+        self.coalesced_types.insert(event_type, coalesced);
+        coalesced
+    }
+
+    /// Add the event to the current run, or start a new run
+    pub fn push(
+        &mut self,
+        event_code: EventCode,
+        event_count: u64,
+        timestamp: Timestamp,
+        raw_timestamp: Timestamp,
+    ) {
+        let event_type = event_code.event_type();
+        match self.run.as_mut() {
+            Some(run) if run.event_type == event_type => {
+                run.last_timestamp = timestamp;
+                run.count += 1;
+            }
+            _ => {
+                self.run = Some(Run {
+                    event_type,
+                    event_code,
+                    event_count,
+                    raw_timestamp,
+                    first_timestamp: timestamp,
+                    last_timestamp: timestamp,
+                    count: 1,
+                })
+            }
+        }
+    }
+
+    /// Take the current run, unless the next event continues it
+    pub fn take_finished(&mut self, next: Option<EventType>) -> Option<Run> {
+        match self.run {
+            Some(run) if Some(run.event_type) != next => self.run.take(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fs::File, io::BufReader, path::Path};
+use trace_recorder_parser::{
+    streaming::event::Event, streaming::RecorderData, time::StreamingInstant,
+};
+
+/// Behavioral profile of a trace, used to compare two traces
+#[derive(Debug, Default)]
+struct TraceProfile {
+    timer_frequency: u64,
+    events: u64,
+    /// Ticks between the first and last event
+    duration: u64,
+    events_by_type: BTreeMap<String, u64>,
+    /// Ticks each task was running for, excluding time spent in ISRs
+    task_runtime: BTreeMap<String, u64>,
+    /// Durations (ticks) of each ISR invocation, including nested ISRs
+    isr_durations: BTreeMap<String, Vec<u64>>,
+    /// Set when the trace couldn't be read to the end
+    truncated: Option<String>,
+}
+
+impl TraceProfile {
+    fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut trd = RecorderData::find(&mut reader)?;
+
+        let mut profile = TraceProfile {
+            timer_frequency: u64::from(trd.timestamp_info.timer_frequency.get_raw()),
+            ..Default::default()
+        };
+        let mut time_rollover_tracker = StreamingInstant::zero();
+        let mut first_timestamp = None;
+        // Active task and the time it was switched in, None while in an ISR
+        let mut active_task: Option<(String, u64)> = None;
+        let mut pending_isrs: Vec<(String, u64)> = Vec::new();
+
+        loop {
+            let (event_type, event) = match trd.read_event(&mut reader) {
+                Ok(Some((code, event))) => (code.event_type(), event),
+                Ok(None) => break,
+                Err(e) => {
+                    profile.truncated = Some(e.to_string());
+                    break;
+                }
+            };
+
+            let first = *first_timestamp.get_or_insert_with(|| {
+                time_rollover_tracker = StreamingInstant::new(
+                    event.timestamp().ticks() as u32,
+                    trd.timestamp_info.timer_wraparounds,
+                );
+                event.timestamp().ticks()
+            });
+            let now = time_rollover_tracker.elapsed(event.timestamp()).ticks();
+            profile.duration = now.saturating_sub(first);
+            profile.events += 1;
+            *profile
+                .events_by_type
+                .entry(event_type.to_string())
+                .or_default() += 1;
+
+            match event {
+                Event::TaskResume(ev) | Event::TaskActivate(ev) => {
+                    if let Some((isr, start)) = pending_isrs.pop() {
+                        profile.record_isr(isr, now - start);
+                    }
+                    if let Some((task, start)) = active_task.take() {
+                        profile.record_runtime(task, now - start);
+                    }
+                    active_task = Some((ev.name.to_string(), now));
+                }
+                Event::IsrBegin(ev) => {
+                    if let Some((task, start)) = active_task.take() {
+                        profile.record_runtime(task, now - start);
+                    }
+                    pending_isrs.push((ev.name.to_string(), now));
+                }
+                Event::IsrResume(_) => {
+                    if let Some((isr, start)) = pending_isrs.pop() {
+                        profile.record_isr(isr, now - start);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if let Some((task, start)) = active_task.take() {
+            let end = first_timestamp.unwrap_or(0) + profile.duration;
+            profile.record_runtime(task, end.saturating_sub(start));
+        }
+
+        Ok(profile)
+    }
+
+    fn record_runtime(&mut self, task: String, ticks: u64) {
+        *self.task_runtime.entry(task).or_default() += ticks;
+    }
+
+    fn record_isr(&mut self, isr: String, ticks: u64) {
+        self.isr_durations.entry(isr).or_default().push(ticks);
+    }
+
+    fn secs(&self, ticks: u64) -> f64 {
+        ticks as f64 / self.timer_frequency.max(1) as f64
+    }
+
+    fn event_rate(&self, count: u64) -> f64 {
+        let secs = self.secs(self.duration);
+        if secs > 0.0 {
+            count as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    fn utilization(&self, task: &str) -> Option<f64> {
+        let runtime = self.task_runtime.get(task)?;
+        Some(100.0 * *runtime as f64 / self.duration.max(1) as f64)
+    }
+
+    fn isr_stats(&self, isr: &str) -> Option<IsrStats> {
+        let mut durations = self.isr_durations.get(isr)?.clone();
+        durations.sort_unstable();
+        let us = |ticks: u64| self.secs(ticks) * 1_000_000.0;
+        let percentile = |p: usize| us(durations[(durations.len() - 1) * p / 100]);
+        Some(IsrStats {
+            count: durations.len(),
+            mean_us: us(durations.iter().sum::<u64>()) / durations.len() as f64,
+            p50_us: percentile(50),
+            p99_us: percentile(99),
+            max_us: us(*durations.last()?),
+        })
+    }
+}
+
+struct IsrStats {
+    count: usize,
+    mean_us: f64,
+    p50_us: f64,
+    p99_us: f64,
+    max_us: f64,
+}
+
+/// Compare two traces, reporting differences in event type rates, the set of tasks,
+/// task CPU utilization and ISR duration distributions
+pub fn run(a_path: &Path, b_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let a = TraceProfile::read(a_path)?;
+    let b = TraceProfile::read(b_path)?;
+
+    println!("A: {}", a_path.display());
+    println!("B: {}", b_path.display());
+    for (label, p) in [("A", &a), ("B", &b)] {
+        if let Some(e) = &p.truncated {
+            println!("NOTE: {label} could not be read to the end ({e})");
+        }
+    }
+
+    println!();
+    println!("{:<32} {:>14} {:>14}", "", "A", "B");
+    println!("{:<32} {:>14} {:>14}", "Events", a.events, b.events);
+    println!(
+        "{:<32} {:>14.6} {:>14.6}",
+        "Duration (s)",
+        a.secs(a.duration),
+        b.secs(b.duration)
+    );
+
+    println!();
+    println!(
+        "{:<32} {:>14} {:>14} {:>10}",
+        "Event type (events/s)", "A", "B", "Change"
+    );
+    let event_types: BTreeSet<_> = a
+        .events_by_type
+        .keys()
+        .chain(b.events_by_type.keys())
+        .collect();
+    for t in event_types {
+        let rate_a = a.event_rate(a.events_by_type.get(t).copied().unwrap_or(0));
+        let rate_b = b.event_rate(b.events_by_type.get(t).copied().unwrap_or(0));
+        println!(
+            "{:<32} {:>14.1} {:>14.1} {:>10}",
+            t,
+            rate_a,
+            rate_b,
+            change(rate_a, rate_b)
+        );
+    }
+
+    println!();
+    println!(
+        "{:<32} {:>14} {:>14} {:>10}",
+        "Task CPU utilization (%)", "A", "B", "Change"
+    );
+    let tasks: BTreeSet<_> = a.task_runtime.keys().chain(b.task_runtime.keys()).collect();
+    for t in tasks {
+        let (util_a, util_b) = (a.utilization(t), b.utilization(t));
+        println!(
+            "{:<32} {:>14} {:>14} {:>10}",
+            t,
+            util_a.map(|u| format!("{u:.2}")).unwrap_or("-".into()),
+            util_b.map(|u| format!("{u:.2}")).unwrap_or("-".into()),
+            match (util_a, util_b) {
+                (Some(a), Some(b)) => change(a, b),
+                (Some(_), None) => "removed".into(),
+                _ => "added".into(),
+            }
+        );
+    }
+
+    println!();
+    println!(
+        "{:<32} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "ISR duration (us)", "Count", "Mean", "p50", "p99", "Max"
+    );
+    let isrs: BTreeSet<_> = a
+        .isr_durations
+        .keys()
+        .chain(b.isr_durations.keys())
+        .collect();
+    for isr in isrs {
+        for (label, p) in [("A", &a), ("B", &b)] {
+            let name = format!("{isr} ({label})");
+            match p.isr_stats(isr) {
+                Some(s) => println!(
+                    "{:<32} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+                    name, s.count, s.mean_us, s.p50_us, s.p99_us, s.max_us
+                ),
+                None => println!("{name:<32} {:>8}", "-"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn change(a: f64, b: f64) -> String {
+    if a == b {
+        String::new()
+    } else if a == 0.0 {
+        "new".into()
+    } else {
+        format!("{:+.1}%", 100.0 * (b - a) / a)
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+/// Extension of the compressed stream files
+const EXTENSION: &str = "zst";
+
+/// The CTF metadata file, kept uncompressed so the trace can still be identified
+const METADATA: &str = "metadata";
+
+/// Compress the stream files of a finished CTF trace with zstd, replacing each with `<name>.zst`.
+/// Looks into the subdirectories too, the trace is in one unless --sink-assume-single-trace.
+pub fn compress_streams(dir: &Path, level: i32) -> io::Result<()> {
+    let mut bytes_in = 0;
+    let mut bytes_out = 0;
+    let mut files = 0;
+    for path in files_in(dir)? {
+        if path.is_dir() {
+            compress_streams(&path, level)?;
+            continue;
+        }
+        let is_stream = path.file_name().is_some_and(|n| n != METADATA)
+            && path.extension().is_none_or(|e| e != EXTENSION);
+        if !is_stream {
+            continue;
+        }
+        let compressed_path = with_extension(&path);
+        let mut output = BufWriter::new(File::create(&compressed_path)?);
+        zstd::stream::copy_encode(BufReader::new(File::open(&path)?), &mut output, level)?;
+        output.flush()?;
+        bytes_in += fs::metadata(&path)?.len();
+        bytes_out += fs::metadata(&compressed_path)?.len();
+        files += 1;
+        fs::remove_file(&path)?;
+        debug!(file = %compressed_path.display(), "Compressed stream file");
+    }
+    if files != 0 {
+        info!(
+            trace = %dir.display(),
+            files,
+            bytes_in,
+            bytes_out,
+            "Compressed the stream files"
+        );
+    }
+    Ok(())
+}
+
+/// Restore the stream files compressed by --compress so babeltrace can read the trace.
+/// Looks into the subdirectories too, e.g. the slices of the slice subcommand.
+pub fn decompress_streams(dir: &Path) -> io::Result<()> {
+    let mut files = 0;
+    for path in files_in(dir)? {
+        if path.is_dir() {
+            decompress_streams(&path)?;
+            continue;
+        }
+        if path.extension().is_none_or(|e| e != EXTENSION) {
+            continue;
+        }
+        let decompressed_path = path.with_extension("");
+        let mut output = BufWriter::new(File::create(&decompressed_path)?);
+        zstd::stream::copy_decode(BufReader::new(File::open(&path)?), &mut output)?;
+        output.flush()?;
+        fs::remove_file(&path)?;
+        files += 1;
+    }
+    if files != 0 {
+        info!(trace = %dir.display(), files, "Decompressed the stream files");
+    }
+    Ok(())
+}
+
+/// The files and directories of the directory, sorted
+fn files_in(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// The path with `.zst` appended, stream files may already have an extension
+fn with_extension(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(EXTENSION);
+    path.into()
+}
@@ -0,0 +1,109 @@
+use crate::clock_sync::ClockSyncRule;
+use crate::latency::LatencyBudget;
+use crate::markers::MarkerRule;
+use crate::task_groups::TaskGroupRule;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Converter configuration file (JSON)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Rules for marking notable events in the markers file
+    #[serde(default)]
+    pub markers: Vec<MarkerRule>,
+
+    /// Latency budgets measured for each occurrence
+    #[serde(default)]
+    pub latency_budgets: Vec<LatencyBudget>,
+
+    /// Recognizes the user events carrying a host wall-clock timestamp
+    #[serde(default)]
+    pub clock_sync: Option<ClockSyncRule>,
+
+    /// Constant packet context members in addition to cpu_id, e.g. a device or session ID
+    /// carrying the provenance of merged multi-device traces
+    #[serde(default)]
+    pub packet_context: BTreeMap<String, PacketContextValue>,
+
+    /// Static task name to core affinity, fills the sched_wakeup target_cpu field
+    /// (the trace events don't carry the core)
+    #[serde(default)]
+    pub task_affinity: BTreeMap<String, u32>,
+
+    /// Event type name to the index of the event parameter holding a caller (return) address,
+    /// for ports recording one
+    #[serde(default)]
+    pub caller_address: BTreeMap<String, usize>,
+
+    /// Task groups (e.g. subsystems) for the stats and the task state intervals
+    #[serde(default)]
+    pub task_groups: Vec<TaskGroupRule>,
+
+    /// Event class name (e.g. sched_switch or TASK_DELAY) to the name used in the output
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+
+    /// Event class name to its display names by language tag (e.g. en or de), stored as the
+    /// `display_names` user attribute of the class, its name stays the canonical one
+    #[serde(default)]
+    pub display_names: BTreeMap<String, BTreeMap<String, String>>,
+
+    /// User event channels carrying numeric telemetry, converted to `<channel>_value` events
+    #[serde(default)]
+    pub telemetry_channels: BTreeMap<String, TelemetryChannel>,
+}
+
+/// A user event channel carrying numeric telemetry
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelemetryChannel {
+    /// Unit of the values (e.g. degC or rpm), attached to the value field class
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+/// Constant packet context member value
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PacketContextValue {
+    Unsigned(u64),
+    Signed(i64),
+    String(String),
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let cfg: Self = serde_json::from_reader(reader)
+            .map_err(|e| format!("Invalid config file '{}'. {e}", path.display()))?;
+        if cfg.packet_context.contains_key("cpu_id") {
+            return Err(format!(
+                "Invalid config file '{}'. The cpu_id packet context member is reserved",
+                path.display()
+            )
+            .into());
+        }
+        if let Some((old, _)) = cfg.rename.iter().find(|(_, new)| new.is_empty()) {
+            return Err(format!(
+                "Invalid config file '{}'. The new name of '{old}' is empty",
+                path.display()
+            )
+            .into());
+        }
+        if let Some((class, lang)) = cfg.display_names.iter().find_map(|(class, names)| {
+            names
+                .iter()
+                .find(|(_, name)| name.is_empty())
+                .map(|(lang, _)| (class, lang))
+        }) {
+            return Err(format!(
+                "Invalid config file '{}'. The '{lang}' display name of '{class}' is empty",
+                path.display()
+            )
+            .into());
+        }
+        Ok(cfg)
+    }
+}
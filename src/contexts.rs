@@ -0,0 +1,119 @@
+use trace_recorder_parser::{
+    streaming::event::{IsrEvent, TaskEvent},
+    types::{ObjectHandle, ObjectName, Priority, STARTUP_TASK_NAME},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Context {
+    pub handle: ObjectHandle,
+    pub name: ObjectName,
+    pub priority: Priority,
+}
+
+impl From<TaskEvent> for Context {
+    fn from(value: TaskEvent) -> Self {
+        Self {
+            handle: value.handle,
+            name: value.name,
+            priority: value.priority,
+        }
+    }
+}
+
+impl From<IsrEvent> for Context {
+    fn from(value: IsrEvent) -> Self {
+        Self {
+            handle: value.handle,
+            name: value.name,
+            priority: value.priority,
+        }
+    }
+}
+
+/// Tracks the running task and the stack of ISRs preempting it
+#[derive(Debug)]
+pub struct ContextTracker {
+    active: Context,
+    /// Innermost last
+    pending_isrs: Vec<Context>,
+}
+
+impl Default for ContextTracker {
+    fn default() -> Self {
+        Self {
+            active: Context {
+                handle: ObjectHandle::NO_TASK,
+                name: STARTUP_TASK_NAME.to_string().into(),
+                priority: 0_u32.into(),
+            },
+            pending_isrs: Vec::new(),
+        }
+    }
+}
+
+impl ContextTracker {
+    /// The running task
+    pub fn active(&self) -> &Context {
+        &self.active
+    }
+
+    pub fn pending_isrs(&self) -> &[Context] {
+        &self.pending_isrs
+    }
+
+    /// The innermost pending ISR
+    pub fn current_isr(&self) -> Option<&Context> {
+        self.pending_isrs.last()
+    }
+
+    /// The innermost pending ISR, or the running task
+    pub fn current(&self) -> &Context {
+        self.pending_isrs.last().unwrap_or(&self.active)
+    }
+
+    pub fn in_isr(&self) -> bool {
+        !self.pending_isrs.is_empty()
+    }
+
+    pub fn isr_begin(&mut self, isr: Context) {
+        self.pending_isrs.push(isr);
+    }
+
+    /// A task resumed or activated, returns the ISR it returned from, if any
+    pub fn isr_returned(&mut self) -> Option<Context> {
+        self.pending_isrs.pop()
+    }
+
+    pub fn switch_to(&mut self, next: Context) {
+        self.active = next;
+    }
+
+    /// Drop the outermost pending ISRs beyond the innermost `keep`, e.g. of a trace missing
+    /// ISR end events, returns the number dropped
+    pub fn drop_outermost_isrs(&mut self, keep: usize) -> usize {
+        let dropped = self.pending_isrs.len().saturating_sub(keep);
+        self.pending_isrs.drain(..dropped);
+        self.pending_isrs.shrink_to_fit();
+        dropped
+    }
+
+    /// Estimated heap bytes of the ISR stack
+    pub fn estimated_size(&self) -> usize {
+        self.pending_isrs.capacity() * std::mem::size_of::<Context>()
+            + self
+                .pending_isrs
+                .iter()
+                .map(|c| c.name.len())
+                .sum::<usize>()
+    }
+
+    /// Return to the interrupted (nested) ISR, returns the ISR that ended.
+    /// Panics if no ISR is pending or the interrupted ISR isn't the previous one on the stack.
+    pub fn isr_resume(&mut self, previous: &Context) -> Context {
+        // This event indicates the previous ISR context before the active context,
+        // top of the stack contains the active context
+        let ctx = self.pending_isrs.pop().unwrap();
+        assert_eq!(Some(previous), self.pending_isrs.last());
+        ctx
+    }
+}
@@ -1,10 +1,56 @@
+use crate::anomaly::Anomaly;
+use crate::coalesce::Coalescer;
+use crate::config::{Config, TelemetryChannel};
+use crate::counters::ThroughputCounters;
+use crate::demangle::ItaniumDemangler;
 use crate::events::*;
-use crate::types::{BorrowedCtfState, Context, StringCache};
+use crate::latency::Violation;
+use crate::leaks::{Allocation, LeakTracker};
+use crate::memory::MemoryUsage;
+use crate::message_buffers::{MessageBufferTracker, MessageBufferUsage};
+use crate::mutex::MutexTracker;
+use crate::os_ticks::OsTickClock;
+use crate::prio::PrioMapping;
+use crate::sched_lock::SchedulerLockTracker;
+use crate::softirq::{SoftIrq, SoftIrqTracker};
+use crate::symbols::Symbols;
+use crate::task_groups::TaskGroups;
+use crate::task_state::{is_blocking_event, TaskStateIntervals, TaskStateTracker, TaskTransition};
+use crate::telemetry;
+use crate::types::{BorrowedCtfState, Interval, StringCache};
+use crate::units;
+use crate::user_args::{self, NamedArgs};
+use crate::Opts;
 use babeltrace2_sys::{ffi, BtResultExt, Error};
-use std::collections::{hash_map::Entry, HashMap};
-use std::ptr;
-use trace_recorder_parser::{streaming::event::*, time::Timestamp, types::*};
-use tracing::warn;
+use regex::Regex;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, VecDeque};
+use std::ffi::{CStr, CString};
+use std::{io, mem::size_of, path::Path, ptr};
+use trace_recorder_parser::{
+    streaming::event::*,
+    time::{Frequency, Timestamp},
+    types::*,
+};
+use trace_recorder_to_ctf::contexts::{Context, ContextTracker};
+use tracing::{debug, warn};
+
+/// Pending ISRs kept when shedding memory, deeper nesting means ISR end events are missing
+const MAX_PENDING_ISRS: usize = 32;
+
+/// First event class ID of the USER_EVENT classes with named arguments, see --named-user-args
+const NAMED_USER_EVENT_ID_BASE: u64 = 0x2000;
+
+/// First event class ID of the `<channel>_value` classes, in the config's telemetry_channels order
+const TELEMETRY_EVENT_ID_BASE: u64 = 0x3000;
+
+/// The converted event's identity and timing, for emit_typed_event
+struct EventMeta {
+    event_type: EventType,
+    event_id: EventId,
+    event_count: u64,
+    timestamp: Timestamp,
+    raw_timestamp: Timestamp,
+}
 
 pub struct TrcCtfConverter {
     unknown_event_class: *mut ffi::bt_event_class,
@@ -13,10 +59,105 @@ pub struct TrcCtfConverter {
     irq_handler_entry_event_class: *mut ffi::bt_event_class,
     irq_handler_exit_event_class: *mut ffi::bt_event_class,
     sched_wakeup_event_class: *mut ffi::bt_event_class,
+    task_state_change_event_class: *mut ffi::bt_event_class,
+    heartbeat_event_class: *mut ffi::bt_event_class,
+    anomaly_event_class: *mut ffi::bt_event_class,
+    clock_correlation_event_class: *mut ffi::bt_event_class,
+    softirq_raise_event_class: *mut ffi::bt_event_class,
+    softirq_entry_event_class: *mut ffi::bt_event_class,
+    softirq_exit_event_class: *mut ffi::bt_event_class,
+    latency_violation_event_class: *mut ffi::bt_event_class,
+    coalesced_events_event_class: *mut ffi::bt_event_class,
+    selftest_event_class: *mut ffi::bt_event_class,
+    leak_candidate_event_class: *mut ffi::bt_event_class,
+    handle_name_event_class: *mut ffi::bt_event_class,
+    task_newtask_event_class: *mut ffi::bt_event_class,
+    priority_inheritance_event_class: *mut ffi::bt_event_class,
+    sched_process_fork_event_class: *mut ffi::bt_event_class,
+    sched_process_exit_event_class: *mut ffi::bt_event_class,
+    sched_process_free_event_class: *mut ffi::bt_event_class,
+    sched_pi_setprio_event_class: *mut ffi::bt_event_class,
     event_classes: HashMap<EventType, *mut ffi::bt_event_class>,
     string_cache: StringCache,
-    active_context: Context,
-    pending_isrs: Vec<Context>,
+    contexts: ContextTracker,
+    task_state_events: bool,
+    heartbeat_events: bool,
+    anomaly_events: bool,
+    clock_correlation_events: bool,
+    latency_violation_events: bool,
+    softirq_events: bool,
+    selftest_events: bool,
+    leak_candidate_events: bool,
+    handle_table_events: bool,
+    /// Task and ISR handles left to emit as handle_name events, taken at the end of the trace
+    handle_table: Option<VecDeque<(ContextKind, ObjectHandle, ObjectName)>>,
+    task_newtask_events: bool,
+    process_lifecycle_events: bool,
+    /// Pairs the memory allocations and frees, when a leak report or events are requested
+    leaks: Option<LeakTracker>,
+    /// Timestamp of the last converted event
+    last_timestamp: Timestamp,
+    softirqs: SoftIrqTracker,
+    coalescer: Coalescer,
+    /// Add the sample_weight common context field
+    sample_weights: bool,
+    /// Occurrences the event being converted stands for, see sampling.rs
+    sample_weight: u64,
+    /// Add the in_irq and irq_name common context fields
+    irq_context_fields: bool,
+    /// ISR the event being converted was emitted in, see irq_context_fields
+    irq_name: Option<ObjectName>,
+    /// Extracts the severity from user event channel names
+    channel_severity_regex: Option<Regex>,
+    channel_log_levels: HashMap<String, LogLevel>,
+    /// Leave the user event formatted_string empty, it's redundant with format_string and args
+    omit_formatted_string: bool,
+    /// Set by --named-user-args
+    named_user_args: bool,
+    /// USER_EVENT classes with named arguments by format string, None when the format string
+    /// doesn't name all of its arguments
+    named_user_event_classes: HashMap<String, Option<(*mut ffi::bt_event_class, NamedArgs)>>,
+    named_user_event_class_count: u64,
+    telemetry_channels: BTreeMap<String, TelemetryChannel>,
+    /// `<channel>_value` event classes by channel
+    telemetry_event_classes: HashMap<String, *mut ffi::bt_event_class>,
+    prio_mapping: PrioMapping,
+    task_affinity: BTreeMap<String, u32>,
+    scheduler_locks: SchedulerLockTracker,
+    mutexes: MutexTracker,
+    message_buffers: MessageBufferTracker,
+    /// Timer periods by handle, from TIMER_CREATE and TIMER_CHANGE_PERIOD*
+    timer_periods: HashMap<ObjectHandle, u32>,
+    /// Event type name to the index of the parameter holding a caller address
+    caller_address: BTreeMap<String, usize>,
+    /// The caller address parameter index of each event type seen so far, saves formatting
+    /// the name per event
+    caller_address_types: HashMap<EventType, Option<usize>>,
+    /// Event class name to the name used in the output
+    rename: BTreeMap<String, String>,
+    /// Event class name to its display names by language tag
+    display_names: BTreeMap<String, BTreeMap<String, String>>,
+    symbols: Option<Symbols>,
+    /// Emits the tick-referenced events against the secondary os_ticks clock
+    os_tick_clock: Option<OsTickClock>,
+    /// Entry timestamps of the pending ISRs
+    isr_start_times: Vec<Timestamp>,
+    /// The last ISR execution, once it ends
+    isr_execution: Option<Interval>,
+    task_states: TaskStateTracker,
+    /// Task names by handle, for the RUNTIME_STATS snapshots
+    task_names: HashMap<ObjectHandle, ObjectName>,
+    /// Task priorities by handle, for the sched_process_exit/free and sched_pi_setprio events
+    task_priorities: HashMap<ObjectHandle, Priority>,
+    /// Non-task object names by handle, from the create and OBJECT_NAME events, for the events
+    /// referring to an object by handle only (e.g. the *_DELETE and timer events)
+    object_names: HashMap<ObjectHandle, ObjectName>,
+    isr_names: HashMap<ObjectHandle, ObjectName>,
+    /// Set by --midrun-capture, ISRs resumed before the first ISR entry were entered before the
+    /// capture started
+    midrun_capture: bool,
+    isr_begin_observed: bool,
+    task_state_intervals: Option<TaskStateIntervals>,
 }
 
 impl Drop for TrcCtfConverter {
@@ -25,6 +166,30 @@ impl Drop for TrcCtfConverter {
             for (_, event_class) in self.event_classes.drain() {
                 ffi::bt_event_class_put_ref(event_class);
             }
+            for (event_class, _) in self.named_user_event_classes.drain().flat_map(|(_, c)| c) {
+                ffi::bt_event_class_put_ref(event_class);
+            }
+            for (_, event_class) in self.telemetry_event_classes.drain() {
+                ffi::bt_event_class_put_ref(event_class);
+            }
+            ffi::bt_event_class_put_ref(self.sched_pi_setprio_event_class);
+            ffi::bt_event_class_put_ref(self.sched_process_free_event_class);
+            ffi::bt_event_class_put_ref(self.sched_process_exit_event_class);
+            ffi::bt_event_class_put_ref(self.sched_process_fork_event_class);
+            ffi::bt_event_class_put_ref(self.priority_inheritance_event_class);
+            ffi::bt_event_class_put_ref(self.task_newtask_event_class);
+            ffi::bt_event_class_put_ref(self.handle_name_event_class);
+            ffi::bt_event_class_put_ref(self.leak_candidate_event_class);
+            ffi::bt_event_class_put_ref(self.selftest_event_class);
+            ffi::bt_event_class_put_ref(self.coalesced_events_event_class);
+            ffi::bt_event_class_put_ref(self.latency_violation_event_class);
+            ffi::bt_event_class_put_ref(self.softirq_exit_event_class);
+            ffi::bt_event_class_put_ref(self.softirq_entry_event_class);
+            ffi::bt_event_class_put_ref(self.softirq_raise_event_class);
+            ffi::bt_event_class_put_ref(self.clock_correlation_event_class);
+            ffi::bt_event_class_put_ref(self.anomaly_event_class);
+            ffi::bt_event_class_put_ref(self.heartbeat_event_class);
+            ffi::bt_event_class_put_ref(self.task_state_change_event_class);
             ffi::bt_event_class_put_ref(self.sched_wakeup_event_class);
             ffi::bt_event_class_put_ref(self.irq_handler_entry_event_class);
             ffi::bt_event_class_put_ref(self.irq_handler_exit_event_class);
@@ -36,7 +201,12 @@ impl Drop for TrcCtfConverter {
 }
 
 impl TrcCtfConverter {
-    pub fn new() -> Self {
+    pub fn new(
+        opts: &Opts,
+        config: &Config,
+        symbols: Option<Symbols>,
+        os_tick_clock: Option<OsTickClock>,
+    ) -> Self {
         Self {
             unknown_event_class: ptr::null_mut(),
             user_event_class: ptr::null_mut(),
@@ -44,14 +214,85 @@ impl TrcCtfConverter {
             irq_handler_entry_event_class: ptr::null_mut(),
             irq_handler_exit_event_class: ptr::null_mut(),
             sched_wakeup_event_class: ptr::null_mut(),
+            task_state_change_event_class: ptr::null_mut(),
+            heartbeat_event_class: ptr::null_mut(),
+            anomaly_event_class: ptr::null_mut(),
+            clock_correlation_event_class: ptr::null_mut(),
+            softirq_raise_event_class: ptr::null_mut(),
+            softirq_entry_event_class: ptr::null_mut(),
+            softirq_exit_event_class: ptr::null_mut(),
+            latency_violation_event_class: ptr::null_mut(),
+            coalesced_events_event_class: ptr::null_mut(),
+            selftest_event_class: ptr::null_mut(),
+            leak_candidate_event_class: ptr::null_mut(),
+            handle_name_event_class: ptr::null_mut(),
+            task_newtask_event_class: ptr::null_mut(),
+            priority_inheritance_event_class: ptr::null_mut(),
+            sched_process_fork_event_class: ptr::null_mut(),
+            sched_process_exit_event_class: ptr::null_mut(),
+            sched_process_free_event_class: ptr::null_mut(),
+            sched_pi_setprio_event_class: ptr::null_mut(),
             event_classes: Default::default(),
-            string_cache: Default::default(),
-            active_context: Context {
-                handle: ObjectHandle::NO_TASK,
-                name: STARTUP_TASK_NAME.to_string().into(),
-                priority: 0_u32.into(),
+            string_cache: if opts.demangle {
+                StringCache::with_demangler(Box::new(ItaniumDemangler))
+            } else {
+                Default::default()
             },
-            pending_isrs: Default::default(),
+            contexts: Default::default(),
+            task_state_events: opts.task_state_events,
+            heartbeat_events: opts.heartbeat_events.is_some() || opts.heartbeat_megabytes.is_some(),
+            anomaly_events: opts.anomaly_events,
+            clock_correlation_events: config.clock_sync.is_some(),
+            latency_violation_events: !config.latency_budgets.is_empty(),
+            softirq_events: opts.softirq_events,
+            selftest_events: opts.inject_selftest,
+            leak_candidate_events: opts.leak_candidate_events,
+            handle_table_events: opts.handle_table_events,
+            handle_table: None,
+            task_newtask_events: opts.task_newtask_events,
+            process_lifecycle_events: opts.process_lifecycle_events,
+            leaks: (opts.leak_report.is_some() || opts.leak_candidate_events)
+                .then(LeakTracker::default),
+            last_timestamp: Timestamp::zero(),
+            softirqs: Default::default(),
+            coalescer: Coalescer::new(&opts.coalesce),
+            sample_weights: !opts.sample.is_empty(),
+            sample_weight: 1,
+            irq_context_fields: opts.irq_context,
+            irq_name: None,
+            channel_severity_regex: opts.channel_severity_regex.clone(),
+            channel_log_levels: Default::default(),
+            omit_formatted_string: opts.omit_formatted_string,
+            named_user_args: opts.named_user_args,
+            named_user_event_classes: Default::default(),
+            named_user_event_class_count: 0,
+            telemetry_channels: config.telemetry_channels.clone(),
+            telemetry_event_classes: Default::default(),
+            prio_mapping: opts.prio_mapping,
+            task_affinity: config.task_affinity.clone(),
+            scheduler_locks: SchedulerLockTracker::default(),
+            mutexes: MutexTracker::default(),
+            message_buffers: MessageBufferTracker::default(),
+            timer_periods: Default::default(),
+            caller_address: config.caller_address.clone(),
+            caller_address_types: Default::default(),
+            rename: config.rename.clone(),
+            display_names: config.display_names.clone(),
+            symbols,
+            os_tick_clock,
+            isr_start_times: Vec::new(),
+            isr_execution: None,
+            task_states: Default::default(),
+            task_names: Default::default(),
+            task_priorities: Default::default(),
+            object_names: Default::default(),
+            isr_names: Default::default(),
+            midrun_capture: opts.midrun_capture,
+            isr_begin_observed: false,
+            task_state_intervals: opts
+                .task_state_intervals
+                .as_ref()
+                .map(|_| Default::default()),
         }
     }
 
@@ -61,7 +302,7 @@ impl TrcCtfConverter {
     ) -> Result<*mut ffi::bt_field_class, Error> {
         unsafe {
             // Create common event context
-            // event ID, event type, event count, timer ticks
+            // event ID, event type, event count, timer ticks[, sample weight][, in IRQ, IRQ name]
             let base_event_context = ffi::bt_field_class_structure_create(trace_class);
 
             let event_id_field = ffi::bt_field_class_integer_unsigned_create(trace_class);
@@ -85,6 +326,7 @@ impl TrcCtfConverter {
             ret.capi_result()?;
 
             let timer_field = ffi::bt_field_class_integer_unsigned_create(trace_class);
+            units::set_unit(timer_field, "timer_ticks")?;
             let ret = ffi::bt_field_class_structure_append_member(
                 base_event_context,
                 b"timer\0".as_ptr() as _,
@@ -92,6 +334,38 @@ impl TrcCtfConverter {
             );
             ret.capi_result()?;
 
+            if self.sample_weights {
+                let sample_weight_field = ffi::bt_field_class_integer_unsigned_create(trace_class);
+                let ret = ffi::bt_field_class_structure_append_member(
+                    base_event_context,
+                    b"sample_weight\0".as_ptr() as _,
+                    sample_weight_field,
+                );
+                ret.capi_result()?;
+                ffi::bt_field_class_put_ref(sample_weight_field);
+            }
+
+            if self.irq_context_fields {
+                let in_irq_field = ffi::bt_field_class_integer_unsigned_create(trace_class);
+                ffi::bt_field_class_integer_set_field_value_range(in_irq_field, 8);
+                let ret = ffi::bt_field_class_structure_append_member(
+                    base_event_context,
+                    b"in_irq\0".as_ptr() as _,
+                    in_irq_field,
+                );
+                ret.capi_result()?;
+                ffi::bt_field_class_put_ref(in_irq_field);
+
+                let irq_name_field = ffi::bt_field_class_string_create(trace_class);
+                let ret = ffi::bt_field_class_structure_append_member(
+                    base_event_context,
+                    b"irq_name\0".as_ptr() as _,
+                    irq_name_field,
+                );
+                ret.capi_result()?;
+                ffi::bt_field_class_put_ref(irq_name_field);
+            }
+
             ffi::bt_field_class_put_ref(timer_field);
             ffi::bt_field_class_put_ref(event_count_field);
             ffi::bt_field_class_put_ref(event_id_field);
@@ -105,11 +379,74 @@ impl TrcCtfConverter {
     pub fn create_event_classes(&mut self, stream: *mut ffi::bt_stream) -> Result<(), Error> {
         let stream_class = unsafe { ffi::bt_stream_borrow_class(stream) };
         self.unknown_event_class = Unknown::event_class(stream_class)?;
-        self.user_event_class = User::event_class(stream_class)?;
+        self.user_event_class = if self.channel_severity_regex.is_some() {
+            UserLog::event_class(stream_class)?
+        } else {
+            User::event_class(stream_class)?
+        };
         self.sched_switch_event_class = SchedSwitch::event_class(stream_class)?;
         self.irq_handler_entry_event_class = IrqHandlerEntry::event_class(stream_class)?;
         self.irq_handler_exit_event_class = IrqHandlerExit::event_class(stream_class)?;
         self.sched_wakeup_event_class = SchedWakeup::event_class(stream_class)?;
+        self.sched_pi_setprio_event_class = SchedPiSetprio::event_class(stream_class)?;
+        self.priority_inheritance_event_class = PriorityInheritance::event_class(stream_class)?;
+        if self.task_state_events {
+            self.task_state_change_event_class = TaskStateChange::event_class(stream_class)?;
+        }
+        if self.heartbeat_events {
+            self.heartbeat_event_class = ConverterHeartbeat::event_class(stream_class)?;
+        }
+        if self.anomaly_events {
+            self.anomaly_event_class = EventRateAnomaly::event_class(stream_class)?;
+        }
+        if self.clock_correlation_events {
+            self.clock_correlation_event_class = ClockCorrelation::event_class(stream_class)?;
+        }
+        if self.latency_violation_events {
+            self.latency_violation_event_class = LatencyViolation::event_class(stream_class)?;
+        }
+        if self.softirq_events {
+            self.softirq_raise_event_class = SoftIrqRaise::event_class(stream_class)?;
+            self.softirq_entry_event_class = SoftIrqEntry::event_class(stream_class)?;
+            self.softirq_exit_event_class = SoftIrqExit::event_class(stream_class)?;
+        }
+        if self.coalescer.is_enabled() {
+            self.coalesced_events_event_class = CoalescedEvents::event_class(stream_class)?;
+        }
+        if self.selftest_events {
+            self.selftest_event_class = SelfTest::event_class(stream_class)?;
+        }
+        if self.leak_candidate_events {
+            self.leak_candidate_event_class = LeakCandidate::event_class(stream_class)?;
+        }
+        if self.handle_table_events {
+            self.handle_name_event_class = HandleName::event_class(stream_class)?;
+        }
+        if self.task_newtask_events {
+            self.task_newtask_event_class = TaskNewtask::event_class(stream_class)?;
+        }
+        if self.process_lifecycle_events {
+            self.sched_process_fork_event_class = SchedProcessFork::event_class(stream_class)?;
+            self.sched_process_exit_event_class = SchedProcessExit::event_class(stream_class)?;
+            self.sched_process_free_event_class = SchedProcessFree::event_class(stream_class)?;
+        }
+        for (idx, (channel, telemetry_channel)) in self.telemetry_channels.iter().enumerate() {
+            let event_class = telemetry::event_class(
+                TELEMETRY_EVENT_ID_BASE + idx as u64,
+                channel,
+                telemetry_channel,
+                stream_class,
+            )?;
+            self.telemetry_event_classes
+                .insert(channel.clone(), event_class);
+        }
+        if !self.rename.is_empty() || !self.display_names.is_empty() {
+            for i in 0..unsafe { ffi::bt_stream_class_get_event_class_count(stream_class) } {
+                let event_class =
+                    unsafe { ffi::bt_stream_class_borrow_event_class_by_index(stream_class, i) };
+                name_event_class(&self.rename, &self.display_names, event_class)?;
+            }
+        }
         Ok(())
     }
 
@@ -135,10 +472,65 @@ impl TrcCtfConverter {
                 ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, 2);
             ffi::bt_field_integer_unsigned_set_value(timer_field, timer.ticks());
 
+            let mut index = 3;
+            if self.sample_weights {
+                let sample_weight_field =
+                    ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, index);
+                ffi::bt_field_integer_unsigned_set_value(sample_weight_field, self.sample_weight);
+                index += 1;
+            }
+
+            if self.irq_context_fields {
+                let in_irq_field =
+                    ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, index);
+                ffi::bt_field_integer_unsigned_set_value(
+                    in_irq_field,
+                    self.irq_name.is_some() as _,
+                );
+                let irq_name_field = ffi::bt_field_structure_borrow_member_field_by_index(
+                    common_ctx_field,
+                    index + 1,
+                );
+                let irq_name = match &self.irq_name {
+                    Some(name) => {
+                        self.string_cache.insert_str(name)?;
+                        self.string_cache.get_str(name)
+                    }
+                    None => Default::default(),
+                };
+                let ret = ffi::bt_field_string_set_value(irq_name_field, irq_name.as_ptr());
+                ret.capi_result()?;
+            }
+
             Ok(())
         }
     }
 
+    /// Create the message of a tick-referenced event (e.g. TASK_DELAY), on the os_ticks clock
+    /// stream when there's a secondary clock
+    fn create_tick_referenced_message<F>(
+        &mut self,
+        stream_class: *mut ffi::bt_stream_class,
+        event_type: EventType,
+        tracked_timestamp: Timestamp,
+        ctf_state: &mut BorrowedCtfState,
+        f: F,
+    ) -> Result<*mut ffi::bt_message, Error>
+    where
+        F: FnOnce(*mut ffi::bt_stream_class) -> Result<*mut ffi::bt_event_class, Error>,
+    {
+        match (self.os_tick_clock, ctf_state.tick_stream_class_mut()) {
+            (Some(clock), Some(tick_stream_class)) => {
+                let event_class = self.event_class(tick_stream_class, event_type, f)?;
+                Ok(ctf_state.create_tick_message(event_class, clock.os_ticks(tracked_timestamp)))
+            }
+            _ => {
+                let event_class = self.event_class(stream_class, event_type, f)?;
+                Ok(ctf_state.create_message(event_class, tracked_timestamp))
+            }
+        }
+    }
+
     fn event_class<F>(
         &mut self,
         stream_class: *mut ffi::bt_stream_class,
@@ -151,27 +543,699 @@ impl TrcCtfConverter {
         let event_class_ref = match self.event_classes.entry(event_type) {
             Entry::Occupied(o) => o.into_mut(),
             Entry::Vacant(v) => {
-                let event_class = f(stream_class)?;
+                let event_class =
+                    name_event_class(&self.rename, &self.display_names, f(stream_class)?)?;
                 v.insert(event_class)
             }
         };
         Ok(*event_class_ref as *const _)
     }
 
+    /// Emit an event of a class named from the event type, the class is created on first use
+    /// and `emit` sets the event's payload
+    fn emit_typed_event<C, F>(
+        &mut self,
+        ctf_state: &mut BorrowedCtfState,
+        meta: &EventMeta,
+        create_event_class: C,
+        emit: F,
+    ) -> Result<(), Error>
+    where
+        C: FnOnce(
+            EventType,
+            EventId,
+            *mut ffi::bt_stream_class,
+        ) -> Result<*mut ffi::bt_event_class, Error>,
+        F: FnOnce(&mut Self, *mut ffi::bt_event) -> Result<(), Error>,
+    {
+        let stream_class = unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
+        let event_class = self.event_class(stream_class, meta.event_type, |stream_class| {
+            create_event_class(meta.event_type, meta.event_id, stream_class)
+        })?;
+        let msg = ctf_state.create_message(event_class, meta.timestamp);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(
+            meta.event_id,
+            meta.event_count,
+            meta.raw_timestamp,
+            ctf_event,
+        )?;
+        emit(self, ctf_event)?;
+        ctf_state.push_message(msg)
+    }
+
+    /// Returns the `<channel>_value` event class and the value of a numeric telemetry
+    /// channel's user event
+    fn telemetry_value(&self, ev: &UserEvent) -> Option<(*const ffi::bt_event_class, f64)> {
+        if self.telemetry_event_classes.is_empty() {
+            return None;
+        }
+        let channel = match &ev.channel {
+            UserEventChannel::Default => UserEventChannel::DEFAULT,
+            UserEventChannel::Custom(c) => c.as_str(),
+        };
+        let event_class = *self.telemetry_event_classes.get(channel)?;
+        match telemetry::value(&ev.args) {
+            Some(value) => Some((event_class as *const _, value)),
+            None => {
+                debug!(
+                    channel,
+                    "Telemetry channel user event without a numeric argument, kept as a USER_EVENT"
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the USER_EVENT class with named arguments for the event's format string,
+    /// created on first use, or None if the format string doesn't name all of its arguments
+    fn named_user_event_class(
+        &mut self,
+        stream_class: *mut ffi::bt_stream_class,
+        ev: &UserEvent,
+    ) -> Result<Option<*const ffi::bt_event_class>, Error> {
+        if !self
+            .named_user_event_classes
+            .contains_key(&*ev.format_string)
+        {
+            let named = match user_args::arg_names(&ev.format_string) {
+                Some(names) if names.len() == ev.args.len() => {
+                    let mut named_args = NamedArgs::new(&names)?;
+                    let event_id = NAMED_USER_EVENT_ID_BASE + self.named_user_event_class_count;
+                    let append = |trace_class, payload_fc| {
+                        named_args.append_members(&ev.args, trace_class, payload_fc)
+                    };
+                    let event_class = if self.channel_severity_regex.is_some() {
+                        UserLog::event_class_with_payload_members(event_id, stream_class, append)?
+                    } else {
+                        User::event_class_with_payload_members(event_id, stream_class, append)?
+                    };
+                    let event_class =
+                        name_event_class(&self.rename, &self.display_names, event_class)?;
+                    self.named_user_event_class_count += 1;
+                    debug!(
+                        event_id,
+                        ?names,
+                        "Created a USER_EVENT class with named arguments for '{}'",
+                        &*ev.format_string
+                    );
+                    Some((event_class, named_args))
+                }
+                _ => None,
+            };
+            self.named_user_event_classes
+                .insert(ev.format_string.to_string(), named);
+        }
+        // The argument types follow the format string, but a mismatch would set the
+        // wrong field types
+        Ok(self
+            .named_user_event_classes
+            .get(&*ev.format_string)
+            .and_then(Option::as_ref)
+            .filter(|(_, named_args)| named_args.matches(&ev.args))
+            .map(|(event_class, _)| *event_class as *const _))
+    }
+
+    pub fn emit_heartbeat(
+        &mut self,
+        event_code: EventCode,
+        tracked_event_count: u64,
+        tracked_timestamp: Timestamp,
+        raw_timestamp: Timestamp,
+        counters: &ThroughputCounters,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<(), Error> {
+        let event_class = self.heartbeat_event_class;
+        let msg = ctf_state.create_message(event_class, tracked_timestamp);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(
+            event_code.event_id(),
+            tracked_event_count,
+            raw_timestamp,
+            ctf_event,
+        )?;
+        ConverterHeartbeat::from(counters).emit_event(ctf_event)?;
+        ctf_state.push_message(msg)?;
+        Ok(())
+    }
+
+    pub fn emit_anomaly(
+        &mut self,
+        event_code: EventCode,
+        tracked_event_count: u64,
+        tracked_timestamp: Timestamp,
+        raw_timestamp: Timestamp,
+        anomaly: &Anomaly,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<(), Error> {
+        if !self.anomaly_events {
+            return Ok(());
+        }
+        let event_class = self.anomaly_event_class;
+        let msg = ctf_state.create_message(event_class, tracked_timestamp);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(
+            event_code.event_id(),
+            tracked_event_count,
+            raw_timestamp,
+            ctf_event,
+        )?;
+        EventRateAnomaly::try_from((anomaly, &mut self.string_cache))?.emit_event(ctf_event)?;
+        ctf_state.push_message(msg)?;
+        Ok(())
+    }
+
+    /// Emit the fixed selftest events at the start of the stream
+    pub fn emit_selftest(&mut self, ctf_state: &mut BorrowedCtfState) -> Result<(), Error> {
+        if !self.selftest_events {
+            return Ok(());
+        }
+        let timestamp = Timestamp::from(0);
+        for selftest in SelfTest::sequence().iter() {
+            let event_class = self.selftest_event_class;
+            let msg = ctf_state.create_message(event_class, timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(EventId(0), 0, timestamp, ctf_event)?;
+            selftest.emit_event(ctf_event)?;
+            ctf_state.push_message(msg)?;
+        }
+        Ok(())
+    }
+
+    /// Emit the coalesced run of events, unless the next event continues it
+    pub fn emit_coalesced_events(
+        &mut self,
+        next: Option<EventType>,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<(), Error> {
+        let run = match self.coalescer.take_finished(next) {
+            Some(run) => run,
+            None => return Ok(()),
+        };
+        let event_class = self.coalesced_events_event_class;
+        let msg = ctf_state.create_message(event_class, run.first_timestamp);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(
+            run.event_code.event_id(),
+            run.event_count,
+            run.raw_timestamp,
+            ctf_event,
+        )?;
+        CoalescedEvents::try_from((&run, &mut self.string_cache))?.emit_event(ctf_event)?;
+        ctf_state.push_message(msg)?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn emit_clock_correlation(
+        &mut self,
+        event_code: EventCode,
+        tracked_event_count: u64,
+        tracked_timestamp: Timestamp,
+        raw_timestamp: Timestamp,
+        host_time_ns: u64,
+        offset_ns: i64,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<(), Error> {
+        let event_class = self.clock_correlation_event_class;
+        let msg = ctf_state.create_message(event_class, tracked_timestamp);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(
+            event_code.event_id(),
+            tracked_event_count,
+            raw_timestamp,
+            ctf_event,
+        )?;
+        ClockCorrelation {
+            host_time_ns,
+            offset_ns,
+        }
+        .emit_event(ctf_event)?;
+        ctf_state.push_message(msg)?;
+        Ok(())
+    }
+
+    pub fn emit_latency_violation(
+        &mut self,
+        event_code: EventCode,
+        tracked_event_count: u64,
+        tracked_timestamp: Timestamp,
+        raw_timestamp: Timestamp,
+        violation: &Violation,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<(), Error> {
+        let event_class = self.latency_violation_event_class;
+        let msg = ctf_state.create_message(event_class, tracked_timestamp);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(
+            event_code.event_id(),
+            tracked_event_count,
+            raw_timestamp,
+            ctf_event,
+        )?;
+        LatencyViolation::try_from((violation, &mut self.string_cache))?.emit_event(ctf_event)?;
+        ctf_state.push_message(msg)?;
+        Ok(())
+    }
+
+    /// The task (or ISR) running, and the pending ISRs (innermost last), for the error snapshot
+    pub fn contexts(&self) -> (&Context, &[Context]) {
+        (self.contexts.active(), self.contexts.pending_isrs())
+    }
+
+    /// Estimated heap bytes of the converter state growing with the trace
+    pub fn memory_usage(&self, usage: &mut MemoryUsage) {
+        usage.string_cache = self.string_cache.estimated_size();
+        usage.isr_stack = self.contexts.estimated_size()
+            + self.isr_start_times.capacity() * size_of::<Timestamp>();
+        usage.leaks = self.leaks.as_ref().map_or(0, LeakTracker::estimated_size);
+        usage.task_state_intervals = self
+            .task_state_intervals
+            .as_ref()
+            .map_or(0, TaskStateIntervals::estimated_size);
+        usage.object_names = [&self.task_names, &self.object_names, &self.isr_names]
+            .into_iter()
+            .flat_map(|names| names.values())
+            .map(|name| size_of::<(ObjectHandle, ObjectName)>() + name.len())
+            .sum();
+    }
+
+    /// Drop the state that can be dropped to get back within the memory budget: the string cache,
+    /// the outermost pending ISRs beyond MAX_PENDING_ISRS and the oldest half of the unfreed
+    /// allocations
+    pub fn shed_memory(&mut self) {
+        self.string_cache.clear();
+        let dropped_isrs = self.contexts.drop_outermost_isrs(MAX_PENDING_ISRS);
+        if dropped_isrs != 0 {
+            let dropped_start_times = self.isr_start_times.len().saturating_sub(MAX_PENDING_ISRS);
+            self.isr_start_times.drain(..dropped_start_times);
+            warn!(
+                isrs = dropped_isrs,
+                "Dropped the outermost pending ISRs to stay within the memory budget"
+            );
+        }
+        if let Some(leaks) = self.leaks.as_mut() {
+            let dropped = leaks.shed_oldest();
+            if dropped != 0 {
+                warn!(
+                    allocations = dropped,
+                    "Stopped tracking the oldest unfreed allocations to stay within the memory budget, \
+                    the leak report is incomplete"
+                );
+            }
+        }
+    }
+
+    /// The last scheduler-locked interval, once it ends
+    pub fn take_scheduler_lock(&mut self) -> Option<Interval> {
+        self.scheduler_locks.take_finished()
+    }
+
+    /// The last ISR execution (including nested ISRs), once it ends
+    pub fn take_isr_execution(&mut self) -> Option<Interval> {
+        self.isr_execution.take()
+    }
+
+    /// Name of the object, from the event or the object's create event
+    fn object_name(&self, handle: ObjectHandle, name: &Option<ObjectName>) -> ObjectName {
+        name.clone()
+            .or_else(|| self.object_names.get(&handle).cloned())
+            .unwrap_or_else(|| UNNAMED_OBJECT.to_string().into())
+    }
+
+    /// The caller address parameter of the event, if configured
+    fn caller_address(&mut self, event_type: EventType, event: &BaseEvent) -> Option<u32> {
+        if self.caller_address.is_empty() {
+            return None;
+        }
+        let idx = match self.caller_address_types.get(&event_type) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.caller_address.get(&event_type.to_string()).copied();
+                self.caller_address_types.insert(event_type, idx);
+                idx
+            }
+        }?;
+        event.parameters().get(idx).copied()
+    }
+
+    fn isr_exited(&mut self, isr: &Context, end: Timestamp) {
+        if let Some(start) = self.isr_start_times.pop() {
+            self.isr_execution = Some(Interval {
+                name: isr.name.clone(),
+                start,
+                end,
+            });
+        }
+    }
+
+    pub fn write_leak_report(&self, path: &Path, timer_frequency: Frequency) -> io::Result<()> {
+        if let Some(leaks) = self.leaks.as_ref() {
+            leaks.write(path, timer_frequency)?;
+        }
+        Ok(())
+    }
+
+    /// Emit a leak_candidate event for each allocation not freed by the end of the trace,
+    /// as many as fit while leaving room for `reserved` messages.
+    /// Returns true once they're all emitted.
+    pub fn emit_leak_candidates(
+        &mut self,
+        reserved: usize,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<bool, Error> {
+        if !self.leak_candidate_events {
+            return Ok(true);
+        }
+        while ctf_state.message_count() == 0 || ctf_state.capacity_left() > reserved {
+            let allocation = match self.leaks.as_mut().and_then(|l| l.next_candidate()) {
+                Some(allocation) => allocation,
+                None => return Ok(true),
+            };
+            let event_class = self.leak_candidate_event_class;
+            let msg = ctf_state.create_message(event_class, self.last_timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(
+                allocation.event_code.event_id(),
+                allocation.event_count,
+                allocation.raw_timestamp,
+                ctf_event,
+            )?;
+            LeakCandidate::try_from((&allocation, &mut self.string_cache))?
+                .emit_event(ctf_event)?;
+            ctf_state.push_message(msg)?;
+        }
+        Ok(false)
+    }
+
+    /// Emit a handle_name event for each task and ISR seen, as many as fit while leaving room
+    /// for `reserved` messages. Returns true once all of them are emitted.
+    pub fn emit_handle_table(
+        &mut self,
+        reserved: usize,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<bool, Error> {
+        if !self.handle_table_events {
+            return Ok(true);
+        }
+        if self.handle_table.is_none() {
+            let tasks = self
+                .task_names
+                .iter()
+                .map(|(h, n)| (ContextKind::Task, *h, n.clone()));
+            let isrs = self
+                .isr_names
+                .iter()
+                .map(|(h, n)| (ContextKind::Isr, *h, n.clone()));
+            let mut table: Vec<_> = tasks.chain(isrs).collect();
+            table.sort_by_key(|(kind, handle, _)| (*kind, *handle));
+            self.handle_table = Some(table.into());
+        }
+        while ctf_state.message_count() == 0 || ctf_state.capacity_left() > reserved {
+            let (kind, handle, name) = match self.handle_table.as_mut().and_then(|t| t.pop_front())
+            {
+                Some(entry) => entry,
+                None => return Ok(true),
+            };
+            let event_class = self.handle_name_event_class;
+            let msg = ctf_state.create_message(event_class, self.last_timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(EventId(0), 0, self.last_timestamp, ctf_event)?;
+            HandleName::try_from((kind, handle, &name, &mut self.string_cache))?
+                .emit_event(ctf_event)?;
+            ctf_state.push_message(msg)?;
+        }
+        Ok(false)
+    }
+
+    pub fn write_task_state_intervals(
+        &mut self,
+        path: &Path,
+        timer_frequency: Frequency,
+        task_groups: &TaskGroups,
+    ) -> io::Result<()> {
+        if let Some(intervals) = self.task_state_intervals.as_mut() {
+            intervals.write(path, timer_frequency, task_groups)?;
+        }
+        Ok(())
+    }
+
+    /// Log level of a user event channel, from the severity regex match
+    fn channel_log_level(&mut self, channel: &UserEventChannel) -> LogLevel {
+        let channel = match channel {
+            UserEventChannel::Default => UserEventChannel::DEFAULT,
+            UserEventChannel::Custom(c) => c.as_str(),
+        };
+        if let Some(log_level) = self.channel_log_levels.get(channel) {
+            return *log_level;
+        }
+        let log_level = self
+            .channel_severity_regex
+            .as_ref()
+            .and_then(|re| re.captures(channel))
+            .and_then(|caps| {
+                caps.name("level")
+                    .or_else(|| caps.get(1))
+                    .or_else(|| caps.get(0))
+            })
+            .map(|m| LogLevel::from_name(m.as_str()))
+            .unwrap_or(LogLevel::Unknown);
+        self.channel_log_levels
+            .insert(channel.to_owned(), log_level);
+        log_level
+    }
+
+    /// Update the task state tracker, returning any state transitions caused by the event
+    fn track_task_state(&mut self, event_type: EventType, event: &Event) -> Vec<TaskTransition> {
+        let mut transitions = Vec::new();
+        match event {
+            Event::TaskCreate(ev) => {
+                transitions.extend(self.task_states.created(ev.handle, &ev.name));
+            }
+            Event::TaskReady(ev) => {
+                transitions.extend(self.task_states.ready(ev.handle, &ev.name));
+            }
+            Event::TaskResume(ev) | Event::TaskActivate(ev) => {
+                let next_ctx = Context::from(ev.clone());
+                transitions.extend(self.task_states.switched(self.contexts.active(), &next_ctx));
+            }
+            Event::Unknown(ev) if event_type == EventType::TaskSuspend => {
+                if let Some(handle) = ev.parameters().first().and_then(|h| ObjectHandle::new(*h)) {
+                    transitions.extend(
+                        self.task_states
+                            .suspended(handle, self.contexts.active().handle),
+                    );
+                }
+            }
+            Event::Unknown(ev) if event_type == EventType::TaskDelete => {
+                if let Some(handle) = ev.parameters().first().and_then(|h| ObjectHandle::new(*h)) {
+                    transitions.extend(self.task_states.deleted(handle));
+                }
+            }
+            _ if is_blocking_event(event_type) && !self.contexts.in_isr() => {
+                self.task_states.blocking_call();
+            }
+            _ => (),
+        }
+        transitions
+    }
+
+    /// Update the softirq tracker, returning the softirq events caused by the event
+    fn track_softirqs(&mut self, event: &Event) -> Vec<SoftIrq> {
+        match event {
+            Event::TaskReady(ev) => match self.contexts.current_isr() {
+                Some(isr) => vec![self.softirqs.woken_from_isr(ev.handle, isr.handle)],
+                None => Vec::new(),
+            },
+            Event::TaskResume(ev) | Event::TaskActivate(ev) => self.softirqs.switched(ev.handle),
+            _ => Vec::new(),
+        }
+    }
+
     pub fn convert(
         &mut self,
         event_code: EventCode,
         tracked_event_count: u64,
         tracked_timestamp: Timestamp,
         event: Event,
+        sample_weight: u64,
         ctf_state: &mut BorrowedCtfState,
     ) -> Result<(), Error> {
+        self.sample_weight = sample_weight;
         let event_id = event_code.event_id();
         let event_type = event_code.event_type();
         let raw_timestamp = event.timestamp();
 
         let stream_class = unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
 
+        ctf_state.set_in_isr(matches!(event, Event::IsrBegin(_)) || self.contexts.in_isr());
+        if self.irq_context_fields {
+            self.irq_name = match &event {
+                Event::IsrBegin(ev) => Some(ev.name.clone()),
+                _ => self.contexts.current_isr().map(|isr| isr.name.clone()),
+            };
+        }
+
+        let softirqs = if self.softirq_events {
+            self.track_softirqs(&event)
+        } else {
+            Vec::new()
+        };
+
+        let task_transitions = if self.task_state_events || self.task_state_intervals.is_some() {
+            self.track_task_state(event_type, &event)
+        } else {
+            Vec::new()
+        };
+
+        if let Event::TaskCreate(ev)
+        | Event::TaskReady(ev)
+        | Event::TaskResume(ev)
+        | Event::TaskActivate(ev) = &event
+        {
+            if self.task_names.get(&ev.handle) != Some(&ev.name) {
+                self.task_names.insert(ev.handle, ev.name.clone());
+            }
+            self.task_priorities.insert(ev.handle, ev.priority);
+        }
+        // Track the new priority, the old one is the last seen (the new one when unknown)
+        let old_priority = match &event {
+            Event::TaskPriority(ev)
+            | Event::TaskPriorityInherit(ev)
+            | Event::TaskPriorityDisinherit(ev) => Some(
+                self.task_priorities
+                    .insert(ev.handle, ev.priority)
+                    .unwrap_or(ev.priority),
+            ),
+            _ => None,
+        };
+        if let Event::IsrBegin(ev) | Event::IsrDefine(ev) = &event {
+            if self.isr_names.get(&ev.handle) != Some(&ev.name) {
+                self.isr_names.insert(ev.handle, ev.name.clone());
+            }
+        }
+        let object_name = match &event {
+            Event::QueueCreate(ev) => Some((ev.handle, ev.name.as_ref())),
+            Event::MutexCreate(ev) => Some((ev.handle, ev.name.as_ref())),
+            Event::SemaphoreBinaryCreate(ev) | Event::SemaphoreCountingCreate(ev) => {
+                Some((ev.handle, ev.name.as_ref()))
+            }
+            Event::EventGroupCreate(ev) => Some((ev.handle, ev.name.as_ref())),
+            Event::MessageBufferCreate(ev) => Some((ev.handle, ev.name.as_ref())),
+            Event::ObjectName(ev) => Some((ev.handle, Some(&ev.name))),
+            _ => None,
+        };
+        if let Some((handle, Some(name))) = object_name {
+            self.object_names.insert(handle, name.clone());
+        }
+
+        if let Some(leaks) = self.leaks.as_mut() {
+            match &event {
+                Event::MemoryAlloc(ev) => leaks.alloc(Allocation {
+                    address: ev.address,
+                    size: ev.size,
+                    task: self.contexts.current().name.clone(),
+                    event_code,
+                    event_count: tracked_event_count,
+                    raw_timestamp,
+                    timestamp: tracked_timestamp,
+                }),
+                Event::MemoryFree(ev) => leaks.free(ev.address),
+                _ => (),
+            }
+        }
+        self.last_timestamp = tracked_timestamp;
+
+        let inheritance = match &event {
+            Event::MutexTake(ev) | Event::MutexTakeRecursive(ev) => {
+                self.mutexes.taken(ev.handle, self.contexts.active());
+                None
+            }
+            Event::MutexGive(ev) | Event::MutexGiveRecursive(ev) => {
+                self.mutexes.given(ev.handle, self.contexts.active());
+                None
+            }
+            Event::MutexTakeBlock(ev) | Event::MutexTakeRecursiveBlock(ev) => self
+                .mutexes
+                .blocked(ev.handle, self.contexts.active())
+                .map(|inheritance| (inheritance, self.object_name(ev.handle, &ev.name))),
+            _ => None,
+        };
+
+        let message_buffer_usage = match &event {
+            Event::MessageBufferCreate(ev) => {
+                self.message_buffers.created(ev.handle, ev.buffer_size)
+            }
+            Event::MessageBufferSend(ev)
+            | Event::MessageBufferSendBlock(ev)
+            | Event::MessageBufferSendFromIsr(ev)
+            | Event::MessageBufferReceive(ev)
+            | Event::MessageBufferReceiveBlock(ev)
+            | Event::MessageBufferReceiveFromIsr(ev)
+            | Event::MessageBufferReset(ev) => {
+                self.message_buffers.updated(ev.handle, ev.bytes_in_buffer)
+            }
+            _ => MessageBufferUsage::default(),
+        };
+
+        let telemetry = match &event {
+            Event::User(ev) => self.telemetry_value(ev),
+            _ => None,
+        };
+        let caller = match &event {
+            Event::Unknown(ev) => self.caller_address(event_type, ev),
+            _ => None,
+        };
+        // Coalesced events skip the typed conversions, the catch-all arm merges them into a run
+        let coalesced = self.coalescer.is_enabled() && self.coalescer.coalesces(event_type);
+        // Scheduler locks are tracked whether or not the events are coalesced
+        let scheduler_lock = match (&event, event_type) {
+            (Event::Unknown(_), EventType::SchedulerSuspend) => Some((
+                self.scheduler_locks
+                    .suspend(tracked_timestamp, &self.contexts.active().name),
+                tracked_timestamp,
+            )),
+            (Event::Unknown(_), EventType::SchedulerResume) => {
+                self.scheduler_locks.resume(tracked_timestamp)
+            }
+            _ => None,
+        };
+        let meta = EventMeta {
+            event_type,
+            event_id,
+            event_count: tracked_event_count,
+            timestamp: tracked_timestamp,
+            raw_timestamp,
+        };
+        let created_task = match &event {
+            Event::TaskCreate(ev) if self.task_newtask_events || self.process_lifecycle_events => {
+                Some(ev.clone())
+            }
+            _ => None,
+        };
+        let deleted_task = match &event {
+            Event::Unknown(ev)
+                if self.process_lifecycle_events && event_type == EventType::TaskDelete =>
+            {
+                ev.parameters()
+                    .first()
+                    .and_then(|h| ObjectHandle::new(*h))
+                    .map(|handle| Context {
+                        handle,
+                        name: self
+                            .task_names
+                            .get(&handle)
+                            .cloned()
+                            .unwrap_or_else(|| UNNAMED_OBJECT.to_string().into()),
+                        priority: self
+                            .task_priorities
+                            .remove(&handle)
+                            .unwrap_or_else(|| 0.into()),
+                    })
+            }
+            _ => None,
+        };
+
         match event {
             Event::TraceStart(ev) => {
                 let event_class =
@@ -183,6 +1247,199 @@ impl TrcCtfConverter {
                 ctf_state.push_message(msg)?;
             }
 
+            Event::Unknown(ev)
+                if event_type == EventType::RuntimeStats
+                    && ev.parameters().len() >= 3
+                    && !coalesced =>
+            {
+                let event_class =
+                    self.event_class(stream_class, event_type, TaskRuntimeStats::event_class)?;
+                let name = ObjectHandle::new(ev.parameters()[0])
+                    .and_then(|h| self.task_names.get(&h))
+                    .cloned()
+                    .unwrap_or_else(|| UNNAMED_OBJECT.to_string().into());
+                let msg = ctf_state.create_message(event_class, tracked_timestamp);
+                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+                TaskRuntimeStats::try_from((&ev, &name, &mut self.string_cache))?
+                    .emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+
+            Event::Unknown(ev)
+                if ObjectClass::from_delete_event(event_type).is_some()
+                    && !ev.parameters().is_empty()
+                    && !coalesced =>
+            {
+                let object_class = ObjectClass::from_delete_event(event_type).unwrap();
+                let handle = ev.parameters()[0];
+                let name = ObjectHandle::new(handle)
+                    .and_then(|h| match object_class {
+                        ObjectClass::Task => self.task_names.get(&h).cloned(),
+                        _ => self.object_names.remove(&h),
+                    })
+                    .unwrap_or_else(|| UNNAMED_OBJECT.to_string().into());
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    ObjectDelete::event_class,
+                    |c, ctf_event| {
+                        ObjectDelete::try_from((object_class, handle, &name, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::Unknown(ev)
+                if event_type == EventType::TaskDelay
+                    && !ev.parameters().is_empty()
+                    && !coalesced =>
+            {
+                let msg = self.create_tick_referenced_message(
+                    stream_class,
+                    event_type,
+                    tracked_timestamp,
+                    ctf_state,
+                    |stream_class| TaskDelay::event_class(event_type, event_id, stream_class),
+                )?;
+                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+                TaskDelay::try_from((&ev, self.contexts.active(), &mut self.string_cache))?
+                    .emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+
+            Event::Unknown(ev)
+                if event_type == EventType::TaskDelayUntil
+                    && !ev.parameters().is_empty()
+                    && !coalesced =>
+            {
+                let msg = self.create_tick_referenced_message(
+                    stream_class,
+                    event_type,
+                    tracked_timestamp,
+                    ctf_state,
+                    |stream_class| TaskDelayUntil::event_class(event_type, event_id, stream_class),
+                )?;
+                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+                TaskDelayUntil::try_from((&ev, self.contexts.active(), &mut self.string_cache))?
+                    .emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+
+            Event::Unknown(_) if event_type == EventType::SchedulerSuspend && !coalesced => {
+                let (depth, _) = scheduler_lock.unwrap_or((0, tracked_timestamp));
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    SchedulerSuspend::event_class,
+                    |c, ctf_event| {
+                        SchedulerSuspend::try_from((
+                            c.contexts.active(),
+                            depth,
+                            &mut c.string_cache,
+                        ))?
+                        .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::Unknown(_) if event_type == EventType::SchedulerResume && !coalesced => {
+                let (depth, locked_since) = scheduler_lock.unwrap_or((0, tracked_timestamp));
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    SchedulerResume::event_class,
+                    |c, ctf_event| {
+                        SchedulerResume::try_from((
+                            c.contexts.active(),
+                            depth,
+                            locked_since,
+                            &mut c.string_cache,
+                        ))?
+                        .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::Unknown(ev)
+                if event_type == EventType::MallocFailed
+                    && ev.parameters().len() >= 2
+                    && !coalesced =>
+            {
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    MallocFailed::event_class,
+                    |c, ctf_event| {
+                        let ctx = c.contexts.current();
+                        MallocFailed::try_from((&ev, ctx, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::Unknown(_) if caller.is_some() && !coalesced => {
+                let caller = caller.unwrap_or_default();
+                self.emit_typed_event(ctf_state, &meta, Caller::event_class, |c, ctf_event| {
+                    let symbol = c
+                        .symbols
+                        .as_ref()
+                        .and_then(|s| s.lookup(caller.into()))
+                        .unwrap_or_default();
+                    Caller::try_from((caller, symbol, &mut c.string_cache))?.emit_event(ctf_event)
+                })?;
+            }
+
+            Event::Unknown(ev)
+                if is_timer_command(event_type) && ev.parameters().len() >= 2 && !coalesced =>
+            {
+                let params = ev.parameters();
+                let handle = ObjectHandle::new(params[0]);
+                let period = match event_type {
+                    EventType::TimerCreate
+                    | EventType::TimerChangePeriod
+                    | EventType::TimerChangePeriodFromIsr => {
+                        if let Some(handle) = handle {
+                            self.timer_periods.insert(handle, params[1]);
+                        }
+                        params[1]
+                    }
+                    _ => handle
+                        .and_then(|h| self.timer_periods.get(&h).copied())
+                        .unwrap_or(0),
+                };
+                let name = handle
+                    .and_then(|h| self.object_names.get(&h).cloned())
+                    .unwrap_or_else(|| UNNAMED_OBJECT.to_string().into());
+                self.emit_typed_event(ctf_state, &meta, Timer::event_class, |c, ctf_event| {
+                    Timer::try_from((params[0], &name, period, &mut c.string_cache))?
+                        .emit_event(ctf_event)
+                })?;
+            }
+
+            Event::Unknown(ev)
+                if event_type == EventType::TimerExpired
+                    && ev.parameters().len() >= 2
+                    && !coalesced =>
+            {
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    TimerExpired::event_class,
+                    |c, ctf_event| {
+                        let function = c
+                            .symbols
+                            .as_ref()
+                            .and_then(|s| s.lookup(ev.parameters()[0].into()))
+                            .unwrap_or_default();
+                        TimerExpired::try_from((&ev, function, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
             Event::Unknown(_) => {
                 let event_class = self.unknown_event_class;
                 let msg = ctf_state.create_message(event_class, tracked_timestamp);
@@ -192,28 +1449,270 @@ impl TrcCtfConverter {
                 ctf_state.push_message(msg)?;
             }
 
-            Event::User(ev) => {
-                let event_class = self.user_event_class;
+            Event::User(_) if telemetry.is_some() && !coalesced => {
+                if let Some((event_class, value)) = telemetry {
+                    let msg = ctf_state.create_message(event_class, tracked_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(
+                        event_id,
+                        tracked_event_count,
+                        raw_timestamp,
+                        ctf_event,
+                    )?;
+                    telemetry::emit_value(ctf_event, value);
+                    ctf_state.push_message(msg)?;
+                }
+            }
+
+            Event::User(ev) if !coalesced => {
+                let named_event_class = if self.named_user_args {
+                    self.named_user_event_class(stream_class, &ev)?
+                } else {
+                    None
+                };
+                let event_class = named_event_class.unwrap_or(self.user_event_class as *const _);
                 let msg = ctf_state.create_message(event_class, tracked_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
                 self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
-                User::try_from((&ev, &mut self.string_cache))?.emit_event(ctf_event)?;
+                if self.channel_severity_regex.is_some() {
+                    let log_level = self.channel_log_level(&ev.channel);
+                    let mut user = UserLog::try_from((
+                        &ev,
+                        self.contexts.active(),
+                        log_level,
+                        &mut self.string_cache,
+                    ))?;
+                    if self.omit_formatted_string {
+                        user.formatted_string = Default::default();
+                    }
+                    user.emit_event(ctf_event)?;
+                } else {
+                    let mut user =
+                        User::try_from((&ev, self.contexts.active(), &mut self.string_cache))?;
+                    if self.omit_formatted_string {
+                        user.formatted_string = Default::default();
+                    }
+                    user.emit_event(ctf_event)?;
+                }
+                if named_event_class.is_some() {
+                    if let Some(Some((_, named_args))) =
+                        self.named_user_event_classes.get(&*ev.format_string)
+                    {
+                        named_args.emit_args(&ev.args, ctf_event)?;
+                    }
+                }
                 ctf_state.push_message(msg)?;
             }
 
-            Event::TaskReady(ev) => {
+            Event::MemoryAlloc(ev) | Event::MemoryFree(ev) if !coalesced => {
+                self.emit_typed_event(ctf_state, &meta, Memory::event_class, |_, ctf_event| {
+                    Memory::from(&ev).emit_event(ctf_event)
+                })?;
+            }
+
+            Event::QueueSend(ev)
+            | Event::QueueSendBlock(ev)
+            | Event::QueueSendFromIsr(ev)
+            | Event::QueueSendFront(ev)
+            | Event::QueueSendFrontBlock(ev)
+            | Event::QueueSendFrontFromIsr(ev)
+            | Event::QueueReceive(ev)
+            | Event::QueueReceiveBlock(ev)
+            | Event::QueueReceiveFromIsr(ev)
+            | Event::QueuePeek(ev)
+            | Event::QueuePeekBlock(ev)
+                if !coalesced =>
+            {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(ctf_state, &meta, Queue::event_class, |c, ctf_event| {
+                    Queue::try_from((&ev, &name, &mut c.string_cache))?.emit_event(ctf_event)
+                })?;
+            }
+
+            Event::SemaphoreGive(ev)
+            | Event::SemaphoreGiveBlock(ev)
+            | Event::SemaphoreGiveFromIsr(ev)
+            | Event::SemaphoreTake(ev)
+            | Event::SemaphoreTakeBlock(ev)
+            | Event::SemaphoreTakeFromIsr(ev)
+            | Event::SemaphorePeek(ev)
+            | Event::SemaphorePeekBlock(ev)
+                if !coalesced =>
+            {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(ctf_state, &meta, Semaphore::event_class, |c, ctf_event| {
+                    Semaphore::try_from((&ev, &name, &mut c.string_cache))?.emit_event(ctf_event)
+                })?;
+            }
+
+            Event::EventGroupCreate(ev) if !coalesced => {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    EventGroup::event_class,
+                    |c, ctf_event| {
+                        EventGroup::try_from((&ev, &name, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::EventGroupSync(ev)
+            | Event::EventGroupSyncBlock(ev)
+            | Event::EventGroupWaitBits(ev)
+            | Event::EventGroupWaitBitsBlock(ev)
+            | Event::EventGroupClearBits(ev)
+            | Event::EventGroupClearBitsFromIsr(ev)
+            | Event::EventGroupSetBits(ev)
+            | Event::EventGroupSetBitsFromIsr(ev)
+                if !coalesced =>
+            {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    EventGroup::event_class,
+                    |c, ctf_event| {
+                        EventGroup::try_from((&ev, &name, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::MessageBufferCreate(ev) if !coalesced => {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    MessageBuffer::event_class,
+                    |c, ctf_event| {
+                        MessageBuffer::try_from((
+                            ev.handle,
+                            &name,
+                            message_buffer_usage,
+                            &mut c.string_cache,
+                        ))?
+                        .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::MessageBufferSend(ev)
+            | Event::MessageBufferSendBlock(ev)
+            | Event::MessageBufferSendFromIsr(ev)
+            | Event::MessageBufferReceive(ev)
+            | Event::MessageBufferReceiveBlock(ev)
+            | Event::MessageBufferReceiveFromIsr(ev)
+            | Event::MessageBufferReset(ev)
+                if !coalesced =>
+            {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    MessageBuffer::event_class,
+                    |c, ctf_event| {
+                        MessageBuffer::try_from((
+                            ev.handle,
+                            &name,
+                            message_buffer_usage,
+                            &mut c.string_cache,
+                        ))?
+                        .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::TaskNotify(ev)
+            | Event::TaskNotifyFromIsr(ev)
+            | Event::TaskNotifyWait(ev)
+            | Event::TaskNotifyWaitBlock(ev)
+                if !coalesced =>
+            {
+                let comm = ev
+                    .task_name
+                    .clone()
+                    .or_else(|| self.task_names.get(&ev.handle).cloned())
+                    .unwrap_or_else(|| UNNAMED_OBJECT.to_string().into());
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    TaskNotify::event_class,
+                    |c, ctf_event| {
+                        TaskNotify::try_from((&ev, &comm, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::UnusedStack(ev) if !coalesced => {
+                self.emit_typed_event(
+                    ctf_state,
+                    &meta,
+                    UnusedStack::event_class,
+                    |c, ctf_event| {
+                        UnusedStack::try_from((&ev, &ev.task, &mut c.string_cache))?
+                            .emit_event(ctf_event)
+                    },
+                )?;
+            }
+
+            Event::MutexGive(ev)
+            | Event::MutexGiveBlock(ev)
+            | Event::MutexGiveRecursive(ev)
+            | Event::MutexTake(ev)
+            | Event::MutexTakeBlock(ev)
+            | Event::MutexTakeRecursive(ev)
+            | Event::MutexTakeRecursiveBlock(ev)
+                if !coalesced =>
+            {
+                let name = self.object_name(ev.handle, &ev.name);
+                self.emit_typed_event(ctf_state, &meta, Mutex::event_class, |c, ctf_event| {
+                    Mutex::try_from((&ev, &name, c.mutexes.owner(ev.handle), &mut c.string_cache))?
+                        .emit_event(ctf_event)
+                })?;
+            }
+
+            Event::TaskPriority(ev)
+            | Event::TaskPriorityInherit(ev)
+            | Event::TaskPriorityDisinherit(ev)
+                if !coalesced =>
+            {
+                let event_class = self.sched_pi_setprio_event_class;
+                let msg = ctf_state.create_message(event_class, tracked_timestamp);
+                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+                let old_priority = old_priority.unwrap_or(ev.priority);
+                let mut setprio = SchedPiSetprio::try_from((
+                    event_type,
+                    &ev,
+                    old_priority,
+                    &mut self.string_cache,
+                ))?;
+                setprio.oldprio = self.prio_mapping.apply(setprio.oldprio);
+                setprio.newprio = self.prio_mapping.apply(setprio.newprio);
+                setprio.emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+
+            Event::TaskReady(ev) if !coalesced => {
                 let event_class = self.sched_wakeup_event_class;
                 let msg = ctf_state.create_message(event_class, tracked_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
                 self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
-                SchedWakeup::try_from((event_type, &ev, &mut self.string_cache))?
-                    .emit_event(ctf_event)?;
+                let mut wakeup = SchedWakeup::try_from((event_type, &ev, &mut self.string_cache))?;
+                wakeup.prio = self.prio_mapping.apply(wakeup.prio);
+                if let Some(core) = self.task_affinity.get(ev.name.as_ref()) {
+                    wakeup.target_cpu = (*core).into();
+                }
+                wakeup.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
             }
 
             Event::TaskResume(ev) | Event::TaskActivate(ev) => {
                 // Check for return from ISR
-                if let Some(isr) = self.pending_isrs.pop() {
+                if let Some(isr) = self.contexts.isr_returned() {
                     // TODO should sched_switch be created if on the same context?
                     // depends on the arg given to xTraceISREnd(arg)
                     let event_class = self.irq_handler_exit_event_class;
@@ -226,6 +1725,7 @@ impl TrcCtfConverter {
                         ctf_event,
                     )?;
                     let ctx = isr;
+                    self.isr_exited(&ctx, tracked_timestamp);
                     IrqHandlerExit::try_from((event_type, &ctx, &mut self.string_cache))?
                         .emit_event(ctf_event)?;
                     ctf_state.push_message(msg)?;
@@ -236,37 +1736,43 @@ impl TrcCtfConverter {
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
                 self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
                 let next_ctx = Context::from(ev);
-                let prev_ctx = &self.active_context;
-                SchedSwitch::try_from((event_type, prev_ctx, &next_ctx, &mut self.string_cache))?
-                    .emit_event(ctf_event)?;
-                self.active_context = next_ctx;
+                let mut switch = SchedSwitch::try_from((
+                    event_type,
+                    self.contexts.active(),
+                    &next_ctx,
+                    &mut self.string_cache,
+                ))?;
+                switch.prev_prio = self.prio_mapping.apply(switch.prev_prio);
+                switch.next_prio = self.prio_mapping.apply(switch.next_prio);
+                switch.emit_event(ctf_event)?;
+                self.contexts.switch_to(next_ctx);
                 ctf_state.push_message(msg)?;
             }
 
             Event::IsrBegin(ev) => {
+                self.isr_begin_observed = true;
                 let context = Context {
                     handle: ev.handle,
                     name: ev.name.clone(),
                     priority: ev.priority,
                 };
-                self.pending_isrs.push(context);
+                self.contexts.isr_begin(context);
+                self.isr_start_times.push(tracked_timestamp);
                 let event_class = self.irq_handler_entry_event_class;
                 let msg = ctf_state.create_message(event_class, tracked_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
                 self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
-                IrqHandlerEntry::try_from((event_type, &ev, &mut self.string_cache))?
-                    .emit_event(ctf_event)?;
+                let mut entry =
+                    IrqHandlerEntry::try_from((event_type, &ev, &mut self.string_cache))?;
+                entry.prio = self.prio_mapping.apply(entry.prio);
+                entry.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
             }
 
             // Return to the interrupted ISR (nested ISR)
-            Event::IsrResume(ev) if !self.pending_isrs.is_empty() => {
-                // This event indicates the previous ISR context before the active context
-                // top of the stack contains the active context
-                let ctx = self.pending_isrs.pop().unwrap();
-                let previous_isr = self.pending_isrs.last();
-                let previous_ctx = Context::from(ev);
-                assert_eq!(Some(&previous_ctx), previous_isr);
+            Event::IsrResume(ev) if self.contexts.in_isr() => {
+                let ctx = self.contexts.isr_resume(&Context::from(ev));
+                self.isr_exited(&ctx, tracked_timestamp);
 
                 let event_class = self.irq_handler_exit_event_class;
                 let msg = ctf_state.create_message(event_class, tracked_timestamp);
@@ -280,20 +1786,216 @@ impl TrcCtfConverter {
             // The rest are named events with no payload
             _ => {
                 if let Event::IsrResume(ev) = event {
-                    warn!(%event_type, event = %ev, "Got ISR resume but no pending IRS");
+                    if self.midrun_capture && !self.isr_begin_observed {
+                        debug!(%event_type, event = %ev, "ISR resumed, entered before the capture started");
+                    } else {
+                        warn!(%event_type, event = %ev, "Got ISR resume but no pending IRS");
+                    }
                 }
 
-                let event_class = self.event_class(stream_class, event_type, |stream_class| {
-                    Unsupported::event_class(event_type, stream_class)
-                })?;
+                if coalesced {
+                    self.coalescer.push(
+                        event_code,
+                        tracked_event_count,
+                        tracked_timestamp,
+                        raw_timestamp,
+                    );
+                } else {
+                    // These dominate many traces, keep the common case to a single lookup
+                    let event_class = match self.event_classes.get(&event_type) {
+                        Some(event_class) => *event_class as *const _,
+                        None => {
+                            let event_class = name_event_class(
+                                &self.rename,
+                                &self.display_names,
+                                Unsupported::event_class(event_type, event_id, stream_class)?,
+                            )?;
+                            self.event_classes.insert(event_type, event_class);
+                            event_class as *const _
+                        }
+                    };
+                    let msg = ctf_state.create_message(event_class, tracked_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(
+                        event_id,
+                        tracked_event_count,
+                        raw_timestamp,
+                        ctf_event,
+                    )?;
+                    // No payload fields to emit
+                    ctf_state.push_message(msg)?;
+                }
+            }
+        }
+
+        // Task state changes and the events synthesized outside of the conversion
+        // belong to the task-context stream
+        ctf_state.set_in_isr(false);
+        self.irq_name = None;
+
+        if let Some(intervals) = self.task_state_intervals.as_mut() {
+            for transition in task_transitions.iter() {
+                intervals.record(transition, tracked_timestamp);
+            }
+        }
+
+        if self.task_state_events {
+            for transition in task_transitions.iter() {
+                let event_class = self.task_state_change_event_class;
                 let msg = ctf_state.create_message(event_class, tracked_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
                 self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
-                Unsupported {}.emit_event(ctf_event)?;
+                TaskStateChange::try_from((event_type, transition, &mut self.string_cache))?
+                    .emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
             }
         }
 
+        if let Some((inheritance, mutex_name)) = inheritance {
+            let event_class = self.priority_inheritance_event_class;
+            let msg = ctf_state.create_message(event_class, tracked_timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+            let mut ev =
+                PriorityInheritance::try_from((&inheritance, &mutex_name, &mut self.string_cache))?;
+            ev.owner_prio = self.prio_mapping.apply(ev.owner_prio);
+            ev.blocked_prio = self.prio_mapping.apply(ev.blocked_prio);
+            ev.emit_event(ctf_event)?;
+            ctf_state.push_message(msg)?;
+        }
+
+        if let Some(task) = created_task {
+            if self.process_lifecycle_events {
+                let event_class = self.sched_process_fork_event_class;
+                let msg = ctf_state.create_message(event_class, tracked_timestamp);
+                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+                let child = Context::from(task.clone());
+                let mut fork = SchedProcessFork::try_from((
+                    event_type,
+                    self.contexts.active(),
+                    &child,
+                    &mut self.string_cache,
+                ))?;
+                fork.child_prio = self.prio_mapping.apply(fork.child_prio);
+                fork.emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+            if self.task_newtask_events {
+                let event_class = self.task_newtask_event_class;
+                let msg = ctf_state.create_message(event_class, tracked_timestamp);
+                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+                let mut newtask =
+                    TaskNewtask::try_from((event_type, &task, &mut self.string_cache))?;
+                newtask.prio = self.prio_mapping.apply(newtask.prio);
+                newtask.emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+        }
+
+        if let Some(task) = deleted_task {
+            let event_class = self.sched_process_exit_event_class;
+            let msg = ctf_state.create_message(event_class, tracked_timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+            let mut exit = SchedProcessExit::try_from((event_type, &task, &mut self.string_cache))?;
+            exit.prio = self.prio_mapping.apply(exit.prio);
+            exit.emit_event(ctf_event)?;
+            ctf_state.push_message(msg)?;
+
+            let event_class = self.sched_process_free_event_class;
+            let msg = ctf_state.create_message(event_class, tracked_timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+            let mut free = SchedProcessFree::try_from((event_type, &task, &mut self.string_cache))?;
+            free.prio = self.prio_mapping.apply(free.prio);
+            free.emit_event(ctf_event)?;
+            ctf_state.push_message(msg)?;
+        }
+
+        for softirq in softirqs.into_iter() {
+            let (event_class, vec) = match softirq {
+                SoftIrq::Raise(vec) => (self.softirq_raise_event_class, vec),
+                SoftIrq::Entry(vec) => (self.softirq_entry_event_class, vec),
+                SoftIrq::Exit(vec) => (self.softirq_exit_event_class, vec),
+            };
+            let msg = ctf_state.create_message(event_class, tracked_timestamp);
+            let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+            self.add_event_common_ctx(event_id, tracked_event_count, raw_timestamp, ctf_event)?;
+            match softirq {
+                SoftIrq::Raise(_) => SoftIrqRaise { vec }.emit_event(ctf_event)?,
+                SoftIrq::Entry(_) => SoftIrqEntry { vec }.emit_event(ctf_event)?,
+                SoftIrq::Exit(_) => SoftIrqExit { vec }.emit_event(ctf_event)?,
+            }
+            ctf_state.push_message(msg)?;
+        }
+
+        // Events synthesized outside of the conversion aren't sampled
+        self.sample_weight = 1;
+
         Ok(())
     }
 }
+
+/// TIMER_CREATE and the timer commands, their parameters are the timer handle and the period
+/// (or the command's value)
+fn is_timer_command(event_type: EventType) -> bool {
+    use EventType::*;
+    matches!(
+        event_type,
+        TimerCreate
+            | TimerStart
+            | TimerStartFromIsr
+            | TimerStop
+            | TimerStopFromIsr
+            | TimerReset
+            | TimerResetFromIsr
+            | TimerChangePeriod
+            | TimerChangePeriodFromIsr
+    )
+}
+
+/// Apply the configured display names and rename, if any, to a newly created event class.
+/// Both are keyed by the converter's event class name.
+fn name_event_class(
+    rename: &BTreeMap<String, String>,
+    display_names: &BTreeMap<String, BTreeMap<String, String>>,
+    event_class: *mut ffi::bt_event_class,
+) -> Result<*mut ffi::bt_event_class, Error> {
+    if rename.is_empty() && display_names.is_empty() {
+        return Ok(event_class);
+    }
+    let name = unsafe { CStr::from_ptr(ffi::bt_event_class_get_name(event_class)) }
+        .to_string_lossy()
+        .into_owned();
+    if let Some(names) = display_names.get(&name) {
+        unsafe {
+            let attrs = ffi::bt_value_map_create();
+            let mut names_map = ptr::null_mut();
+            let ret = ffi::bt_value_map_insert_empty_map_entry(
+                attrs,
+                b"display_names\0".as_ptr() as _,
+                &mut names_map,
+            );
+            ret.capi_result()?;
+            for (lang, display_name) in names.iter() {
+                let lang = CString::new(lang.as_str())?;
+                let display_name = CString::new(display_name.as_str())?;
+                let ret = ffi::bt_value_map_insert_string_entry(
+                    names_map,
+                    lang.as_ptr(),
+                    display_name.as_ptr(),
+                );
+                ret.capi_result()?;
+            }
+            ffi::bt_event_class_set_user_attributes(event_class, attrs);
+            ffi::bt_value_put_ref(attrs);
+        }
+    }
+    if let Some(new_name) = rename.get(&name) {
+        let new_name = CString::new(new_name.as_str())?;
+        unsafe { ffi::bt_event_class_set_name(event_class, new_name.as_ptr()) }.capi_result()?;
+    }
+    Ok(event_class)
+}
@@ -0,0 +1,123 @@
+use std::io::{self, Read};
+
+/// Monotonic (odometer-style) conversion throughput counters
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ThroughputCounters {
+    /// Input events read
+    pub events: u64,
+    /// Input bytes read
+    pub bytes: u64,
+    /// Events the recorder reported as dropped
+    pub dropped_events: u64,
+    /// Already seen events that were skipped
+    pub duplicate_events: u64,
+}
+
+/// How far behind the last event counter seen an event can be and still count as a duplicate.
+/// Anything further behind is taken as a forward jump (e.g. events dropped across a reconnect).
+const DUPLICATE_WINDOW: u16 = 0x1000;
+
+/// Returns true when the event counter is at or at most DUPLICATE_WINDOW behind the last one seen,
+/// i.e. the event was already seen.
+///
+/// Some stream ports re-send buffered chunks after a reconnect, which shows up as
+/// the (wrapping) event counter going backwards.
+pub fn is_duplicate_event_count(last_event_count: u16, event_count: u16) -> bool {
+    last_event_count.wrapping_sub(event_count) < DUPLICATE_WINDOW
+}
+
+/// Decides when the next converter_heartbeat event is due
+#[derive(Debug)]
+pub struct Heartbeat {
+    every_events: Option<u64>,
+    every_bytes: Option<u64>,
+    next_events: u64,
+    next_bytes: u64,
+}
+
+impl Heartbeat {
+    pub fn new(every_events: Option<u64>, every_bytes: Option<u64>) -> Self {
+        Self {
+            every_events,
+            every_bytes,
+            next_events: every_events.unwrap_or(u64::MAX),
+            next_bytes: every_bytes.unwrap_or(u64::MAX),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.every_events.is_some() || self.every_bytes.is_some()
+    }
+
+    /// Returns true when a heartbeat is due, the next one is scheduled relative to the
+    /// given counters
+    pub fn is_due(&mut self, counters: &ThroughputCounters) -> bool {
+        if counters.events < self.next_events && counters.bytes < self.next_bytes {
+            return false;
+        }
+        if let Some(every) = self.every_events {
+            self.next_events = counters.events.saturating_add(every);
+        }
+        if let Some(every) = self.every_bytes {
+            self.next_bytes = counters.bytes.saturating_add(every);
+        }
+        true
+    }
+}
+
+/// A reader that counts the bytes read through it
+#[derive(Debug)]
+pub struct CountingReader<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, bytes: 0 }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_event_count_wrap() {
+        assert!(!is_duplicate_event_count(u16::MAX, 0));
+        assert!(!is_duplicate_event_count(u16::MAX - 1, 1));
+        assert!(is_duplicate_event_count(1, u16::MAX));
+    }
+
+    #[test]
+    fn duplicate_event_count_resent_range() {
+        assert!(is_duplicate_event_count(500, 500));
+        assert!(is_duplicate_event_count(500, 400));
+        assert!(is_duplicate_event_count(
+            500,
+            500_u16.wrapping_sub(DUPLICATE_WINDOW - 1)
+        ));
+    }
+
+    #[test]
+    fn duplicate_event_count_forward_gap() {
+        assert!(!is_duplicate_event_count(500, 501));
+        assert!(!is_duplicate_event_count(500, 500 + 0x8000));
+        assert!(!is_duplicate_event_count(
+            500,
+            500_u16.wrapping_sub(DUPLICATE_WINDOW)
+        ));
+    }
+}
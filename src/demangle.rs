@@ -0,0 +1,24 @@
+use cpp_demangle::{DemangleOptions, Symbol};
+
+/// Makes the names of tasks and objects named by (mangled) symbol readable
+pub trait Demangler: Send {
+    /// None when the name isn't mangled
+    fn demangle(&self, name: &str) -> Option<String>;
+}
+
+/// Demangles C++ symbols of the Itanium ABI (GCC, Clang)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ItaniumDemangler;
+
+impl Demangler for ItaniumDemangler {
+    fn demangle(&self, name: &str) -> Option<String> {
+        // Cheap check first, most names aren't mangled
+        if !name.starts_with("_Z") {
+            return None;
+        }
+        Symbol::new(name)
+            .ok()?
+            .demangle(&DemangleOptions::default())
+            .ok()
+    }
+}
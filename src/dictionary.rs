@@ -0,0 +1,108 @@
+use babeltrace2_sys::ffi;
+use serde::Serialize;
+use std::ffi::CStr;
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
+
+/// Event class IDs at and above this are synthesized by the converter,
+/// below it they're the trace recorder event ID
+const SYNTHESIZED_EVENT_ID_BASE: u64 = 0x1000;
+
+#[derive(Serialize)]
+struct Dictionary {
+    common_context: Vec<FieldDump>,
+    event_classes: Vec<EventClassDump>,
+}
+
+#[derive(Serialize)]
+struct EventClassDump {
+    id: u64,
+    name: String,
+    /// Trace recorder event ID the class maps to, none for synthesized classes
+    trace_recorder_event_id: Option<u64>,
+    fields: Vec<FieldDump>,
+}
+
+#[derive(Serialize)]
+struct FieldDump {
+    name: String,
+    #[serde(rename = "type")]
+    typ: &'static str,
+}
+
+/// Write the event classes of the stream classes, with their payload fields, as JSON.
+/// The common context is the first stream class's, they all share it.
+///
+/// # Safety
+/// The stream classes must be valid, and there must be at least one
+pub unsafe fn write(path: &Path, stream_classes: &[*const ffi::bt_stream_class]) -> io::Result<()> {
+    let mut event_classes: Vec<_> = stream_classes
+        .iter()
+        .flat_map(|sc| (0..ffi::bt_stream_class_get_event_class_count(*sc)).map(move |i| (*sc, i)))
+        .map(|(stream_class, i)| {
+            let event_class =
+                ffi::bt_stream_class_borrow_event_class_by_index_const(stream_class, i);
+            let id = ffi::bt_event_class_get_id(event_class);
+            EventClassDump {
+                id,
+                name: c_str(ffi::bt_event_class_get_name(event_class)),
+                trace_recorder_event_id: (id < SYNTHESIZED_EVENT_ID_BASE).then_some(id),
+                fields: struct_fields(ffi::bt_event_class_borrow_payload_field_class_const(
+                    event_class,
+                )),
+            }
+        })
+        .collect();
+    event_classes.sort_by_key(|c| c.id);
+    let dictionary = Dictionary {
+        common_context: struct_fields(
+            ffi::bt_stream_class_borrow_event_common_context_field_class_const(stream_classes[0]),
+        ),
+        event_classes,
+    };
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &dictionary)?;
+    Ok(())
+}
+
+unsafe fn c_str(s: *const std::os::raw::c_char) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn struct_fields(fc: *const ffi::bt_field_class) -> Vec<FieldDump> {
+    if fc.is_null() {
+        return Vec::new();
+    }
+    (0..ffi::bt_field_class_structure_get_member_count(fc))
+        .map(|i| {
+            let member = ffi::bt_field_class_structure_borrow_member_by_index_const(fc, i);
+            FieldDump {
+                name: c_str(ffi::bt_field_class_structure_member_get_name(member)),
+                typ: type_name(ffi::bt_field_class_get_type(
+                    ffi::bt_field_class_structure_member_borrow_field_class_const(member),
+                )),
+            }
+        })
+        .collect()
+}
+
+fn type_name(typ: ffi::bt_field_class_type) -> &'static str {
+    use ffi::bt_field_class_type::*;
+    match typ {
+        BT_FIELD_CLASS_TYPE_BOOL => "bool",
+        BT_FIELD_CLASS_TYPE_STRING => "string",
+        BT_FIELD_CLASS_TYPE_UNSIGNED_INTEGER => "unsigned_integer",
+        BT_FIELD_CLASS_TYPE_SIGNED_INTEGER => "signed_integer",
+        BT_FIELD_CLASS_TYPE_UNSIGNED_ENUMERATION => "unsigned_enumeration",
+        BT_FIELD_CLASS_TYPE_SIGNED_ENUMERATION => "signed_enumeration",
+        BT_FIELD_CLASS_TYPE_DOUBLE_PRECISION_REAL => "double",
+        _ => "other",
+    }
+}
@@ -0,0 +1,200 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use trace_recorder_parser::{
+    streaming::event::{Event, EventType},
+    types::UserEventChannel,
+};
+
+/// An event payload field value, as seen by match rules and filters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FieldValue<'a> {
+    Int(i64),
+    Str(&'a str),
+}
+
+/// The trace recorder event type name of the event, e.g. `TASK_READY` or `USER_EVENT`
+pub fn event_name(event_type: EventType, event: &Event) -> Cow<'static, str> {
+    match event {
+        // User event types are parameterized by the argument count
+        Event::User(_) => Cow::Borrowed("USER_EVENT"),
+        _ => Cow::Owned(event_type.to_string()),
+    }
+}
+
+/// Look up an event payload field by the name of the corresponding CTF payload field
+pub fn event_field<'a>(event: &'a Event, name: &str) -> Option<FieldValue<'a>> {
+    use FieldValue::*;
+    let value = match (event, name) {
+        (Event::TraceStart(ev), "task") => Str(&ev.current_task),
+        (Event::TraceStart(ev), "task_handle") => Int(u32::from(ev.current_task_handle).into()),
+
+        (Event::User(ev), "channel") => Str(match &ev.channel {
+            UserEventChannel::Default => UserEventChannel::DEFAULT,
+            UserEventChannel::Custom(c) => c.as_str(),
+        }),
+        (Event::User(ev), "format_string") => Str(&ev.format_string),
+        (Event::User(ev), "formatted_string") => Str(&ev.formatted_string),
+
+        (
+            Event::TaskCreate(ev)
+            | Event::TaskReady(ev)
+            | Event::TaskResume(ev)
+            | Event::TaskActivate(ev)
+            | Event::TaskPriority(ev)
+            | Event::TaskPriorityInherit(ev)
+            | Event::TaskPriorityDisinherit(ev),
+            field,
+        ) => match field {
+            "comm" => Str(&ev.name),
+            "tid" => Int(u32::from(ev.handle).into()),
+            "prio" => Int(u32::from(ev.priority).into()),
+            _ => return None,
+        },
+
+        (Event::IsrBegin(ev) | Event::IsrResume(ev), field) => match field {
+            "name" => Str(&ev.name),
+            "irq" => Int(u32::from(ev.handle).into()),
+            "prio" => Int(u32::from(ev.priority).into()),
+            _ => return None,
+        },
+
+        (
+            Event::QueueSend(ev)
+            | Event::QueueSendBlock(ev)
+            | Event::QueueSendFromIsr(ev)
+            | Event::QueueSendFront(ev)
+            | Event::QueueSendFrontBlock(ev)
+            | Event::QueueSendFrontFromIsr(ev)
+            | Event::QueueReceive(ev)
+            | Event::QueueReceiveBlock(ev)
+            | Event::QueueReceiveFromIsr(ev)
+            | Event::QueuePeek(ev)
+            | Event::QueuePeekBlock(ev),
+            field,
+        ) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            "messages_waiting" => Int(ev.messages_waiting.into()),
+            _ => return None,
+        },
+
+        (
+            Event::SemaphoreGive(ev)
+            | Event::SemaphoreGiveBlock(ev)
+            | Event::SemaphoreGiveFromIsr(ev)
+            | Event::SemaphoreTake(ev)
+            | Event::SemaphoreTakeBlock(ev)
+            | Event::SemaphoreTakeFromIsr(ev)
+            | Event::SemaphorePeek(ev)
+            | Event::SemaphorePeekBlock(ev),
+            field,
+        ) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            "count" => Int(ev.count.into()),
+            _ => return None,
+        },
+
+        (Event::EventGroupCreate(ev), field) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            "bits" => Int(ev.event_bits.into()),
+            _ => return None,
+        },
+
+        (
+            Event::EventGroupSync(ev)
+            | Event::EventGroupSyncBlock(ev)
+            | Event::EventGroupWaitBits(ev)
+            | Event::EventGroupWaitBitsBlock(ev)
+            | Event::EventGroupClearBits(ev)
+            | Event::EventGroupClearBitsFromIsr(ev)
+            | Event::EventGroupSetBits(ev)
+            | Event::EventGroupSetBitsFromIsr(ev),
+            field,
+        ) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            "bits" => Int(ev.bits.into()),
+            _ => return None,
+        },
+
+        (Event::MessageBufferCreate(ev), field) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            "bytes_available" => Int(ev.buffer_size.into()),
+            _ => return None,
+        },
+
+        (
+            Event::MessageBufferSend(ev)
+            | Event::MessageBufferSendBlock(ev)
+            | Event::MessageBufferSendFromIsr(ev)
+            | Event::MessageBufferReceive(ev)
+            | Event::MessageBufferReceiveBlock(ev)
+            | Event::MessageBufferReceiveFromIsr(ev)
+            | Event::MessageBufferReset(ev),
+            field,
+        ) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            "bytes_in_buffer" => Int(ev.bytes_in_buffer.into()),
+            _ => return None,
+        },
+
+        (
+            Event::TaskNotify(ev)
+            | Event::TaskNotifyFromIsr(ev)
+            | Event::TaskNotifyWait(ev)
+            | Event::TaskNotifyWaitBlock(ev),
+            field,
+        ) => match field {
+            "comm" => Str(ev.task_name.as_deref()?),
+            "tid" => Int(u32::from(ev.handle).into()),
+            "ticks_to_wait" => Int(ev.ticks_to_wait.map(|t| t.ticks()).unwrap_or(0).into()),
+            _ => return None,
+        },
+
+        (Event::UnusedStack(ev), field) => match field {
+            "comm" => Str(&ev.task),
+            "tid" => Int(u32::from(ev.handle).into()),
+            "bytes_free" => Int(ev.low_mark.into()),
+            _ => return None,
+        },
+
+        (
+            Event::MutexGive(ev)
+            | Event::MutexGiveBlock(ev)
+            | Event::MutexGiveRecursive(ev)
+            | Event::MutexTake(ev)
+            | Event::MutexTakeBlock(ev)
+            | Event::MutexTakeRecursive(ev)
+            | Event::MutexTakeRecursiveBlock(ev),
+            field,
+        ) => match field {
+            "handle" => Int(u32::from(ev.handle).into()),
+            "name" => Str(ev.name.as_deref()?),
+            _ => return None,
+        },
+
+        (Event::MemoryAlloc(ev) | Event::MemoryFree(ev), field) => match field {
+            "address" => Int(ev.address.into()),
+            "size" => Int(ev.size.into()),
+            _ => return None,
+        },
+
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Whether the event's payload fields equal the given values
+pub fn fields_match(event: &Event, fields: &BTreeMap<String, serde_json::Value>) -> bool {
+    fields.iter().all(
+        |(field, expected)| match (event_field(event, field), expected) {
+            (Some(FieldValue::Str(s)), serde_json::Value::String(e)) => s == e,
+            (Some(FieldValue::Int(i)), serde_json::Value::Number(e)) => e.as_i64() == Some(i),
+            _ => false,
+        },
+    )
+}
@@ -0,0 +1,112 @@
+use crate::config::Config;
+use crate::Opts;
+use trace_recorder_parser::streaming::event::{EventId, EventType};
+
+/// Trace recorder event IDs are 12 bits
+const MAX_EVENT_ID: u16 = 0x0FFF;
+
+/// Print every known event type, the CTF event classes it's converted to and whether the
+/// --filter and --sample options include it
+pub fn print(opts: &Opts, config: &Config) {
+    println!(
+        "{:<6} {:<32} {:<40} Included",
+        "ID", "Event type", "CTF event"
+    );
+    let mut listed = Vec::new();
+    for id in 0..=MAX_EVENT_ID {
+        let event_type = EventType::from(EventId(id));
+        let name = match event_type {
+            EventType::Unknown(_) => continue,
+            // User event types are parameterized by the argument count, see event_name
+            EventType::UserEvent(_) => "USER_EVENT".to_owned(),
+            _ => event_type.to_string(),
+        };
+        if listed.contains(&name) {
+            continue;
+        }
+        let ctf_events = ctf_event_names(event_type, &name, opts, config)
+            .into_iter()
+            .map(|n| config.rename.get(&n).cloned().unwrap_or(n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "0x{id:03X}  {name:<32} {ctf_events:<40} {}",
+            inclusion(&name, opts)
+        );
+        listed.push(name);
+    }
+}
+
+/// The event classes the events of the type are converted to, before renaming
+fn ctf_event_names(event_type: EventType, name: &str, opts: &Opts, config: &Config) -> Vec<String> {
+    let names: &[&str] = match event_type {
+        _ if opts.coalesce.iter().any(|c| c == name) => &["coalesced_events"],
+        EventType::TraceStart => &["TRACE_START"],
+        EventType::TaskReady => &["sched_wakeup"],
+        EventType::TaskPriority
+        | EventType::TaskPriorityInherit
+        | EventType::TaskPriorityDisinherit => &["sched_pi_setprio"],
+        EventType::MutexTakeBlock => &["MUTEX_TAKE_BLOCK", "priority_inheritance"],
+        EventType::MutexTakeRecursiveBlock => {
+            &["MUTEX_TAKE_RECURSIVE_BLOCK", "priority_inheritance"]
+        }
+        // irq_handler_exit when returning from an ISR
+        EventType::TaskActivate | EventType::TaskResume => &["sched_switch", "irq_handler_exit"],
+        EventType::TaskSwitchIsrBegin => &["irq_handler_entry"],
+        EventType::TaskSwitchIsrResume => &["irq_handler_exit"],
+        EventType::UserEvent(_) => &["USER_EVENT"],
+        EventType::RuntimeStats => &["task_runtime_stats"],
+        _ if config.caller_address.contains_key(name) => &[],
+        // Named from the event type
+        _ => &[],
+    };
+    let mut names = if names.is_empty() {
+        vec![name.to_owned()]
+    } else {
+        names.iter().map(|n| n.to_string()).collect()
+    };
+    // Synthesized alongside the event
+    match event_type {
+        EventType::TaskCreate => {
+            if opts.process_lifecycle_events {
+                names.push("sched_process_fork".to_owned());
+            }
+            if opts.task_newtask_events {
+                names.push("task_newtask".to_owned());
+            }
+        }
+        EventType::TaskDelete if opts.process_lifecycle_events => {
+            names.push("sched_process_exit".to_owned());
+            names.push("sched_process_free".to_owned());
+        }
+        // Instead of USER_EVENT for the numeric telemetry channels
+        EventType::UserEvent(_) if !opts.coalesce.iter().any(|c| c == name) => names.extend(
+            config
+                .telemetry_channels
+                .keys()
+                .map(|channel| format!("{channel}_value")),
+        ),
+        _ => (),
+    }
+    names
+}
+
+fn inclusion(name: &str, opts: &Opts) -> String {
+    let filtered = opts
+        .filter
+        .as_ref()
+        .map_or(Some(true), |f| f.includes(name));
+    let sample_rate = opts.sample.iter().find(|r| r.event == name);
+    let sampled_per_channel = opts.sample.iter().any(|r| {
+        r.event
+            .strip_prefix(name)
+            .is_some_and(|c| c.starts_with(':'))
+    });
+    match (filtered, sample_rate) {
+        (Some(false), _) => "no".to_owned(),
+        (None, _) => "depends on the payload (filter)".to_owned(),
+        (Some(true), Some(rate)) => format!("yes, 1 in {}", rate.every),
+        (Some(true), None) if sampled_per_channel => "yes, sampled per channel".to_owned(),
+        (Some(true), None) => "yes".to_owned(),
+    }
+}
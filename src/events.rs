@@ -1,15 +1,35 @@
+use crate::anomaly::Anomaly;
+use crate::coalesce::Run;
+use crate::counters::ThroughputCounters;
+use crate::latency::Violation;
+use crate::leaks::Allocation;
+use crate::message_buffers::MessageBufferUsage;
+use crate::mutex::Inheritance;
+use crate::task_state::TaskTransition;
 use crate::types::{Context, StringCache};
 use babeltrace2_sys::Error;
 use ctf_macros::CtfEventClass;
 use enum_iterator::Sequence;
+use serde::Serialize;
 use std::convert::TryFrom;
 use std::ffi::CStr;
-use trace_recorder_parser::{streaming::event::*, types::UserEventChannel};
+use trace_recorder_parser::{
+    streaming::event::*,
+    time::Timestamp,
+    types::{Argument, ObjectHandle, ObjectName, Priority, UserEventChannel},
+};
 
 // TODO - any way to use serde-reflection to synthesize these?
 
+// Event class IDs
+//
+// Classes that map 1:1 to a trace-recorder event use the trace-recorder event ID.
+// Classes synthesized by the converter (possibly from several trace-recorder events)
+// use IDs above the 12-bit trace-recorder event ID space.
+
 #[derive(CtfEventClass)]
 #[event_name = "TRACE_START"]
+#[event_id = 0x01]
 pub struct TraceStart<'a> {
     pub task_handle: i64,
     pub task: &'a CStr,
@@ -29,6 +49,7 @@ impl<'a> TryFrom<(&TraceStartEvent, &'a mut StringCache)> for TraceStart<'a> {
 
 #[derive(CtfEventClass)]
 #[event_name = "UNKNOWN"]
+#[event_id = 0x1000]
 pub struct Unknown<'a> {
     pub event_type: &'a CStr,
 }
@@ -46,28 +67,140 @@ impl<'a> TryFrom<(EventType, &'a mut StringCache)> for Unknown<'a> {
 
 #[derive(CtfEventClass)]
 #[event_name = "USER_EVENT"]
+#[event_id = 0x1001]
 pub struct User<'a> {
     pub channel: &'a CStr,
     pub format_string: &'a CStr,
     pub formatted_string: &'a CStr,
-    // TODO args
+    /// Decoded arguments, comma separated
+    pub args: &'a CStr,
+    /// Task that emitted the event
+    pub comm: &'a CStr,
+    pub tid: i64,
 }
 
-impl<'a> TryFrom<(&UserEvent, &'a mut StringCache)> for User<'a> {
+impl<'a> TryFrom<(&UserEvent, &Context, &'a mut StringCache)> for User<'a> {
     type Error = Error;
 
-    fn try_from(value: (&UserEvent, &'a mut StringCache)) -> Result<Self, Self::Error> {
-        let ch = match &value.0.channel {
+    fn try_from(value: (&UserEvent, &Context, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        let (ev, ctx, cache) = value;
+        let ch = match &ev.channel {
             UserEventChannel::Default => UserEventChannel::DEFAULT,
             UserEventChannel::Custom(c) => c.as_str(),
         };
-        value.1.insert_str(ch)?;
-        value.1.insert_str(&value.0.format_string)?;
-        value.1.insert_str(&value.0.formatted_string)?;
+        cache.insert_str(ch)?;
+        cache.insert_str(&ev.format_string)?;
+        cache.insert_str(&ev.formatted_string)?;
+        let args = ev
+            .args
+            .iter()
+            .map(|arg| match arg {
+                Argument::I8(v) => v.to_string(),
+                Argument::U8(v) => v.to_string(),
+                Argument::I16(v) => v.to_string(),
+                Argument::U16(v) => v.to_string(),
+                Argument::I32(v) => v.to_string(),
+                Argument::U32(v) => v.to_string(),
+                Argument::F32(v) => v.to_string(),
+                Argument::F64(v) => v.to_string(),
+                Argument::String(v) => v.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        cache.insert_str(&args)?;
+        cache.insert_str(&ctx.name)?;
+        Ok(Self {
+            channel: cache.get_str(ch),
+            format_string: cache.get_str(&ev.format_string),
+            formatted_string: cache.get_str(&ev.formatted_string),
+            args: cache.get_str(&args),
+            comm: cache.get_str(&ctx.name),
+            tid: u32::from(ctx.handle).into(),
+        })
+    }
+}
+
+/// LTTng log levels
+#[repr(i64)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Sequence)]
+pub enum LogLevel {
+    Unknown = -1,
+    Emerg = 0,
+    Alert = 1,
+    Crit = 2,
+    Err = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 14,
+}
+
+impl LogLevel {
+    /// Map a severity name, e.g. "error" or "WARN", to a log level
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "emerg" | "emergency" => LogLevel::Emerg,
+            "alert" => LogLevel::Alert,
+            "crit" | "critical" | "fatal" => LogLevel::Crit,
+            "err" | "error" => LogLevel::Err,
+            "warn" | "warning" => LogLevel::Warning,
+            "notice" => LogLevel::Notice,
+            "info" => LogLevel::Info,
+            "debug" | "dbg" | "trace" => LogLevel::Debug,
+            _ => LogLevel::Unknown,
+        }
+    }
+
+    fn as_ffi(&self) -> *const i8 {
+        let ptr = match self {
+            LogLevel::Unknown => b"UNKNOWN\0".as_ptr(),
+            LogLevel::Emerg => b"TRACE_EMERG\0".as_ptr(),
+            LogLevel::Alert => b"TRACE_ALERT\0".as_ptr(),
+            LogLevel::Crit => b"TRACE_CRIT\0".as_ptr(),
+            LogLevel::Err => b"TRACE_ERR\0".as_ptr(),
+            LogLevel::Warning => b"TRACE_WARNING\0".as_ptr(),
+            LogLevel::Notice => b"TRACE_NOTICE\0".as_ptr(),
+            LogLevel::Info => b"TRACE_INFO\0".as_ptr(),
+            LogLevel::Debug => b"TRACE_DEBUG\0".as_ptr(),
+        };
+        ptr as *const i8
+    }
+
+    fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+/// USER_EVENT with the log level derived from the channel name,
+/// used instead of [`User`] when a channel severity regex is configured
+#[derive(CtfEventClass)]
+#[event_name = "USER_EVENT"]
+#[event_id = 0x1001]
+pub struct UserLog<'a> {
+    pub channel: &'a CStr,
+    pub format_string: &'a CStr,
+    pub formatted_string: &'a CStr,
+    pub args: &'a CStr,
+    pub comm: &'a CStr,
+    pub tid: i64,
+    pub loglevel: LogLevel,
+}
+
+impl<'a> TryFrom<(&UserEvent, &Context, LogLevel, &'a mut StringCache)> for UserLog<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&UserEvent, &Context, LogLevel, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        let user = User::try_from((value.0, value.1, value.3))?;
         Ok(Self {
-            channel: value.1.get_str(ch),
-            format_string: value.1.get_str(&value.0.format_string),
-            formatted_string: value.1.get_str(&value.0.formatted_string),
+            channel: user.channel,
+            format_string: user.format_string,
+            formatted_string: user.formatted_string,
+            args: user.args,
+            comm: user.comm,
+            tid: user.tid,
+            loglevel: value.2,
         })
     }
 }
@@ -117,6 +250,7 @@ impl TaskState {
 
 #[derive(CtfEventClass)]
 #[event_name = "sched_switch"]
+#[event_id = 0x1002]
 pub struct SchedSwitch<'a> {
     pub src_event_type: &'a CStr,
     pub prev_comm: &'a CStr,
@@ -156,6 +290,7 @@ impl<'a> TryFrom<(EventType, &Context, &Context, &'a mut StringCache)> for Sched
 
 #[derive(CtfEventClass)]
 #[event_name = "sched_wakeup"]
+#[event_id = 0x1003]
 pub struct SchedWakeup<'a> {
     pub src_event_type: &'a CStr,
     pub comm: &'a CStr,
@@ -182,6 +317,7 @@ impl<'a> TryFrom<(EventType, &TaskEvent, &'a mut StringCache)> for SchedWakeup<'
 
 #[derive(CtfEventClass)]
 #[event_name = "irq_handler_entry"]
+#[event_id = 0x1004]
 pub struct IrqHandlerEntry<'a> {
     pub src_event_type: &'a CStr,
     pub irq: i64,
@@ -206,6 +342,7 @@ impl<'a> TryFrom<(EventType, &IsrEvent, &'a mut StringCache)> for IrqHandlerEntr
 
 #[derive(CtfEventClass)]
 #[event_name = "irq_handler_exit"]
+#[event_id = 0x1005]
 pub struct IrqHandlerExit<'a> {
     pub src_event_type: &'a CStr,
     pub irq: i64,
@@ -233,3 +370,1084 @@ impl<'a> TryFrom<(EventType, &Context, &'a mut StringCache)> for IrqHandlerExit<
 pub struct Unsupported {
     // No payload fields
 }
+
+/// Kernel object class of the *_DELETE events
+#[repr(i64)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Sequence)]
+pub enum ObjectClass {
+    Queue = 0,
+    Semaphore = 1,
+    Mutex = 2,
+    Task = 3,
+    Timer = 4,
+    EventGroup = 5,
+    StreamBuffer = 6,
+    MessageBuffer = 7,
+}
+
+impl ObjectClass {
+    pub fn from_delete_event(event_type: EventType) -> Option<Self> {
+        Some(match event_type {
+            EventType::QueueDelete => ObjectClass::Queue,
+            EventType::SemaphoreDelete => ObjectClass::Semaphore,
+            EventType::MutexDelete => ObjectClass::Mutex,
+            EventType::TaskDelete => ObjectClass::Task,
+            EventType::TimerDelete => ObjectClass::Timer,
+            EventType::EventGroupDelete => ObjectClass::EventGroup,
+            EventType::StreamBufferDelete => ObjectClass::StreamBuffer,
+            EventType::MessageBufferDelete => ObjectClass::MessageBuffer,
+            _ => return None,
+        })
+    }
+
+    fn as_ffi(&self) -> *const i8 {
+        let ptr = match self {
+            ObjectClass::Queue => b"QUEUE\0".as_ptr(),
+            ObjectClass::Semaphore => b"SEMAPHORE\0".as_ptr(),
+            ObjectClass::Mutex => b"MUTEX\0".as_ptr(),
+            ObjectClass::Task => b"TASK\0".as_ptr(),
+            ObjectClass::Timer => b"TIMER\0".as_ptr(),
+            ObjectClass::EventGroup => b"EVENT_GROUP\0".as_ptr(),
+            ObjectClass::StreamBuffer => b"STREAM_BUFFER\0".as_ptr(),
+            ObjectClass::MessageBuffer => b"MESSAGE_BUFFER\0".as_ptr(),
+        };
+        ptr as *const i8
+    }
+
+    fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+/// *_DELETE events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct ObjectDelete<'a> {
+    pub object_class: ObjectClass,
+    pub handle: u64,
+    pub name: &'a CStr,
+}
+
+impl<'a> TryFrom<(ObjectClass, u32, &ObjectName, &'a mut StringCache)> for ObjectDelete<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (ObjectClass, u32, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.3.insert_str(value.2)?;
+        Ok(Self {
+            object_class: value.0,
+            handle: value.1.into(),
+            name: value.3.get_str(value.2),
+        })
+    }
+}
+
+/// TIMER_CREATE and the TIMER_* start, stop, reset and change period commands, named from
+/// the event type. Decoded from the event parameters, the handle and the period (or the
+/// command's value)
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct Timer<'a> {
+    pub handle: u64,
+    pub name: &'a CStr,
+    /// Period after the operation, 0 when the timer was created before the trace started
+    #[unit = "ticks"]
+    pub period: u64,
+}
+
+impl<'a> TryFrom<(u32, &ObjectName, u32, &'a mut StringCache)> for Timer<'a> {
+    type Error = Error;
+
+    fn try_from(value: (u32, &ObjectName, u32, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.3.insert_str(value.1)?;
+        Ok(Self {
+            handle: value.0.into(),
+            name: value.3.get_str(value.1),
+            period: value.2.into(),
+        })
+    }
+}
+
+/// TIMER_EXPIRED, the event carries the timer's callback and ID rather than its handle
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct TimerExpired<'a> {
+    #[hex]
+    pub callback: u64,
+    /// Function at the callback address, empty without an ELF file or a matching symbol
+    pub function: &'a CStr,
+    #[hex]
+    pub timer_id: u64,
+}
+
+impl<'a> TryFrom<(&BaseEvent, &str, &'a mut StringCache)> for TimerExpired<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&BaseEvent, &str, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        let params = value.0.parameters();
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            callback: params[0].into(),
+            function: value.2.get_str(value.1),
+            timer_id: params[1].into(),
+        })
+    }
+}
+
+/// QUEUE_SEND*, QUEUE_RECEIVE* and QUEUE_PEEK* events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct Queue<'a> {
+    pub handle: u64,
+    pub name: &'a CStr,
+    /// Messages in the queue after the operation
+    pub messages_waiting: u64,
+}
+
+impl<'a> TryFrom<(&QueueEvent, &ObjectName, &'a mut StringCache)> for Queue<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&QueueEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            handle: u32::from(value.0.handle).into(),
+            name: value.2.get_str(value.1),
+            messages_waiting: value.0.messages_waiting.into(),
+        })
+    }
+}
+
+/// SEMAPHORE_GIVE*, SEMAPHORE_TAKE* and SEMAPHORE_PEEK* events of binary and counting
+/// semaphores, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct Semaphore<'a> {
+    pub handle: u64,
+    pub name: &'a CStr,
+    /// Count after the operation
+    pub count: u64,
+}
+
+impl<'a> TryFrom<(&SemaphoreEvent, &ObjectName, &'a mut StringCache)> for Semaphore<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&SemaphoreEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            handle: u32::from(value.0.handle).into(),
+            name: value.2.get_str(value.1),
+            count: value.0.count.into(),
+        })
+    }
+}
+
+/// EVENTGROUP_CREATE and the EVENTGROUP_* bits operations, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct EventGroup<'a> {
+    pub handle: u64,
+    pub name: &'a CStr,
+    /// Initial event bits of EVENTGROUP_CREATE, else the bits synced, waited for, cleared or set
+    #[hex]
+    pub bits: u64,
+}
+
+impl<'a> TryFrom<(&EventGroupCreateEvent, &ObjectName, &'a mut StringCache)> for EventGroup<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&EventGroupCreateEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            handle: u32::from(value.0.handle).into(),
+            name: value.2.get_str(value.1),
+            bits: value.0.event_bits.into(),
+        })
+    }
+}
+
+impl<'a> TryFrom<(&EventGroupEvent, &ObjectName, &'a mut StringCache)> for EventGroup<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&EventGroupEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            handle: u32::from(value.0.handle).into(),
+            name: value.2.get_str(value.1),
+            bits: value.0.bits.into(),
+        })
+    }
+}
+
+/// MESSAGEBUFFER_CREATE and the MESSAGEBUFFER_* send, receive and reset events,
+/// named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct MessageBuffer<'a> {
+    pub handle: u64,
+    pub name: &'a CStr,
+    /// Bytes in the buffer after the operation
+    #[unit = "bytes"]
+    pub bytes_in_buffer: u64,
+    /// Bytes sent, received or discarded (reset), 0 when the previous level is unknown
+    #[unit = "bytes"]
+    pub bytes_transferred: u64,
+    /// Space left after the operation, 0 when the buffer size is unknown
+    #[unit = "bytes"]
+    pub bytes_available: u64,
+}
+
+impl<'a>
+    TryFrom<(
+        ObjectHandle,
+        &ObjectName,
+        MessageBufferUsage,
+        &'a mut StringCache,
+    )> for MessageBuffer<'a>
+{
+    type Error = Error;
+
+    fn try_from(
+        value: (
+            ObjectHandle,
+            &ObjectName,
+            MessageBufferUsage,
+            &'a mut StringCache,
+        ),
+    ) -> Result<Self, Self::Error> {
+        let (handle, name, usage, cache) = value;
+        cache.insert_str(name)?;
+        Ok(Self {
+            handle: u32::from(handle).into(),
+            name: cache.get_str(name),
+            bytes_in_buffer: usage.bytes_in_buffer.into(),
+            bytes_transferred: usage.bytes_transferred.into(),
+            bytes_available: usage.bytes_available.into(),
+        })
+    }
+}
+
+/// TASK_NOTIFY* events, named from the event type, the notified (or waiting) task.
+/// The parser doesn't decode the notification value and index.
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct TaskNotify<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    /// Timeout of the TASK_NOTIFY_WAIT* events, 0 for the notifying events
+    #[unit = "ticks"]
+    pub ticks_to_wait: u64,
+}
+
+impl<'a> TryFrom<(&TaskNotifyEvent, &ObjectName, &'a mut StringCache)> for TaskNotify<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&TaskNotifyEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            comm: value.2.get_str(value.1),
+            tid: u32::from(value.0.handle).into(),
+            ticks_to_wait: value.0.ticks_to_wait.map(|t| t.ticks()).unwrap_or(0).into(),
+        })
+    }
+}
+
+/// MUTEX_GIVE* and MUTEX_TAKE* events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct Mutex<'a> {
+    pub handle: u64,
+    pub name: &'a CStr,
+    /// Task holding the mutex after the operation, empty and 0 when free (or unknown)
+    pub owner_comm: &'a CStr,
+    pub owner_tid: i64,
+}
+
+impl<'a>
+    TryFrom<(
+        &MutexEvent,
+        &ObjectName,
+        Option<&Context>,
+        &'a mut StringCache,
+    )> for Mutex<'a>
+{
+    type Error = Error;
+
+    fn try_from(
+        value: (
+            &MutexEvent,
+            &ObjectName,
+            Option<&Context>,
+            &'a mut StringCache,
+        ),
+    ) -> Result<Self, Self::Error> {
+        let owner_name = value.2.map(|o| o.name.as_ref()).unwrap_or_default();
+        value.3.insert_str(value.1)?;
+        value.3.insert_str(owner_name)?;
+        Ok(Self {
+            handle: u32::from(value.0.handle).into(),
+            name: value.3.get_str(value.1),
+            owner_comm: value.3.get_str(owner_name),
+            owner_tid: value.2.map(|o| u32::from(o.handle).into()).unwrap_or(0),
+        })
+    }
+}
+
+/// TASK_DELAY events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct TaskDelay<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    /// Ticks to delay for, relative to the call
+    #[unit = "ticks"]
+    pub ticks: u64,
+}
+
+impl<'a> TryFrom<(&BaseEvent, &Context, &'a mut StringCache)> for TaskDelay<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&BaseEvent, &Context, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            comm: value.2.get_str(&value.1.name),
+            tid: u32::from(value.1.handle).into(),
+            ticks: value.0.parameters()[0].into(),
+        })
+    }
+}
+
+/// TASK_DELAY_UNTIL events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct TaskDelayUntil<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    /// Absolute tick count to wake at
+    #[unit = "ticks"]
+    pub wake_time: u64,
+}
+
+impl<'a> TryFrom<(&BaseEvent, &Context, &'a mut StringCache)> for TaskDelayUntil<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&BaseEvent, &Context, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            comm: value.2.get_str(&value.1.name),
+            tid: u32::from(value.1.handle).into(),
+            wake_time: value.0.parameters()[0].into(),
+        })
+    }
+}
+
+/// SCHEDULER_SUSPEND events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct SchedulerSuspend<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    /// Nesting depth, 1 for the outermost suspend
+    pub depth: u64,
+}
+
+impl<'a> TryFrom<(&Context, u32, &'a mut StringCache)> for SchedulerSuspend<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&Context, u32, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_str(&value.0.name)?;
+        Ok(Self {
+            comm: value.2.get_str(&value.0.name),
+            tid: u32::from(value.0.handle).into(),
+            depth: value.1.into(),
+        })
+    }
+}
+
+/// SCHEDULER_RESUME events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct SchedulerResume<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    /// Nesting depth left, 0 once the scheduler is unlocked
+    pub depth: u64,
+    /// Timestamp of the outermost SCHEDULER_SUSPEND
+    #[unit = "timer_ticks"]
+    pub locked_since: u64,
+}
+
+impl<'a> TryFrom<(&Context, u32, Timestamp, &'a mut StringCache)> for SchedulerResume<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&Context, u32, Timestamp, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.3.insert_str(&value.0.name)?;
+        Ok(Self {
+            comm: value.3.get_str(&value.0.name),
+            tid: u32::from(value.0.handle).into(),
+            depth: value.1.into(),
+            locked_since: value.2.ticks(),
+        })
+    }
+}
+
+/// Events carrying a caller address, see the caller_address config, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct Caller<'a> {
+    #[hex]
+    pub caller: u64,
+    /// Function containing the caller address, empty without an ELF file or a matching symbol
+    pub caller_symbol: &'a CStr,
+}
+
+impl<'a> TryFrom<(u32, &str, &'a mut StringCache)> for Caller<'a> {
+    type Error = Error;
+
+    fn try_from(value: (u32, &str, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            caller: value.0.into(),
+            caller_symbol: value.2.get_str(value.1),
+        })
+    }
+}
+
+/// MEMORY_ALLOC and MEMORY_FREE events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct Memory {
+    pub address: u64,
+    #[unit = "bytes"]
+    pub size: u64,
+    #[unit = "bytes"]
+    pub heap_current: u64,
+    #[unit = "bytes"]
+    pub heap_high_water_mark: u64,
+    #[unit = "bytes"]
+    pub heap_max: u64,
+    /// heap_max - heap_current, the heap left after the event
+    #[unit = "bytes"]
+    pub heap_remaining: u64,
+}
+
+impl From<&MemoryEvent> for Memory {
+    fn from(value: &MemoryEvent) -> Self {
+        Self {
+            address: value.address.into(),
+            size: value.size.into(),
+            heap_current: value.heap.current.into(),
+            heap_high_water_mark: value.heap.high_water_mark.into(),
+            heap_max: value.heap.max.into(),
+            heap_remaining: u64::from(value.heap.max).saturating_sub(value.heap.current.into()),
+        }
+    }
+}
+
+/// MALLOC_FAILED events, named from the event type
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct MallocFailed<'a> {
+    /// Requested size
+    #[unit = "bytes"]
+    pub size: u64,
+    /// Task (or ISR) that made the request
+    pub comm: &'a CStr,
+}
+
+impl<'a> TryFrom<(&BaseEvent, &Context, &'a mut StringCache)> for MallocFailed<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&BaseEvent, &Context, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        // Parameters are the (null) address and the requested size
+        let params = value.0.parameters();
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            size: params[1].into(),
+            comm: value.2.get_str(&value.1.name),
+        })
+    }
+}
+
+/// UNUSED_STACK events, named from the event type, a task's stack high-watermark
+#[derive(CtfEventClass)]
+#[event_name_from_event_type]
+pub struct UnusedStack<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    /// Lowest amount of unused stack the task has had
+    #[unit = "bytes"]
+    pub bytes_free: u64,
+}
+
+impl<'a> TryFrom<(&UnusedStackEvent, &ObjectName, &'a mut StringCache)> for UnusedStack<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&UnusedStackEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            comm: value.2.get_str(value.1),
+            tid: u32::from(value.0.handle).into(),
+            bytes_free: value.0.low_mark.into(),
+        })
+    }
+}
+
+#[repr(i64)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Sequence, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TaskLifecycleState {
+    Unknown = 0,
+    Created = 1,
+    Ready = 2,
+    Running = 3,
+    Blocked = 4,
+    Suspended = 5,
+    Deleted = 6,
+}
+
+impl TaskLifecycleState {
+    fn as_ffi(&self) -> *const i8 {
+        let ptr = match self {
+            TaskLifecycleState::Unknown => b"UNKNOWN\0".as_ptr(),
+            TaskLifecycleState::Created => b"CREATED\0".as_ptr(),
+            TaskLifecycleState::Ready => b"READY\0".as_ptr(),
+            TaskLifecycleState::Running => b"RUNNING\0".as_ptr(),
+            TaskLifecycleState::Blocked => b"BLOCKED\0".as_ptr(),
+            TaskLifecycleState::Suspended => b"SUSPENDED\0".as_ptr(),
+            TaskLifecycleState::Deleted => b"DELETED\0".as_ptr(),
+        };
+        ptr as *const i8
+    }
+
+    fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "task_state_change"]
+#[event_id = 0x1006]
+pub struct TaskStateChange<'a> {
+    pub src_event_type: &'a CStr,
+    pub comm: &'a CStr,
+    pub tid: i64,
+    pub prev_state: TaskLifecycleState,
+    pub next_state: TaskLifecycleState,
+}
+
+impl<'a> TryFrom<(EventType, &TaskTransition, &'a mut StringCache)> for TaskStateChange<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (EventType, &TaskTransition, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.2.insert_type(value.0)?;
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            src_event_type: value.2.get_type(&value.0),
+            comm: value.2.get_str(&value.1.name),
+            tid: u32::from(value.1.handle).into(),
+            prev_state: value.1.prev_state,
+            next_state: value.1.next_state,
+        })
+    }
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "converter_heartbeat"]
+#[event_id = 0x1007]
+pub struct ConverterHeartbeat {
+    pub events: u64,
+    #[unit = "bytes"]
+    pub bytes: u64,
+    pub dropped_events: u64,
+}
+
+impl From<&ThroughputCounters> for ConverterHeartbeat {
+    fn from(value: &ThroughputCounters) -> Self {
+        Self {
+            events: value.events,
+            bytes: value.bytes,
+            dropped_events: value.dropped_events,
+        }
+    }
+}
+
+/// A RUNTIME_STATS snapshot for a task, parameters are the task handle,
+/// total runtime (timer ticks) and switch count
+#[derive(CtfEventClass)]
+#[event_name = "task_runtime_stats"]
+#[event_id = 0x1009]
+pub struct TaskRuntimeStats<'a> {
+    pub comm: &'a CStr,
+    pub tid: i64,
+    #[unit = "timer_ticks"]
+    pub total_runtime: u64,
+    pub switch_count: u64,
+}
+
+impl<'a> TryFrom<(&BaseEvent, &ObjectName, &'a mut StringCache)> for TaskRuntimeStats<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&BaseEvent, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        let params = value.0.parameters();
+        value.2.insert_str(value.1)?;
+        Ok(Self {
+            comm: value.2.get_str(value.1),
+            tid: params[0].into(),
+            total_runtime: params[1].into(),
+            switch_count: params[2].into(),
+        })
+    }
+}
+
+/// A user event carrying the host wall-clock time, see clock_sync.rs
+#[derive(CtfEventClass)]
+#[event_name = "clock_correlation"]
+#[event_id = 0x100A]
+pub struct ClockCorrelation {
+    #[unit = "ns"]
+    pub host_time_ns: u64,
+    /// Host time minus the target time
+    #[unit = "ns"]
+    pub offset_ns: i64,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "softirq_raise"]
+#[event_id = 0x100B]
+pub struct SoftIrqRaise {
+    pub vec: u64,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "softirq_entry"]
+#[event_id = 0x100C]
+pub struct SoftIrqEntry {
+    pub vec: u64,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "softirq_exit"]
+#[event_id = 0x100D]
+pub struct SoftIrqExit {
+    pub vec: u64,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "latency_violation"]
+#[event_id = 0x100E]
+pub struct LatencyViolation<'a> {
+    pub budget: &'a CStr,
+    #[unit = "ns"]
+    pub latency_ns: u64,
+    #[unit = "ns"]
+    pub budget_ns: u64,
+}
+
+impl<'a> TryFrom<(&Violation, &'a mut StringCache)> for LatencyViolation<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&Violation, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.1.insert_str(&value.0.budget)?;
+        Ok(Self {
+            budget: value.1.get_str(&value.0.budget),
+            latency_ns: value.0.latency_ns,
+            budget_ns: value.0.budget_ns,
+        })
+    }
+}
+
+#[repr(i64)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Sequence)]
+pub enum AnomalyKind {
+    Burst = 0,
+    Silence = 1,
+}
+
+impl AnomalyKind {
+    fn as_ffi(&self) -> *const i8 {
+        let ptr = match self {
+            AnomalyKind::Burst => b"BURST\0".as_ptr(),
+            AnomalyKind::Silence => b"SILENCE\0".as_ptr(),
+        };
+        ptr as *const i8
+    }
+
+    fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "anomaly"]
+#[event_id = 0x1008]
+pub struct EventRateAnomaly<'a> {
+    pub kind: AnomalyKind,
+    pub src_event_type: &'a CStr,
+    pub count: u64,
+    pub expected: u64,
+    #[unit = "ns"]
+    pub duration_ns: u64,
+}
+
+impl<'a> TryFrom<(&Anomaly, &'a mut StringCache)> for EventRateAnomaly<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&Anomaly, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.1.insert_type(value.0.event_type)?;
+        Ok(Self {
+            kind: value.0.kind,
+            src_event_type: value.1.get_type(&value.0.event_type),
+            count: value.0.count,
+            expected: value.0.expected,
+            duration_ns: value.0.duration_ns,
+        })
+    }
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "coalesced_events"]
+#[event_id = 0x100F]
+pub struct CoalescedEvents<'a> {
+    pub src_event_type: &'a CStr,
+    pub count: u64,
+    #[unit = "timer_ticks"]
+    pub first_timestamp: u64,
+    #[unit = "timer_ticks"]
+    pub last_timestamp: u64,
+}
+
+impl<'a> TryFrom<(&Run, &'a mut StringCache)> for CoalescedEvents<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&Run, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.1.insert_type(value.0.event_type)?;
+        Ok(Self {
+            src_event_type: value.1.get_type(&value.0.event_type),
+            count: value.0.count,
+            first_timestamp: value.0.first_timestamp.ticks(),
+            last_timestamp: value.0.last_timestamp.ticks(),
+        })
+    }
+}
+
+/// Fixed synthetic events emitted at the start of the stream by --inject-selftest,
+/// covering the payload field encodings
+#[derive(CtfEventClass)]
+#[event_name = "selftest"]
+#[event_id = 0x1010]
+pub struct SelfTest<'a> {
+    pub seq: u64,
+    pub unsigned_value: u64,
+    pub signed_value: i64,
+    pub string_value: &'a CStr,
+}
+
+impl SelfTest<'static> {
+    pub fn sequence() -> [Self; 3] {
+        [
+            Self {
+                seq: 0,
+                unsigned_value: 0,
+                signed_value: 0,
+                string_value: c"",
+            },
+            Self {
+                seq: 1,
+                unsigned_value: u64::MAX,
+                signed_value: i64::MIN,
+                string_value: c"selftest",
+            },
+            Self {
+                seq: 2,
+                unsigned_value: 0x0123_4567_89AB_CDEF,
+                signed_value: -1,
+                string_value: c"caf\u{e9} \\ \"quoted\"",
+            },
+        ]
+    }
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "leak_candidate"]
+#[event_id = 0x1011]
+pub struct LeakCandidate<'a> {
+    pub address: u64,
+    #[unit = "bytes"]
+    pub size: u64,
+    pub comm: &'a CStr,
+    #[unit = "timer_ticks"]
+    pub alloc_timestamp: u64,
+}
+
+impl<'a> TryFrom<(&Allocation, &'a mut StringCache)> for LeakCandidate<'a> {
+    type Error = Error;
+
+    fn try_from(value: (&Allocation, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.1.insert_str(&value.0.task)?;
+        Ok(Self {
+            address: value.0.address.into(),
+            size: value.0.size.into(),
+            comm: value.1.get_str(&value.0.task),
+            alloc_timestamp: value.0.timestamp.ticks(),
+        })
+    }
+}
+
+#[repr(i64)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Sequence)]
+pub enum ContextKind {
+    Task = 0,
+    Isr = 1,
+}
+
+impl ContextKind {
+    fn as_ffi(&self) -> *const i8 {
+        let ptr = match self {
+            ContextKind::Task => b"TASK\0".as_ptr(),
+            ContextKind::Isr => b"ISR\0".as_ptr(),
+        };
+        ptr as *const i8
+    }
+
+    fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+/// A task or ISR handle and its (last) name, emitted at the end of the trace
+#[derive(CtfEventClass)]
+#[event_name = "handle_name"]
+#[event_id = 0x1012]
+pub struct HandleName<'a> {
+    pub kind: ContextKind,
+    pub handle: u64,
+    pub name: &'a CStr,
+}
+
+impl<'a> TryFrom<(ContextKind, ObjectHandle, &ObjectName, &'a mut StringCache)> for HandleName<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (ContextKind, ObjectHandle, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.3.insert_str(value.2)?;
+        Ok(Self {
+            kind: value.0,
+            handle: u32::from(value.1).into(),
+            name: value.3.get_str(value.2),
+        })
+    }
+}
+
+/// A task created, carrying its name for viewers that only learn names from sched_switch
+#[derive(CtfEventClass)]
+#[event_name = "task_newtask"]
+#[event_id = 0x1013]
+pub struct TaskNewtask<'a> {
+    pub src_event_type: &'a CStr,
+    pub tid: i64,
+    pub comm: &'a CStr,
+    pub prio: i64,
+    pub clone_flags: u64,
+    pub oom_score_adj: i64,
+}
+
+impl<'a> TryFrom<(EventType, &TaskEvent, &'a mut StringCache)> for TaskNewtask<'a> {
+    type Error = Error;
+
+    fn try_from(value: (EventType, &TaskEvent, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_type(value.0)?;
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            src_event_type: value.2.get_type(&value.0),
+            tid: u32::from(value.1.handle).into(),
+            comm: value.2.get_str(&value.1.name),
+            prio: u32::from(value.1.priority).into(),
+            clone_flags: 0,
+            oom_score_adj: 0,
+        })
+    }
+}
+
+/// A task blocked on a mutex held by a lower priority task, the owner inherits its priority
+#[derive(CtfEventClass)]
+#[event_name = "priority_inheritance"]
+#[event_id = 0x1014]
+pub struct PriorityInheritance<'a> {
+    pub mutex_handle: u64,
+    pub mutex: &'a CStr,
+    pub owner_comm: &'a CStr,
+    pub owner_tid: i64,
+    pub owner_prio: i64,
+    pub blocked_comm: &'a CStr,
+    pub blocked_tid: i64,
+    pub blocked_prio: i64,
+}
+
+impl<'a> TryFrom<(&Inheritance, &ObjectName, &'a mut StringCache)> for PriorityInheritance<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (&Inheritance, &ObjectName, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        let inheritance = value.0;
+        let cache = value.2;
+        cache.insert_str(value.1)?;
+        cache.insert_str(&inheritance.owner.name)?;
+        cache.insert_str(&inheritance.blocked.name)?;
+        Ok(Self {
+            mutex_handle: u32::from(inheritance.mutex).into(),
+            mutex: cache.get_str(value.1),
+            owner_comm: cache.get_str(&inheritance.owner.name),
+            owner_tid: u32::from(inheritance.owner.handle).into(),
+            owner_prio: u32::from(inheritance.owner.priority).into(),
+            blocked_comm: cache.get_str(&inheritance.blocked.name),
+            blocked_tid: u32::from(inheritance.blocked.handle).into(),
+            blocked_prio: u32::from(inheritance.blocked.priority).into(),
+        })
+    }
+}
+
+/// A task created by the running task (or before the scheduler started), modeled after the
+/// LTTng event, the pid is the tid
+#[derive(CtfEventClass)]
+#[event_name = "sched_process_fork"]
+#[event_id = 0x1015]
+pub struct SchedProcessFork<'a> {
+    pub src_event_type: &'a CStr,
+    pub parent_comm: &'a CStr,
+    pub parent_tid: i64,
+    pub parent_pid: i64,
+    pub child_comm: &'a CStr,
+    pub child_tid: i64,
+    pub child_pid: i64,
+    pub child_prio: i64,
+}
+
+impl<'a> TryFrom<(EventType, &Context, &Context, &'a mut StringCache)> for SchedProcessFork<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (EventType, &Context, &Context, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        let event_type = value.0;
+        let parent = value.1;
+        let child = value.2;
+        let cache = value.3;
+        cache.insert_type(event_type)?;
+        cache.insert_str(&parent.name)?;
+        cache.insert_str(&child.name)?;
+        Ok(Self {
+            src_event_type: cache.get_type(&event_type),
+            parent_comm: cache.get_str(&parent.name),
+            parent_tid: u32::from(parent.handle).into(),
+            parent_pid: u32::from(parent.handle).into(),
+            child_comm: cache.get_str(&child.name),
+            child_tid: u32::from(child.handle).into(),
+            child_pid: u32::from(child.handle).into(),
+            child_prio: u32::from(child.priority).into(),
+        })
+    }
+}
+
+/// A task deleted, modeled after the LTTng event
+#[derive(CtfEventClass)]
+#[event_name = "sched_process_exit"]
+#[event_id = 0x1016]
+pub struct SchedProcessExit<'a> {
+    pub src_event_type: &'a CStr,
+    pub comm: &'a CStr,
+    pub tid: i64,
+    pub prio: i64,
+}
+
+impl<'a> TryFrom<(EventType, &Context, &'a mut StringCache)> for SchedProcessExit<'a> {
+    type Error = Error;
+
+    fn try_from(value: (EventType, &Context, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_type(value.0)?;
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            src_event_type: value.2.get_type(&value.0),
+            comm: value.2.get_str(&value.1.name),
+            tid: u32::from(value.1.handle).into(),
+            prio: u32::from(value.1.priority).into(),
+        })
+    }
+}
+
+/// A deleted task's resources freed, follows its sched_process_exit
+#[derive(CtfEventClass)]
+#[event_name = "sched_process_free"]
+#[event_id = 0x1017]
+pub struct SchedProcessFree<'a> {
+    pub src_event_type: &'a CStr,
+    pub comm: &'a CStr,
+    pub tid: i64,
+    pub prio: i64,
+}
+
+impl<'a> TryFrom<(EventType, &Context, &'a mut StringCache)> for SchedProcessFree<'a> {
+    type Error = Error;
+
+    fn try_from(value: (EventType, &Context, &'a mut StringCache)) -> Result<Self, Self::Error> {
+        value.2.insert_type(value.0)?;
+        value.2.insert_str(&value.1.name)?;
+        Ok(Self {
+            src_event_type: value.2.get_type(&value.0),
+            comm: value.2.get_str(&value.1.name),
+            tid: u32::from(value.1.handle).into(),
+            prio: u32::from(value.1.priority).into(),
+        })
+    }
+}
+
+/// TASK_PRIORITY, TASK_PRIORITY_INHERIT and TASK_PRIORITY_DISINHERIT events, modeled after the
+/// LTTng event
+#[derive(CtfEventClass)]
+#[event_name = "sched_pi_setprio"]
+#[event_id = 0x1018]
+pub struct SchedPiSetprio<'a> {
+    pub src_event_type: &'a CStr,
+    pub comm: &'a CStr,
+    pub tid: i64,
+    pub oldprio: i64,
+    pub newprio: i64,
+}
+
+impl<'a> TryFrom<(EventType, &TaskEvent, Priority, &'a mut StringCache)> for SchedPiSetprio<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: (EventType, &TaskEvent, Priority, &'a mut StringCache),
+    ) -> Result<Self, Self::Error> {
+        value.3.insert_type(value.0)?;
+        value.3.insert_str(&value.1.name)?;
+        Ok(Self {
+            src_event_type: value.3.get_type(&value.0),
+            comm: value.3.get_str(&value.1.name),
+            tid: u32::from(value.1.handle).into(),
+            oldprio: u32::from(value.2).into(),
+            newprio: u32::from(value.1.priority).into(),
+        })
+    }
+}
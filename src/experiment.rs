@@ -0,0 +1,87 @@
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+/// Places the converted trace next to an existing host-side LTTng trace and writes
+/// the correlation metadata for setting up a Trace Compass experiment
+///
+/// The converted trace is symlinked (copied on non-unix platforms) into the host trace's
+/// parent directory, so importing that directory picks up both traces.
+pub fn create(
+    output: &Path,
+    host_trace: &Path,
+    trace_name: &str,
+    target_clock_offset_ns: Option<i64>,
+) -> io::Result<PathBuf> {
+    if !host_trace.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Host trace directory '{}' not found", host_trace.display()),
+        ));
+    }
+    let host_trace = host_trace.canonicalize()?;
+    let experiment_dir = host_trace.parent().unwrap_or(&host_trace).to_path_buf();
+    let target_trace = experiment_dir.join(trace_name);
+
+    if target_trace.is_symlink() {
+        fs::remove_file(&target_trace)?;
+    } else if target_trace.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists", target_trace.display()),
+        ));
+    }
+    link_or_copy(&output.canonicalize()?, &target_trace)?;
+
+    if target_clock_offset_ns.is_none() {
+        warn!("The target clock isn't aligned to the host, configure a clock_sync rule to correlate them");
+    }
+
+    let path = experiment_dir.join(format!("{trace_name}.experiment.json"));
+    let experiment = Experiment {
+        host_trace: host_trace.to_string_lossy(),
+        target_trace: target_trace.to_string_lossy(),
+        target_clock_origin_is_unix_epoch: target_clock_offset_ns.is_some(),
+        target_clock_offset_ns,
+    };
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, &experiment)?;
+    info!(experiment = %path.display(), "Wrote experiment metadata");
+
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn link_or_copy(src: &Path, dst: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(not(unix))]
+fn link_or_copy(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let dst = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_or_copy(&entry.path(), &dst)?;
+        } else {
+            fs::copy(entry.path(), dst)?;
+        }
+    }
+    Ok(())
+}
+
+/// Paths are written lossily, serde refuses non-UTF-8 paths
+#[derive(Serialize)]
+struct Experiment<'a> {
+    host_trace: Cow<'a, str>,
+    target_trace: Cow<'a, str>,
+    target_clock_origin_is_unix_epoch: bool,
+    /// Host time minus target time, the target CTF clock class offset
+    target_clock_offset_ns: Option<i64>,
+}
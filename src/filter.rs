@@ -0,0 +1,418 @@
+use crate::event_fields::{event_field, event_name, FieldValue};
+use std::{borrow::Cow, cmp::Ordering, fmt, str::FromStr};
+use trace_recorder_parser::streaming::event::{Event, EventType};
+
+/// A per-event filter expression
+///
+/// ```text
+/// expr    := and ('||' and)*
+/// and     := unary ('&&' unary)*
+/// unary   := '!' unary | '(' expr ')' | field op literal
+/// op      := '==' | '!=' | '<' | '<=' | '>' | '>='
+/// literal := "string" | integer (decimal or 0x hex)
+/// ```
+///
+/// `event` is the trace recorder event type name, other fields are the event's
+/// CTF payload fields. Comparisons against a field the event doesn't have are false.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn matches(&self, event_type: EventType, event: &Event) -> bool {
+        self.expr.eval(&(event_type, event))
+    }
+
+    /// Whether the events of the type (by name) pass the filter, None when it depends on
+    /// their payload fields
+    pub fn includes(&self, event_name: &str) -> Option<bool> {
+        self.expr.eval_name(event_name)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr()?;
+        if let Some(t) = parser.peek() {
+            return Err(format!("Unexpected '{t}' in filter expression"));
+        }
+        Ok(Self { expr })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Cmp(String, CmpOp, Literal),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Literal {
+    Int(i64),
+    Str(String),
+}
+
+/// The event name and fields a filter is evaluated against
+trait FilterInput {
+    fn name(&self) -> Cow<'_, str>;
+
+    fn field(&self, name: &str) -> Option<FieldValue<'_>>;
+}
+
+impl FilterInput for (EventType, &Event) {
+    fn name(&self) -> Cow<'_, str> {
+        event_name(self.0, self.1)
+    }
+
+    fn field(&self, name: &str) -> Option<FieldValue<'_>> {
+        event_field(self.1, name)
+    }
+}
+
+impl Expr {
+    fn eval<I: FilterInput>(&self, input: &I) -> bool {
+        match self {
+            Expr::And(l, r) => l.eval(input) && r.eval(input),
+            Expr::Or(l, r) => l.eval(input) || r.eval(input),
+            Expr::Not(e) => !e.eval(input),
+            Expr::Cmp(field, op, literal) => {
+                let ordering = if field == "event" {
+                    let name = input.name();
+                    match literal {
+                        Literal::Str(s) => Some(name.as_ref().cmp(s.as_str())),
+                        Literal::Int(_) => None,
+                    }
+                } else {
+                    match (input.field(field), literal) {
+                        (Some(FieldValue::Str(v)), Literal::Str(s)) => Some(v.cmp(s.as_str())),
+                        (Some(FieldValue::Int(v)), Literal::Int(i)) => Some(v.cmp(i)),
+                        _ => None,
+                    }
+                };
+                ordering.map(|o| op.is_satisfied_by(o)).unwrap_or(false)
+            }
+        }
+    }
+}
+
+impl Expr {
+    fn eval_name(&self, name: &str) -> Option<bool> {
+        match self {
+            Expr::And(l, r) => match (l.eval_name(name), r.eval_name(name)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Expr::Or(l, r) => match (l.eval_name(name), r.eval_name(name)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Expr::Not(e) => e.eval_name(name).map(|b| !b),
+            Expr::Cmp(field, op, literal) if field == "event" => Some(match literal {
+                Literal::Str(s) => op.is_satisfied_by(name.cmp(s.as_str())),
+                Literal::Int(_) => false,
+            }),
+            Expr::Cmp(..) => None,
+        }
+    }
+}
+
+impl CmpOp {
+    fn is_satisfied_by(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering.is_eq(),
+            CmpOp::Ne => ordering.is_ne(),
+            CmpOp::Lt => ordering.is_lt(),
+            CmpOp::Le => ordering.is_le(),
+            CmpOp::Gt => ordering.is_gt(),
+            CmpOp::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(Literal),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => f.write_str(s),
+            Token::Literal(Literal::Int(i)) => write!(f, "{i}"),
+            Token::Literal(Literal::Str(s)) => write!(f, "\"{s}\""),
+            Token::Op(op) => f.write_str(match op {
+                CmpOp::Eq => "==",
+                CmpOp::Ne => "!=",
+                CmpOp::Lt => "<",
+                CmpOp::Le => "<=",
+                CmpOp::Gt => ">",
+                CmpOp::Ge => ">=",
+            }),
+            Token::And => f.write_str("&&"),
+            Token::Or => f.write_str("||"),
+            Token::Not => f.write_str("!"),
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next_is = |chars: &mut std::iter::Peekable<std::str::Chars>, n: char| {
+            chars.next_if_eq(&n).is_some()
+        };
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' if next_is(&mut chars, '&') => Token::And,
+            '|' if next_is(&mut chars, '|') => Token::Or,
+            '=' if next_is(&mut chars, '=') => Token::Op(CmpOp::Eq),
+            '!' if next_is(&mut chars, '=') => Token::Op(CmpOp::Ne),
+            '!' => Token::Not,
+            '<' if next_is(&mut chars, '=') => Token::Op(CmpOp::Le),
+            '<' => Token::Op(CmpOp::Lt),
+            '>' if next_is(&mut chars, '=') => Token::Op(CmpOp::Ge),
+            '>' => Token::Op(CmpOp::Gt),
+            '"' => {
+                let mut lit = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => lit.push(c),
+                            None => return Err("Unterminated string in filter expression".into()),
+                        },
+                        Some(c) => lit.push(c),
+                        None => return Err("Unterminated string in filter expression".into()),
+                    }
+                }
+                Token::Literal(Literal::Str(lit))
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut lit = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric()) {
+                    lit.push(c);
+                }
+                Token::Literal(Literal::Int(parse_int(&lit).ok_or_else(|| {
+                    format!("Invalid integer '{lit}' in filter expression")
+                })?))
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(format!("Unexpected '{c}' in filter expression")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Decimal or 0x hex, with an optional sign
+fn parse_int(lit: &str) -> Option<i64> {
+    let (negative, digits) = match lit.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, lit),
+    };
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+    i64::try_from(if negative { -magnitude } else { magnitude }).ok()
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let expr = self.expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err("Missing ')' in filter expression".into()),
+                }
+            }
+            Some(Token::Ident(field)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(format!("Expected a comparison after '{field}'")),
+                };
+                match self.next() {
+                    Some(Token::Literal(literal)) => Ok(Expr::Cmp(field, op, literal)),
+                    _ => Err(format!(
+                        "Expected a string or integer to compare '{field}' with"
+                    )),
+                }
+            }
+            Some(t) => Err(format!("Unexpected '{t}' in filter expression")),
+            None => Err("Unexpected end of filter expression".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestEvent {
+        name: &'static str,
+        fields: &'static [(&'static str, FieldValue<'static>)],
+    }
+
+    impl FilterInput for TestEvent {
+        fn name(&self) -> Cow<'_, str> {
+            Cow::Borrowed(self.name)
+        }
+
+        fn field(&self, name: &str) -> Option<FieldValue<'_>> {
+            self.fields
+                .iter()
+                .find(|(f, _)| *f == name)
+                .map(|(_, v)| *v)
+        }
+    }
+
+    const ALLOC: TestEvent = TestEvent {
+        name: "MEMORY_ALLOC",
+        fields: &[
+            ("address", FieldValue::Int(0x2000_0000)),
+            ("size", FieldValue::Int(64)),
+        ],
+    };
+    const USER: TestEvent = TestEvent {
+        name: "USER_EVENT",
+        fields: &[("channel", FieldValue::Str("comms"))],
+    };
+
+    fn eval(filter: &str, event: &TestEvent) -> bool {
+        filter.parse::<Filter>().unwrap().expr.eval(event)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let filter = r#"event == "USER_EVENT" || event == "MEMORY_ALLOC" && size > 100"#;
+        assert!(eval(filter, &USER));
+        assert!(!eval(filter, &ALLOC));
+        let filter = r#"(event == "USER_EVENT" || event == "MEMORY_ALLOC") && size > 100"#;
+        assert!(!eval(filter, &USER));
+    }
+
+    #[test]
+    fn not_and_not_equal() {
+        assert!(eval(r#"event != "USER_EVENT""#, &ALLOC));
+        assert!(eval(r#"!event == "USER_EVENT""#, &ALLOC));
+        assert!(eval(r#"!(event != "MEMORY_ALLOC")"#, &ALLOC));
+        assert!(eval("!!size == 64", &ALLOC));
+        assert!("! = 1".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn integer_literals() {
+        assert!(eval("address == 0x20000000", &ALLOC));
+        assert!(eval("size == 0x40 && size == 64", &ALLOC));
+        assert!(eval("size > -0x10 && size > -16", &ALLOC));
+        assert_eq!(parse_int("-0x10"), Some(-16));
+        assert_eq!(parse_int("-0x8000000000000000"), Some(i64::MIN));
+        assert_eq!(parse_int("0x8000000000000000"), None);
+        assert_eq!(parse_int("-"), None);
+        assert_eq!(parse_int("0x"), None);
+        assert!("size == 12abc".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn missing_fields_compare_false() {
+        assert!(!eval("size == 64", &USER));
+        assert!(!eval("size != 64", &USER));
+        assert!(eval("!(size == 64)", &USER));
+        assert!(eval("!(size >= 0x1000)", &USER));
+        // Type mismatches are missing fields too
+        assert!(!eval(r#"size == "64""#, &ALLOC));
+        assert!(!eval("event == 1", &ALLOC));
+    }
+
+    #[test]
+    fn includes_by_name() {
+        let filter: Filter = r#"event == "USER_EVENT" && channel == "comms""#.parse().unwrap();
+        assert_eq!(filter.includes("USER_EVENT"), None);
+        assert_eq!(filter.includes("TASK_READY"), Some(false));
+
+        let filter: Filter = r#"event == "TASK_READY" || channel == "comms""#.parse().unwrap();
+        assert_eq!(filter.includes("TASK_READY"), Some(true));
+        assert_eq!(filter.includes("USER_EVENT"), None);
+
+        let filter: Filter = r#"!(event == "MEMORY_FREE" || size >= 0x1000)"#.parse().unwrap();
+        assert_eq!(filter.includes("MEMORY_FREE"), Some(false));
+        assert_eq!(filter.includes("MEMORY_ALLOC"), None);
+
+        let filter: Filter = r#"event >= "TASK_" && event != "TASK_READY""#.parse().unwrap();
+        assert_eq!(filter.includes("TASK_RESUME"), Some(true));
+        assert_eq!(filter.includes("TASK_READY"), Some(false));
+        assert_eq!(filter.includes("ISR_BEGIN"), Some(false));
+    }
+}
@@ -0,0 +1,46 @@
+//! Fuzzing entry point, runs the parser and the converter's state tracking without babeltrace
+
+use crate::{
+    contexts::{Context, ContextTracker},
+    trackers::{EventCountStrategy, TimestampStrategy},
+};
+use std::io::Cursor;
+use trace_recorder_parser::streaming::{event::Event, RecorderData};
+
+/// Parse the input as a streaming trace and drive the context, ISR stack and rollover trackers
+/// the way a conversion does, stopping at the first data error.
+/// Panics only on bugs, which is what a fuzzer is looking for.
+pub fn convert_bytes(data: &[u8]) {
+    let mut reader = Cursor::new(data);
+    let Ok(mut trd) = RecorderData::find(&mut reader) else {
+        return;
+    };
+
+    let mut contexts = ContextTracker::default();
+    let mut event_counter_tracker = EventCountStrategy::default().tracker();
+    let mut time_rollover_tracker = TimestampStrategy::default().tracker();
+    let mut first_event_observed = false;
+
+    while let Ok(Some((_event_code, event))) = trd.read_event(&mut reader) {
+        if !first_event_observed {
+            first_event_observed = true;
+            event_counter_tracker.init(event.event_count());
+            time_rollover_tracker.init(event.timestamp(), trd.timestamp_info.timer_wraparounds);
+        } else {
+            event_counter_tracker.update(event.event_count());
+        }
+        time_rollover_tracker.elapsed(event.timestamp());
+
+        match event {
+            Event::TaskResume(ev) | Event::TaskActivate(ev) => {
+                contexts.isr_returned();
+                contexts.switch_to(Context::from(ev));
+            }
+            Event::IsrBegin(ev) => contexts.isr_begin(Context::from(ev)),
+            Event::IsrResume(ev) if contexts.in_isr() => {
+                contexts.isr_resume(&Context::from(ev));
+            }
+            _ => (),
+        }
+    }
+}
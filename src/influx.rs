@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use trace_recorder_parser::{
+    streaming::event::Event,
+    time::{Frequency, Timestamp},
+    types::Heap,
+};
+
+/// Writes time series derived from the events in InfluxDB line protocol,
+/// one point per series and window
+pub struct InfluxExport {
+    writer: BufWriter<File>,
+    /// Escaped trace name tag value
+    trace: String,
+    timer_frequency: u128,
+    /// Added to the trace time, e.g. the host wall-clock offset
+    offset_ns: i64,
+    window_ns: u64,
+    /// Current window, nanoseconds
+    window: Option<(u64, u64)>,
+    /// Switched in task and since when
+    running: Option<(String, u64)>,
+    task_ns: BTreeMap<String, u64>,
+    isr_counts: BTreeMap<String, u64>,
+    heap: Option<Heap>,
+    /// Last and max messages waiting per queue
+    queues: BTreeMap<String, (u32, u32)>,
+}
+
+impl InfluxExport {
+    pub fn new(
+        path: &Path,
+        trace_name: &str,
+        timer_frequency: Frequency,
+        offset_ns: i64,
+        window_ms: u64,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            trace: escape(trace_name),
+            timer_frequency: u128::from(timer_frequency.get_raw()).max(1),
+            offset_ns,
+            window_ns: window_ms.max(1) * 1_000_000,
+            window: None,
+            running: None,
+            task_ns: BTreeMap::new(),
+            isr_counts: BTreeMap::new(),
+            heap: None,
+            queues: BTreeMap::new(),
+        })
+    }
+
+    fn to_ns(&self, timestamp: Timestamp) -> u64 {
+        (u128::from(timestamp.ticks()) * 1_000_000_000 / self.timer_frequency) as u64
+    }
+
+    pub fn observe(&mut self, event: &Event, timestamp: Timestamp) -> io::Result<()> {
+        let ns = self.to_ns(timestamp);
+        let (mut start, mut end) = *self.window.get_or_insert_with(|| {
+            let start = ns - ns % self.window_ns;
+            (start, start + self.window_ns)
+        });
+        while ns >= end {
+            self.write_window(start, end)?;
+            start = end;
+            end += self.window_ns;
+        }
+        self.window = Some((start, end));
+
+        match event {
+            Event::TaskResume(ev) | Event::TaskActivate(ev) => {
+                self.account_running(ns);
+                self.running = Some((ev.name.to_string(), ns));
+            }
+            Event::IsrBegin(ev) => *self.isr_counts.entry(ev.name.to_string()).or_default() += 1,
+            Event::MemoryAlloc(ev) | Event::MemoryFree(ev) => self.heap = Some(ev.heap),
+            Event::QueueSend(ev)
+            | Event::QueueSendBlock(ev)
+            | Event::QueueSendFromIsr(ev)
+            | Event::QueueSendFront(ev)
+            | Event::QueueSendFrontBlock(ev)
+            | Event::QueueSendFrontFromIsr(ev)
+            | Event::QueueReceive(ev)
+            | Event::QueueReceiveBlock(ev)
+            | Event::QueueReceiveFromIsr(ev)
+            | Event::QueuePeek(ev)
+            | Event::QueuePeekBlock(ev) => {
+                let name = match &ev.name {
+                    Some(name) => name.to_string(),
+                    None => format!("{}", u32::from(ev.handle)),
+                };
+                let depth = self.queues.entry(name).or_default();
+                depth.0 = ev.messages_waiting;
+                depth.1 = depth.1.max(ev.messages_waiting);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Write the last, partial, window ending at the timestamp and flush
+    pub fn finish(&mut self, timestamp: Timestamp) -> io::Result<()> {
+        if let Some((start, _)) = self.window.take() {
+            let end = self.to_ns(timestamp).max(start + 1);
+            self.write_window(start, end)?;
+        }
+        self.writer.flush()
+    }
+
+    fn account_running(&mut self, ns: u64) {
+        if let Some((task, since)) = self.running.as_mut() {
+            *self.task_ns.entry(task.clone()).or_default() += ns.saturating_sub(*since);
+            *since = ns;
+        }
+    }
+
+    fn write_window(&mut self, start: u64, end: u64) -> io::Result<()> {
+        self.account_running(end);
+        let point_ns = (i128::from(end) + i128::from(self.offset_ns)).max(0);
+        let duration_ns = (end - start) as f64;
+        let trace = &self.trace;
+        let w = &mut self.writer;
+
+        for (task, ns) in std::mem::take(&mut self.task_ns).into_iter() {
+            let load = ns as f64 / duration_ns;
+            writeln!(
+                w,
+                "task_cpu_load,trace={trace},task={} load={load} {point_ns}",
+                escape(&task)
+            )?;
+        }
+        for (isr, count) in std::mem::take(&mut self.isr_counts).into_iter() {
+            let rate = count as f64 * 1e9 / duration_ns;
+            writeln!(
+                w,
+                "isr_rate,trace={trace},isr={} count={count}i,rate={rate} {point_ns}",
+                escape(&isr)
+            )?;
+        }
+        if let Some(heap) = self.heap.as_ref() {
+            writeln!(
+                w,
+                "heap,trace={trace} current={}i,high_water_mark={}i,max={}i {point_ns}",
+                heap.current, heap.high_water_mark, heap.max
+            )?;
+        }
+        for (queue, (last, max)) in std::mem::take(&mut self.queues).into_iter() {
+            writeln!(
+                w,
+                "queue_depth,trace={trace},queue={} messages_waiting={last}i,max={max}i {point_ns}",
+                escape(&queue)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape a tag value
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    if escaped.is_empty() {
+        // Empty tag values aren't allowed
+        escaped.push('-');
+    }
+    escaped
+}
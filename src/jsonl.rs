@@ -0,0 +1,118 @@
+use babeltrace2_sys::{ffi, BtResultExt};
+use serde_json::{Map, Value};
+use std::ffi::CStr;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    os::raw::c_char,
+    path::Path,
+    ptr,
+};
+use trace_recorder_parser::time::Frequency;
+use trace_recorder_to_ctf::{write_json_line, ConvertedEvent};
+
+/// Writes the converted events as JSON Lines, one object per event
+pub struct JsonLinesWriter {
+    writer: BufWriter<Box<dyn Write>>,
+    timer_frequency: u128,
+}
+
+impl JsonLinesWriter {
+    /// Writes to stdout when the path is '-'
+    pub fn new(path: &Path, timer_frequency: Frequency) -> io::Result<Self> {
+        let writer: Box<dyn Write> = if path == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(Self {
+            writer: BufWriter::new(writer),
+            timer_frequency: u128::from(timer_frequency.get_raw()).max(1),
+        })
+    }
+
+    /// Write the event messages, others are skipped
+    pub fn write_messages(&mut self, msgs: &[*const ffi::bt_message]) -> io::Result<()> {
+        for msg in msgs.iter().copied() {
+            if unsafe { ffi::bt_message_get_type(msg) }
+                != ffi::bt_message_type::BT_MESSAGE_TYPE_EVENT
+            {
+                continue;
+            }
+            let line = unsafe {
+                let event = ffi::bt_message_event_borrow_event_const(msg);
+                let event_class = ffi::bt_event_borrow_class_const(event);
+                let timestamp = ffi::bt_clock_snapshot_get_value(
+                    ffi::bt_message_event_borrow_default_clock_snapshot_const(msg),
+                );
+                ConvertedEvent {
+                    name: str_value(ffi::bt_event_class_get_name(event_class)).to_owned(),
+                    timestamp,
+                    timestamp_ns: (u128::from(timestamp) * 1_000_000_000 / self.timer_frequency)
+                        as u64,
+                    common: struct_value(ffi::bt_event_borrow_common_context_field_const(event)),
+                    payload: struct_value(ffi::bt_event_borrow_payload_field_const(event)),
+                }
+            };
+            write_json_line(&mut self.writer, &line)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+unsafe fn str_value<'a>(s: *const c_char) -> &'a str {
+    if s.is_null() {
+        return "";
+    }
+    CStr::from_ptr(s).to_str().unwrap_or("<invalid>")
+}
+
+unsafe fn struct_value(field: *const ffi::bt_field) -> Map<String, Value> {
+    let mut map = Map::new();
+    if field.is_null() {
+        return map;
+    }
+    let fc = ffi::bt_field_borrow_class_const(field);
+    for i in 0..ffi::bt_field_class_structure_get_member_count(fc) {
+        let name = str_value(ffi::bt_field_class_structure_member_get_name(
+            ffi::bt_field_class_structure_borrow_member_by_index_const(fc, i),
+        ));
+        let member = ffi::bt_field_structure_borrow_member_field_by_index_const(field, i);
+        map.insert(name.to_owned(), field_value(member));
+    }
+    map
+}
+
+unsafe fn field_value(field: *const ffi::bt_field) -> Value {
+    use ffi::bt_field_class_type::*;
+    match ffi::bt_field_get_class_type(field) {
+        BT_FIELD_CLASS_TYPE_BOOL => Value::from(ffi::bt_field_bool_get_value(field) != 0),
+        BT_FIELD_CLASS_TYPE_STRING => Value::from(str_value(ffi::bt_field_string_get_value(field))),
+        BT_FIELD_CLASS_TYPE_UNSIGNED_INTEGER | BT_FIELD_CLASS_TYPE_UNSIGNED_ENUMERATION => {
+            Value::from(ffi::bt_field_integer_unsigned_get_value(field))
+        }
+        BT_FIELD_CLASS_TYPE_SIGNED_INTEGER => {
+            Value::from(ffi::bt_field_integer_signed_get_value(field))
+        }
+        // The enum's label, all of the converter's enums are signed
+        BT_FIELD_CLASS_TYPE_SIGNED_ENUMERATION => {
+            let mut labels: *const *const c_char = ptr::null();
+            let mut count = 0;
+            let ret =
+                ffi::bt_field_enumeration_signed_get_mapping_labels(field, &mut labels, &mut count);
+            if ret.capi_result().is_ok() && count != 0 {
+                Value::from(str_value(*labels))
+            } else {
+                Value::from(ffi::bt_field_integer_signed_get_value(field))
+            }
+        }
+        BT_FIELD_CLASS_TYPE_DOUBLE_PRECISION_REAL => {
+            Value::from(ffi::bt_field_real_double_precision_get_value(field))
+        }
+        _ => Value::Null,
+    }
+}
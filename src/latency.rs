@@ -0,0 +1,157 @@
+use crate::event_fields::{event_name, fields_match};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+use trace_recorder_parser::{
+    streaming::event::{Event, EventType},
+    time::{Frequency, Timestamp},
+};
+
+/// Events of a type whose payload fields equal the given values
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventPattern {
+    /// Trace recorder event type name, e.g. ISR_BEGIN
+    pub event: String,
+    /// CTF payload field name to value
+    #[serde(default)]
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl EventPattern {
+    fn matches(&self, name: &str, event: &Event) -> bool {
+        self.event == name && fields_match(event, &self.fields)
+    }
+}
+
+/// A named latency budget from a start event to the following end event
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LatencyBudget {
+    pub name: String,
+    pub start: EventPattern,
+    pub end: EventPattern,
+    pub max_us: u64,
+}
+
+#[derive(Debug)]
+pub struct Violation {
+    pub budget: String,
+    pub latency_ns: u64,
+    pub budget_ns: u64,
+}
+
+#[derive(Debug, Default)]
+struct BudgetStats {
+    /// Start times (nanoseconds) not yet matched by an end event
+    pending: VecDeque<u64>,
+    occurrences: u64,
+    violations: u64,
+    min_ns: Option<u64>,
+    max_ns: Option<u64>,
+    total_ns: u128,
+}
+
+/// Measures each occurrence of the configured latency budgets
+#[derive(Debug)]
+pub struct LatencyChecker {
+    path: PathBuf,
+    timer_frequency: Frequency,
+    budgets: Vec<(LatencyBudget, BudgetStats)>,
+}
+
+impl LatencyChecker {
+    pub fn new(path: PathBuf, timer_frequency: Frequency, budgets: Vec<LatencyBudget>) -> Self {
+        Self {
+            path,
+            timer_frequency,
+            budgets: budgets
+                .into_iter()
+                .map(|b| (b, BudgetStats::default()))
+                .collect(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the budget violations ended by the event
+    pub fn observe(
+        &mut self,
+        event_type: EventType,
+        event: &Event,
+        timestamp: Timestamp,
+    ) -> Vec<Violation> {
+        let freq = u128::from(self.timer_frequency.get_raw()).max(1);
+        let time_ns = (u128::from(timestamp.ticks()) * 1_000_000_000 / freq) as u64;
+        let name = event_name(event_type, event);
+        let mut violations = Vec::new();
+        for (budget, stats) in self.budgets.iter_mut() {
+            if budget.end.matches(&name, event) {
+                if let Some(start_ns) = stats.pending.pop_front() {
+                    let latency_ns = time_ns.saturating_sub(start_ns);
+                    let budget_ns = budget.max_us.saturating_mul(1_000);
+                    stats.occurrences += 1;
+                    stats.total_ns += u128::from(latency_ns);
+                    stats.min_ns = Some(stats.min_ns.map_or(latency_ns, |m| m.min(latency_ns)));
+                    stats.max_ns = Some(stats.max_ns.map_or(latency_ns, |m| m.max(latency_ns)));
+                    if latency_ns > budget_ns {
+                        stats.violations += 1;
+                        violations.push(Violation {
+                            budget: budget.name.clone(),
+                            latency_ns,
+                            budget_ns,
+                        });
+                    }
+                }
+            }
+            if budget.start.matches(&name, event) {
+                stats.pending.push_back(time_ns);
+            }
+        }
+        violations
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        let budgets = self
+            .budgets
+            .iter()
+            .map(|(budget, stats)| BudgetDump {
+                name: &budget.name,
+                max_us: budget.max_us,
+                passed: stats.violations == 0,
+                occurrences: stats.occurrences,
+                violations: stats.violations,
+                min_ns: stats.min_ns,
+                max_ns: stats.max_ns,
+                mean_ns: (stats.occurrences != 0)
+                    .then(|| (stats.total_ns / u128::from(stats.occurrences)) as u64),
+            })
+            .collect();
+        let writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(writer, &LatencyDump { budgets })?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct LatencyDump<'a> {
+    budgets: Vec<BudgetDump<'a>>,
+}
+
+#[derive(Serialize)]
+struct BudgetDump<'a> {
+    name: &'a str,
+    max_us: u64,
+    passed: bool,
+    occurrences: u64,
+    violations: u64,
+    min_ns: Option<u64>,
+    max_ns: Option<u64>,
+    mean_ns: Option<u64>,
+}
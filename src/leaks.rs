@@ -0,0 +1,128 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    mem::size_of,
+    path::Path,
+};
+use trace_recorder_parser::{
+    streaming::event::EventCode,
+    time::{Frequency, Timestamp},
+    types::ObjectName,
+};
+
+/// An allocation not freed (yet)
+#[derive(Clone, Debug)]
+pub struct Allocation {
+    pub address: u32,
+    pub size: u32,
+    /// Task (or ISR) that allocated it
+    pub task: ObjectName,
+    /// Event code, counter and raw timestamp of the MEMORY_ALLOC event, for the common context
+    pub event_code: EventCode,
+    pub event_count: u64,
+    pub raw_timestamp: Timestamp,
+    pub timestamp: Timestamp,
+}
+
+/// Pairs MEMORY_ALLOC and MEMORY_FREE events by address
+#[derive(Debug, Default)]
+pub struct LeakTracker {
+    live: HashMap<u32, Allocation>,
+    /// Frees of addresses without a matching allocation, e.g. allocated before the trace started
+    unmatched_frees: u64,
+    /// Unfreed allocations left to emit as leak_candidate events, taken at the end of the trace
+    candidates: Option<VecDeque<Allocation>>,
+}
+
+impl LeakTracker {
+    pub fn alloc(&mut self, allocation: Allocation) {
+        self.live.insert(allocation.address, allocation);
+    }
+
+    pub fn free(&mut self, address: u32) {
+        if self.live.remove(&address).is_none() {
+            self.unmatched_frees += 1;
+        }
+    }
+
+    /// Drop the oldest half of the unfreed allocations, returns the number dropped
+    pub fn shed_oldest(&mut self) -> usize {
+        let mut oldest: Vec<_> = self
+            .live
+            .values()
+            .map(|a| (a.timestamp, a.address))
+            .collect();
+        oldest.sort_unstable();
+        oldest.truncate(oldest.len() / 2);
+        for (_, address) in oldest.iter() {
+            self.live.remove(address);
+        }
+        self.live.shrink_to_fit();
+        oldest.len()
+    }
+
+    /// Estimated heap bytes
+    pub fn estimated_size(&self) -> usize {
+        self.live
+            .values()
+            .map(|a| size_of::<(u32, Allocation)>() + a.task.len())
+            .sum()
+    }
+
+    /// Unfreed allocations, oldest first
+    fn unfreed(&self) -> Vec<&Allocation> {
+        let mut allocations: Vec<_> = self.live.values().collect();
+        allocations.sort_by_key(|a| (a.timestamp, a.address));
+        allocations
+    }
+
+    /// The next unfreed allocation to emit as a leak_candidate event
+    pub fn next_candidate(&mut self) -> Option<Allocation> {
+        if self.candidates.is_none() {
+            self.candidates = Some(self.unfreed().into_iter().cloned().collect());
+        }
+        self.candidates.as_mut().and_then(|c| c.pop_front())
+    }
+
+    pub fn write(&self, path: &Path, timer_frequency: Frequency) -> io::Result<()> {
+        let freq = u128::from(timer_frequency.get_raw()).max(1);
+        let to_ns = |t: Timestamp| (u128::from(t.ticks()) * 1_000_000_000 / freq) as u64;
+        let allocations: Vec<_> = self
+            .unfreed()
+            .into_iter()
+            .map(|a| AllocationDump {
+                address: a.address,
+                size: a.size,
+                task: &a.task,
+                first_seen_ns: to_ns(a.timestamp),
+            })
+            .collect();
+        let dump = LeakDump {
+            unfreed_allocations: allocations.len(),
+            unfreed_bytes: allocations.iter().map(|a| u64::from(a.size)).sum(),
+            unmatched_frees: self.unmatched_frees,
+            allocations,
+        };
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &dump)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct LeakDump<'a> {
+    unfreed_allocations: usize,
+    unfreed_bytes: u64,
+    unmatched_frees: u64,
+    allocations: Vec<AllocationDump<'a>>,
+}
+
+#[derive(Serialize)]
+struct AllocationDump<'a> {
+    address: u32,
+    size: u32,
+    task: &'a str,
+    first_seen_ns: u64,
+}
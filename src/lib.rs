@@ -0,0 +1,142 @@
+//! The parts of the converter that don't need babeltrace
+//!
+//! The conversion itself needs babeltrace, it's only available through the binary.
+//! [`JsonLinesReader`] reads back the events written by `--format jsonl`, to build custom analyses
+//! without touching the CTF or FFI.
+//! The `fuzzing` feature exposes `fuzzing::convert_bytes` for a cargo-fuzz harness.
+
+pub mod contexts;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod trackers;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// A converted event, as written by `--format jsonl`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConvertedEvent {
+    /// Event class name, e.g. `sched_switch`
+    pub name: String,
+    /// Timer ticks
+    pub timestamp: u64,
+    pub timestamp_ns: u64,
+    /// Event common context fields (`id`, `event_count`, `timer`, ...)
+    pub common: Map<String, Value>,
+    /// Payload fields, enums are their label
+    pub payload: Map<String, Value>,
+}
+
+impl ConvertedEvent {
+    /// A payload field as a string
+    pub fn payload_str(&self, field: &str) -> Option<&str> {
+        self.payload.get(field).and_then(Value::as_str)
+    }
+
+    /// A payload field as an unsigned integer
+    pub fn payload_u64(&self, field: &str) -> Option<u64> {
+        self.payload.get(field).and_then(Value::as_u64)
+    }
+
+    /// A payload field as a signed integer
+    pub fn payload_i64(&self, field: &str) -> Option<i64> {
+        self.payload.get(field).and_then(Value::as_i64)
+    }
+}
+
+/// Write an event as a JSON Lines line
+pub fn write_json_line<W: Write>(writer: &mut W, event: &ConvertedEvent) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, event)?;
+    writer.write_all(b"\n")
+}
+
+/// Reads the converted events of a JSON Lines output written by the converter, in order
+pub struct JsonLinesReader<R> {
+    lines: io::Lines<R>,
+}
+
+impl JsonLinesReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> JsonLinesReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonLinesReader<R> {
+    type Item = io::Result<ConvertedEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Some(serde_json::from_str(&line).map_err(io::Error::from));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(name: &str, timestamp: u64) -> ConvertedEvent {
+        let Value::Object(common) =
+            json!({ "event_count": timestamp, "id": 6, "timer": timestamp })
+        else {
+            unreachable!()
+        };
+        let Value::Object(payload) =
+            json!({ "next_comm": "CLI", "next_prio": 1, "prev_state": "TASK_RUNNING" })
+        else {
+            unreachable!()
+        };
+        ConvertedEvent {
+            name: name.to_owned(),
+            timestamp,
+            timestamp_ns: timestamp * 10,
+            common,
+            payload,
+        }
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let events = vec![event("sched_switch", 1502), event("USER_EVENT", 1600)];
+        let mut buf = Vec::new();
+        for ev in events.iter() {
+            write_json_line(&mut buf, ev).unwrap();
+        }
+        buf.extend_from_slice(b"\n");
+        let read = JsonLinesReader::new(buf.as_slice())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, events);
+        assert_eq!(read[0].payload_str("next_comm"), Some("CLI"));
+        assert_eq!(read[0].payload_u64("next_prio"), Some(1));
+    }
+
+    #[test]
+    fn json_lines_invalid_line() {
+        let mut iter = JsonLinesReader::new(&b"{\"name\":\"x\"}\n"[..]);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(iter.next().is_none());
+    }
+}
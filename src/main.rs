@@ -1,36 +1,129 @@
 #![allow(clippy::manual_c_str_literals)]
 
-use crate::{convert::TrcCtfConverter, types::BorrowedCtfState};
+use crate::{
+    anomaly::AnomalyDetector,
+    clock_sync::ClockSync,
+    config::{Config, PacketContextValue},
+    convert::TrcCtfConverter,
+    counters::{is_duplicate_event_count, CountingReader, Heartbeat, ThroughputCounters},
+    filter::Filter,
+    influx::InfluxExport,
+    jsonl::JsonLinesWriter,
+    latency::LatencyChecker,
+    markers::Markers,
+    memory::{MemoryMonitor, MemoryUsage},
+    os_ticks::OsTickClock,
+    pipeline::{CtfSinkParams, PipelineDescription, ScratchDir, SinkParam},
+    prio::PrioMapping,
+    profile::Profile,
+    replay::Replay,
+    sampling::{SampleRate, Sampler},
+    snapshot::{ContextSnapshot, RecentEvents, StateSnapshot},
+    stats::{ConversionSummary, SharedStats, WarningCounter, LARGEST_EVENT_CLASSES},
+    symbols::Symbols,
+    task_groups::TaskGroups,
+    tick_rate::{TickRateCheck, TickRateEstimate, TimestampScale},
+    tui::Tui,
+    types::BorrowedCtfState,
+};
 use babeltrace2_sys::{
-    ffi, source_plugin_descriptors, BtResult, BtResultExt, CtfPluginSinkFsInitParams,
-    EncoderPipeline, Error, LoggingLevel, MessageIteratorStatus, Plugin, RunStatus, SelfComponent,
-    SelfMessageIterator, SourcePluginDescriptor, SourcePluginHandler,
+    ffi, source_plugin_descriptors, BtResult, BtResultExt, EncoderPipeline, Error, LoggingLevel,
+    MessageIteratorStatus, Plugin, RunStatus, SelfComponent, SelfMessageIterator,
+    SourcePluginDescriptor, SourcePluginHandler,
 };
 use chrono::prelude::{DateTime, Utc};
-use clap::Parser;
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use interruptor::Interruptor;
+use regex::Regex;
 use std::{
+    collections::VecDeque,
     ffi::{CStr, CString},
-    fs::File,
-    io::BufReader,
-    path::PathBuf,
+    fs::{self, File},
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom},
+    mem,
+    path::{Path, PathBuf},
     ptr,
+    time::{Duration, Instant},
 };
 use trace_recorder_parser::{
-    streaming::event::{Event, EventCode, EventType, TrackingEventCounter},
+    streaming::event::{Event, EventCode, EventType},
     streaming::RecorderData,
-    time::StreamingInstant,
+    time::Timestamp,
+};
+use trace_recorder_to_ctf::trackers::{
+    EventCountStrategy, EventCountTracker, TimestampStrategy, TimestampTracker,
 };
 use tracing::{debug, error, info, warn};
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
 
+mod anomaly;
+mod clock_sync;
+mod coalesce;
+mod compare;
+mod compress;
+mod config;
 mod convert;
+mod counters;
+mod demangle;
+mod dictionary;
+mod event_fields;
+mod event_list;
 mod events;
+mod experiment;
+mod filter;
+mod influx;
 mod interruptor;
+mod jsonl;
+mod latency;
+mod leaks;
+mod markers;
+mod memory;
+mod message_buffers;
+mod mutex;
+mod os_ticks;
+mod pipeline;
+mod prio;
+mod probe;
+mod profile;
+mod recover;
+mod replay;
+mod sampling;
+mod sched_lock;
+mod slice;
+mod snapshot;
+mod softirq;
+mod stats;
+mod symbols;
+mod task_groups;
+mod task_state;
+mod telemetry;
+mod tick_rate;
+mod tui;
 mod types;
+mod units;
+mod user_args;
+mod verify;
 
 /// Convert FreeRTOS trace-recorder traces to CTF
 #[derive(Parser, Debug, Clone)]
-#[clap(version)]
+#[clap(
+    version,
+    long_version = probe::LONG_VERSION,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    #[clap(flatten)]
+    pub opts: Opts,
+}
+
+/// Conversion options
+#[derive(Args, Debug, Clone)]
 pub struct Opts {
     /// The CTF clock class name
     #[clap(long, default_value = "monotonic")]
@@ -40,32 +133,535 @@ pub struct Opts {
     #[clap(long, default_value = "freertos")]
     pub trace_name: String,
 
+    /// The emulated tracer name, tracer_name environment entry
+    #[clap(long, default_value = "lttng-modules")]
+    pub tracer_name: String,
+
+    /// The emulated tracer major version, tracer_major environment entry
+    #[clap(long, default_value_t = 2)]
+    pub tracer_major: u32,
+
+    /// The emulated tracer minor version, tracer_minor environment entry
+    #[clap(long, default_value_t = 12)]
+    pub tracer_minor: u32,
+
+    /// The emulated tracer patch level, tracer_patchlevel environment entry
+    #[clap(long, default_value_t = 5)]
+    pub tracer_patchlevel: u32,
+
+    /// The trace_buffering_scheme environment entry
+    #[clap(long, default_value = "global", value_parser = ["global", "uid", "pid"])]
+    pub trace_buffering_scheme: String,
+
+    /// Skip this many bytes at the start of the input, e.g. a boot log before the trace
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    pub skip_bytes: u64,
+
+    /// Demux a capture where the trace data is interleaved with other data (e.g. console logs
+    /// sharing a UART), writing the non-trace data to this text file
+    #[clap(long, value_name = "FILE")]
+    pub demux: Option<PathBuf>,
+
+    /// Scan the input for the PSF header instead of expecting it at the start
+    /// (after --skip-bytes)
+    #[clap(long)]
+    pub seek_header: bool,
+
+    /// Path to a JSON configuration file
+    #[clap(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Conversion preset turning on a set of options (and a filter, unless --filter is given)
+    #[clap(long, value_enum)]
+    pub profile: Option<Profile>,
+
+    /// Write the events matched by the configured marker rules to this JSON file,
+    /// defaults to <OUTPUT>.markers.json
+    #[clap(long, value_name = "FILE")]
+    pub markers: Option<PathBuf>,
+
+    /// Write the configured latency budget statistics to this JSON file,
+    /// defaults to <OUTPUT>.latency.json
+    #[clap(long, value_name = "FILE")]
+    pub latency_report: Option<PathBuf>,
+
+    /// Only emit the events converted from the trace recorder events matching this filter expression,
+    /// e.g. 'event == "USER_EVENT" && channel == "comms"'.
+    /// Events derived by the converter (coalesced_events, latency_violation, anomaly, ...) are always emitted
+    #[clap(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+
+    /// Only emit every Nth occurrence of an event type, <EVENT_TYPE>=N or USER_EVENT:<CHANNEL>=N.
+    /// Adds a sample_weight common context field with the number of occurrences each event stands for.
+    /// Can be supplied multiple times.
+    #[clap(long, value_name = "EVENT_TYPE=N")]
+    pub sample: Vec<SampleRate>,
+
+    /// Add a loglevel field to user events, from the severity name matched in the channel name
+    /// by this regex (the `level` named group, else the first group, else the whole match),
+    /// e.g. '^(\w+):' for channels like "error:comms"
+    #[clap(long, value_name = "REGEX")]
+    pub channel_severity_regex: Option<Regex>,
+
+    /// Leave the user event formatted_string field empty, keeping only the format_string and args
+    #[clap(long)]
+    pub omit_formatted_string: bool,
+
+    /// Give USER_EVENTs whose format string names every argument (e.g. "temp=%d rpm=%u")
+    /// a payload field per argument, named after the keys
+    #[clap(long)]
+    pub named_user_args: bool,
+
     /// babeltrace2 log level
     #[clap(long, default_value = "warn")]
     pub log_level: LoggingLevel,
 
-    /// Output directory to write traces to
+    /// Events converted per message iterator call, while the babeltrace message array has room.
+    /// Larger batches trade latency (e.g. live from a named pipe) for throughput
+    #[clap(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub batch_size: u64,
+
+    /// Print the babeltrace component graph (components, parameters and connections) to stderr
+    /// before running it
+    #[clap(long)]
+    pub print_pipeline: bool,
+
+    /// sink.ctf.fs parameter (the only sink linked in), overriding the --sink-* options, e.g. quiet=false.
+    /// Can be supplied multiple times.
+    #[clap(long, value_name = "KEY=VALUE", value_parser = pipeline::parse_sink_param)]
+    pub sink_param: Vec<SinkParam>,
+
+    /// sink.ctf.fs assume-single-trace parameter, write the trace directly into the output
+    /// directory instead of a subdirectory named after the trace
+    #[clap(long, value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    pub sink_assume_single_trace: bool,
+
+    /// sink.ctf.fs ignore-discarded-events parameter, don't record the events lost by the
+    /// recorder (or between the segments of the recover subcommand) in the packets
+    #[clap(long)]
+    pub sink_ignore_discarded_events: bool,
+
+    /// sink.ctf.fs ignore-discarded-packets parameter
+    #[clap(long)]
+    pub sink_ignore_discarded_packets: bool,
+
+    /// sink.ctf.fs quiet parameter, set to false for the sink to print the paths it writes
+    #[clap(long, value_name = "BOOL", default_value_t = true, action = ArgAction::Set)]
+    pub sink_quiet: bool,
+
+    /// Emit task_state_change events tracking each task's lifecycle state
+    #[clap(long)]
+    pub task_state_events: bool,
+
+    /// Write the per-task state intervals computed during conversion to this JSON file
+    #[clap(long, value_name = "FILE")]
+    pub task_state_intervals: Option<PathBuf>,
+
+    /// Emit a converter_heartbeat event with cumulative throughput counters
+    /// every COUNT input events
+    #[clap(long, value_name = "COUNT")]
+    pub heartbeat_events: Option<u64>,
+
+    /// Emit a converter_heartbeat event with cumulative throughput counters
+    /// every MB megabytes of input
+    #[clap(long, value_name = "MB")]
+    pub heartbeat_megabytes: Option<u64>,
+
+    /// Flag per-event-type rate bursts and silences over a sliding window of MS milliseconds
+    #[clap(long, value_name = "MS")]
+    pub anomaly_window_ms: Option<u64>,
+
+    /// Flag a burst when an event type's count within the window exceeds FACTOR times
+    /// the count expected from its average rate
+    #[clap(long, value_name = "FACTOR", default_value_t = 10.0)]
+    pub anomaly_burst_factor: f64,
+
+    /// Flag a silence when the gap between two events of a type exceeds both the window
+    /// and FACTOR times its average interval
+    #[clap(long, value_name = "FACTOR", default_value_t = 10.0)]
+    pub anomaly_silence_factor: f64,
+
+    /// Emit an anomaly event for each detected rate anomaly, in addition to the warning
+    #[clap(long, requires = "anomaly_window_ms")]
+    pub anomaly_events: bool,
+
+    /// Emit softirq_raise/entry/exit events for ISRs deferring their work to a handler task
+    /// (ISR wakes up a task that gets switched in as soon as the ISR ends)
+    #[clap(long)]
+    pub softirq_events: bool,
+
+    /// Place ISR-context events in a separate CTF stream from the task-context events
+    #[clap(long)]
+    pub stream_per_isr: bool,
+
+    /// How priorities are written to the prev_prio, next_prio and prio fields:
+    /// raw, invert (negated, FreeRTOS priorities increase with importance) or offset=N
+    #[clap(long, value_name = "MAPPING", default_value_t)]
+    pub prio_mapping: PrioMapping,
+
+    /// Add in_irq and irq_name common context fields, telling ISR-context events apart
+    /// from task-context events
+    #[clap(long)]
+    pub irq_context: bool,
+
+    /// Unix time (nanoseconds) of timestamp 0, aligns the clock to wall-clock time.
+    /// Overrides the offset found by the configured clock_sync rule.
+    #[clap(long, value_name = "NS", allow_negative_numbers = true)]
+    pub clock_offset_ns: Option<i64>,
+
+    /// Make timestamps relative to the first event, which gets timestamp 0
+    #[clap(long)]
+    pub zero_base_time: bool,
+
+    /// Add a secondary os_ticks clock class at the OS tick rate, and emit the tick-referenced
+    /// events (TASK_DELAY, TASK_DELAY_UNTIL) against it in a stream of their own
+    #[clap(long, conflicts_with = "zero_base_time")]
+    pub os_tick_clock: bool,
+
+    /// Trust the OS tick rate over the header's timer frequency: when the timer frequency
+    /// observed from the tick-referenced wake-ups doesn't match the declared one, rescale the
+    /// timestamps to it
+    #[clap(long)]
+    pub trust_ticks: bool,
+
+    /// Throttle the conversion to the trace's original event timing, e.g. to demo
+    /// or test live-viewing setups
+    #[clap(long)]
+    pub replay_realtime: bool,
+
+    /// Replay speed factor, 2.0 replays twice as fast as the original timing
+    #[clap(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = replay::parse_speed, requires = "replay_realtime")]
+    pub speed: f64,
+
+    /// How raw timestamps are extended into a monotonic timeline
+    #[clap(long, value_enum, default_value_t)]
+    pub timestamp_tracker: TimestampStrategy,
+
+    /// How raw event counters are extended, and whether their gaps are dropped events
+    #[clap(long, value_enum, default_value_t)]
+    pub event_count_tracker: EventCountStrategy,
+
+    /// Merge runs of consecutive events of a type (e.g. a ticker) into a single
+    /// coalesced_events event with a count, instead of converting each event.
+    /// Can be supplied multiple times.
+    #[clap(long, value_name = "EVENT_TYPE", value_parser = coalesce::parse_event_type)]
+    pub coalesce: Vec<String>,
+
+    /// ELF file of the traced firmware, symbolicates the caller addresses
+    /// (see the caller_address config) and the TIMER_EXPIRED callbacks
+    #[clap(long, value_name = "FILE")]
+    pub elf: Option<PathBuf>,
+
+    /// Compress the stream files with zstd once the conversion finishes, e.g. for soak-test
+    /// captures. babeltrace can't read them until restored with the decompress subcommand
+    #[clap(long)]
+    pub compress: bool,
+
+    /// zstd compression level of --compress
+    #[clap(long, value_name = "LEVEL", default_value_t = 3, requires = "compress")]
+    pub compress_level: i32,
+
+    /// Demangle the task, ISR and object names that are mangled C++ symbols (Itanium ABI),
+    /// e.g. tasks named by their function
+    #[clap(long)]
+    pub demangle: bool,
+
+    /// Number of the longest ISR executions and scheduler-locked intervals reported
+    /// (with their timestamps) when the conversion finishes
+    #[clap(long, value_name = "N", default_value_t = 10)]
+    pub worst_offenders: usize,
+
+    /// Write time series derived from the events (task CPU load, ISR rates, heap usage,
+    /// queue depths) to this file in InfluxDB line protocol
+    #[clap(long, value_name = "FILE")]
+    pub influx: Option<PathBuf>,
+
+    /// Time series window length
+    #[clap(long, value_name = "MS", default_value_t = 1000, requires = "influx")]
+    pub influx_interval_ms: u64,
+
+    /// On a fatal conversion error, write a snapshot of the converter state (active context,
+    /// ISR stack, trackers and the last event codes) to this JSON file, to attach to bug reports.
+    /// Task and ISR names are left out.
+    #[clap(long, value_name = "FILE")]
+    pub error_snapshot: Option<PathBuf>,
+
+    /// Write the output's event classes (names, IDs, trace recorder event IDs and payload fields)
+    /// to this JSON file, so scripts don't need to parse the CTF metadata
+    #[clap(long, value_name = "FILE")]
+    pub event_dictionary: Option<PathBuf>,
+
+    /// Write the memory allocations not freed by the end of the trace (paired by address)
+    /// to this JSON file
+    #[clap(long, value_name = "FILE")]
+    pub leak_report: Option<PathBuf>,
+
+    /// Emit a leak_candidate event at the end of the trace for each allocation not freed
+    #[clap(long)]
+    pub leak_candidate_events: bool,
+
+    /// Emit a handle_name event at the end of the trace for each task and ISR seen, so scripts
+    /// can resolve handles to names without replaying the trace
+    #[clap(long)]
+    pub handle_table_events: bool,
+
+    /// Emit a task_newtask event with the task name for each TASK_CREATE, so viewers get the
+    /// names of tasks never switched in during the capture
+    #[clap(long)]
+    pub task_newtask_events: bool,
+
+    /// Emit sched_process_fork events for TASK_CREATE and sched_process_exit/sched_process_free
+    /// events for TASK_DELETE, for the process lifecycle of the Trace Compass kernel analysis
+    #[clap(long)]
+    pub process_lifecycle_events: bool,
+
+    /// Emit a fixed sequence of selftest events at the start of the stream, a canary for the
+    /// metadata, clock and payload encoding
+    #[clap(long)]
+    pub inject_selftest: bool,
+
+    /// What to do with the events before TRACE_START, e.g. of a capture started mid-run
+    #[clap(long, value_enum, default_value_t)]
+    pub pre_start: PreStartPolicy,
+
+    /// The capture was attached mid-run and may not contain TRACE_START, don't warn about a
+    /// missing TRACE_START or ISRs entered before the capture started
+    #[clap(long)]
+    pub midrun_capture: bool,
+
+    /// Memory budget for the converter state growing with the trace (string cache, ISR stack,
+    /// stats, leak tracking, task state intervals), e.g. for week-long captures from a named pipe.
+    /// The usage is reported every minute, and the state that can be dropped is dropped when
+    /// it's over budget.
+    #[clap(long, value_name = "MB")]
+    pub memory_budget_mb: Option<u64>,
+
+    /// Show a live progress and summary screen instead of log output
+    #[clap(long)]
+    pub tui: bool,
+
+    /// Place the converted trace next to this host-side LTTng trace directory and write
+    /// the correlation metadata for a Trace Compass experiment
+    #[clap(long, value_name = "LTTNG_TRACE_DIR")]
+    pub experiment_with: Option<PathBuf>,
+
+    /// Write a single-line JSON summary of the conversion (success, counts, warnings)
+    /// to this file when done, e.g. /dev/fd/3
+    #[clap(long, value_name = "FILE")]
+    pub summary_file: Option<PathBuf>,
+
+    /// Output format
+    #[clap(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Output directory to write traces to, or the file to write the JSON Lines to
+    /// ('-' for stdout) with --format jsonl
     #[clap(short = 'o', long, default_value = "ctf_trace")]
     pub output: PathBuf,
 
+    /// Print every known trace recorder event type, the CTF event it's converted to and whether
+    /// the --filter and --sample options include it, then exit
+    #[clap(long)]
+    pub list_events: bool,
+
     /// Path to the input trace recorder binary file (psf) to read
-    pub input: PathBuf,
+    #[clap(required_unless_present = "list_events")]
+    pub input: Option<PathBuf>,
+
+    /// Convert the cleanly parseable segments of the input, set by the recover subcommand
+    #[clap(skip)]
+    pub recover: bool,
+
+    /// Write one trace per time window of this length, set by the slice subcommand
+    #[clap(skip)]
+    pub slice_every: Option<Duration>,
+
+    /// The input is a slice continuing a trace, its first event isn't expected to be TRACE_START
+    #[clap(skip)]
+    pub continued: bool,
+
+    /// The timer frequency observed before the conversion, set by --trust-ticks on a mismatch
+    #[clap(skip)]
+    pub tick_rate_correction: Option<TickRateEstimate>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    match do_main() {
-        Err(e) => {
-            error!("{}", e);
-            Err(e)
+impl Opts {
+    /// The input path, clap requires it when no subcommand or --list-events is given
+    pub fn input_path(&self) -> &Path {
+        self.input.as_deref().expect("Missing input path")
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PreStartPolicy {
+    /// Convert them, the trackers are initialized from the first event
+    #[default]
+    Keep,
+    /// Discard them, nothing is converted if there's no TRACE_START
+    Drop,
+    /// Hold them back until a TRACE_START follows, then convert them ahead of it on its
+    /// timeline. Converted as with keep if the input ends without one
+    BufferUntilStart,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// CTF trace directory
+    #[default]
+    Ctf,
+    /// One JSON object per converted event
+    Jsonl,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Generate shell completions and write them to stdout
+    Completions {
+        /// The shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+
+    /// Generate a man page and write it to stdout
+    Man,
+
+    /// Decompress the stream files of a trace written with --compress, in place, so
+    /// babeltrace and Trace Compass can read it
+    Decompress {
+        /// The CTF trace directory (or a directory of traces, e.g. the slices)
+        trace: PathBuf,
+    },
+
+    /// Compare two traces, reporting differences in event type rates, tasks,
+    /// task CPU utilization and ISR durations
+    Compare {
+        /// The baseline trace recorder binary file (psf)
+        a: PathBuf,
+
+        /// The trace recorder binary file (psf) to compare against the baseline
+        b: PathBuf,
+    },
+
+    /// Convert the cleanly parseable segments of a truncated or corrupted trace,
+    /// resynchronizing after data errors instead of stopping at the first one
+    Recover {
+        #[clap(flatten)]
+        opts: Box<Opts>,
+    },
+
+    /// Write one CTF trace per time window of the input, into <OUTPUT>/slice-NNNN,
+    /// e.g. to view enormous soak-test captures
+    Slice {
+        /// Time window length, e.g. 500ms, 60s, 10m or 1h
+        #[clap(long, value_name = "DURATION", value_parser = slice::parse_duration)]
+        every: Duration,
+
+        #[clap(flatten)]
+        opts: Box<Opts>,
+    },
+
+    /// Check the selftest events of a JSON Lines output written with --inject-selftest
+    /// against the fixed sequence
+    Verify {
+        /// The JSON Lines file written with --format jsonl
+        jsonl: PathBuf,
+    },
+}
+
+impl Command {
+    fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Cli::command();
+        match self {
+            Command::Completions { shell } => {
+                let name = cmd.get_name().to_string();
+                clap_complete::generate(*shell, &mut cmd, name, &mut io::stdout());
+            }
+            Command::Man => {
+                clap_mangen::Man::new(cmd).render(&mut io::stdout())?;
+            }
+            Command::Compare { a, b } => compare::run(a, b)?,
+            Command::Decompress { trace } => compress::decompress_streams(trace)?,
+            Command::Verify { jsonl } => verify::run(jsonl)?,
+            Command::Recover { .. } | Command::Slice { .. } => {
+                unreachable!("Recover and Slice run the conversion")
+            }
         }
-        Ok(()) => Ok(()),
+        Ok(())
     }
 }
 
-fn do_main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let mut opts = match cli.command {
+        None => cli.opts,
+        Some(Command::Recover { mut opts }) => {
+            opts.recover = true;
+            *opts
+        }
+        Some(Command::Slice { every, mut opts }) => {
+            opts.slice_every = Some(every);
+            *opts
+        }
+        Some(cmd) => return cmd.run(),
+    };
+    if let Some(profile) = opts.profile {
+        profile.apply(&mut opts)?;
+    }
+
+    if opts.list_events {
+        let config = match &opts.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        event_list::print(&opts, &config);
+        return Ok(());
+    }
+
+    let stats = SharedStats::default();
+    stats.lock().unwrap().worst_offenders = opts.worst_offenders;
+    let warnings = WarningCounter::default();
+    let started_at = Instant::now();
+    let res = do_main(&opts, stats.clone(), warnings.clone());
+    if let Err(e) = &res {
+        error!("{}", e);
+    }
+
+    if let Some(path) = &opts.summary_file {
+        let summary = ConversionSummary::new(
+            &opts,
+            &stats.lock().unwrap(),
+            warnings.count(),
+            started_at.elapsed(),
+            res.as_ref().err().map(|e| e.as_ref()),
+        );
+        if let Err(e) = summary.write(path) {
+            error!(path = %path.display(), "Failed to write the conversion summary. {e}");
+        }
+    }
 
-    let opts = Opts::parse();
+    res
+}
+
+fn do_main(
+    opts: &Opts,
+    stats: SharedStats,
+    warnings: WarningCounter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let jsonl_to_stdout = opts.format == OutputFormat::Jsonl && opts.output == Path::new("-");
+    // Log output would clobber the TUI, and mustn't mix with JSON Lines on stdout
+    let fmt_layer = (!opts.tui).then(|| {
+        let writer = if jsonl_to_stdout {
+            BoxMakeWriter::new(io::stderr)
+        } else {
+            BoxMakeWriter::new(io::stdout)
+        };
+        tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_filter(EnvFilter::from_default_env())
+    });
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(warnings.clone())
+        .init();
 
     let intr = Interruptor::new();
     let intr_clone = intr.clone();
@@ -86,84 +682,727 @@ fn do_main() -> Result<(), Box<dyn std::error::Error>> {
         intr_clone.set();
     })?;
 
-    info!(input = %opts.input.display(), "Reading header info");
-    let file = File::open(&opts.input)?;
-    let mut reader = BufReader::new(file);
+    let config = match &opts.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+
+    let mut clock_sync = config.clock_sync.as_ref().map(ClockSync::new).transpose()?;
+
+    let is_fifo = probe::is_fifo(opts.input_path());
+    if is_fifo && clock_sync.is_some() {
+        return Err("The clock_sync rule needs a second pass over the input, \
+            which isn't possible when reading from a FIFO"
+            .into());
+    }
+    if is_fifo && opts.trust_ticks {
+        return Err("--trust-ticks needs a second pass over the input, \
+            which isn't possible when reading from a FIFO"
+            .into());
+    }
+
+    // A FIFO can only be opened and read once, it's read from this single reader throughout
+    let mut fifo = is_fifo
+        .then(|| open_input(opts, opts.skip_bytes))
+        .transpose()?;
+    // The bytes read from the FIFO while seeking the header, from the header onwards
+    let mut fifo_header_bytes = Vec::new();
+
+    let mut skip_bytes = opts.skip_bytes;
+    if opts.seek_header {
+        let found = match fifo.as_mut() {
+            Some(fifo) => probe::read_to_header(fifo)?.map(|(offset, bytes)| {
+                fifo_header_bytes = bytes;
+                offset
+            }),
+            None => probe::seek_header(open_input(opts, skip_bytes)?)?,
+        };
+        match found {
+            Some(offset) => {
+                skip_bytes += offset;
+                info!(offset = skip_bytes, "Found the PSF header");
+            }
+            None => return Err("No PSF header found in the input".into()),
+        }
+    }
 
-    let trd = RecorderData::find(&mut reader)?;
+    info!(input = %opts.input_path().display(), "Reading header info");
+    let mut tick_rate_correction = None;
+    let input: Box<dyn Read> = if opts.recover || opts.demux.is_some() || opts.slice_every.is_some()
+    {
+        let data = match fifo.as_mut() {
+            Some(fifo) => {
+                let mut data = fifo_header_bytes;
+                fifo.read_to_end(&mut data)?;
+                // Already positioned after the skipped bytes
+                skip_bytes = 0;
+                data
+            }
+            None => fs::read(opts.input_path())?,
+        };
+        let data = data.get(skip_bytes as usize..).unwrap_or_default();
+        let recovered = if opts.recover || opts.demux.is_some() {
+            let (recovered, segments) = if opts.demux.is_some() {
+                recover::demux(data)
+            } else {
+                recover::recover(data)
+            }
+            .map_err(|e| probe::header_error(opts.input_path(), e))?;
+            if let Some(path) = &opts.demux {
+                info!(path = %path.display(), "Writing non-trace data");
+                recover::write_passthrough(path, data, &segments)?;
+            }
+            info!(
+                segments = segments.len(),
+                input_bytes = data.len(),
+                recovered_bytes = recovered.len(),
+                "Recovered trace segments"
+            );
+            recovered
+        } else {
+            data.to_vec()
+        };
+        tick_rate_correction = find_tick_rate_correction(opts, Cursor::new(&recovered))?;
+        if let Some(every) = opts.slice_every {
+            let opts = &Opts {
+                tick_rate_correction,
+                ..opts.clone()
+            };
+            return convert_slices(opts, &config, &recovered, every, warnings, intr);
+        }
+        stats.lock().unwrap().input_size = Some(recovered.len() as u64);
+        if let Some(clock_sync) = clock_sync.as_mut() {
+            clock_sync.find_offset(Cursor::new(&recovered), opts.zero_base_time)?;
+        }
+        Box::new(Cursor::new(recovered))
+    } else if let Some(fifo) = fifo {
+        Box::new(Cursor::new(fifo_header_bytes).chain(fifo))
+    } else {
+        if let Some(clock_sync) = clock_sync.as_mut() {
+            clock_sync.find_offset(open_input(opts, skip_bytes)?, opts.zero_base_time)?;
+        }
+        tick_rate_correction = find_tick_rate_correction(opts, open_input(opts, skip_bytes)?)?;
+        let input_size = fs::metadata(opts.input_path())?.len();
+        stats.lock().unwrap().input_size = Some(input_size.saturating_sub(skip_bytes));
+        Box::new(open_input(opts, skip_bytes)?)
+    };
+
+    let opts = &Opts {
+        tick_rate_correction,
+        ..opts.clone()
+    };
+    convert(opts, config, clock_sync, input, stats, warnings, intr)
+}
 
-    let output_path = CString::new(opts.output.to_str().unwrap())?;
-    let params = CtfPluginSinkFsInitParams::new(
-        Some(true), // assume_single_trace
-        None,       // ignore_discarded_events
-        None,       // ignore_discarded_packets
-        Some(true), // quiet
-        &output_path,
-    )?;
+/// With --trust-ticks, scan the input for the timer frequency observed from the OS tick rate.
+/// It's returned when it doesn't match the declared frequency.
+fn find_tick_rate_correction<R: Read>(
+    opts: &Opts,
+    r: R,
+) -> Result<Option<TickRateEstimate>, Box<dyn std::error::Error>> {
+    if !opts.trust_ticks {
+        return Ok(None);
+    }
+    match TickRateCheck::scan(r, opts.timestamp_tracker)? {
+        None => {
+            warn!(
+                "Not enough tick-referenced wake-ups to check the timer frequency, \
+                the timestamps aren't corrected"
+            );
+            Ok(None)
+        }
+        Some(estimate) if estimate.is_mismatch() => {
+            warn!(
+                declared = estimate.declared,
+                observed = estimate.observed,
+                samples = estimate.samples,
+                "The declared timer frequency doesn't match the OS tick rate, \
+                rescaling the timestamps to the observed frequency"
+            );
+            Ok(Some(estimate))
+        }
+        Some(estimate) => {
+            info!(
+                declared = estimate.declared,
+                observed = estimate.observed,
+                "The declared timer frequency matches the OS tick rate"
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Convert the slices of the trace, each into its own output directory
+fn convert_slices(
+    opts: &Opts,
+    config: &Config,
+    data: &[u8],
+    every: Duration,
+    warnings: WarningCounter,
+    intr: Interruptor,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if opts.format == OutputFormat::Jsonl {
+        return Err("Slicing needs the CTF output format".into());
+    }
+    if opts.tui || opts.experiment_with.is_some() || opts.summary_file.is_some() {
+        return Err(
+            "--tui, --experiment-with and --summary-file can't be used when slicing".into(),
+        );
+    }
+
+    let slices = slice::split(data, every, opts.timestamp_tracker)
+        .map_err(|e| probe::header_error(opts.input_path(), e))?;
+    info!(slices = slices.len(), "Slicing the trace");
+    fs::create_dir_all(&opts.output)?;
+
+    for (index, slice) in slices.into_iter().enumerate() {
+        if intr.is_set() {
+            break;
+        }
+        let mut slice_opts = opts.clone();
+        slice_opts.output = opts.output.join(format!("slice-{index:04}"));
+        slice_opts.continued = index != 0;
+        for path in [
+            &mut slice_opts.markers,
+            &mut slice_opts.latency_report,
+            &mut slice_opts.task_state_intervals,
+            &mut slice_opts.influx,
+            &mut slice_opts.error_snapshot,
+            &mut slice_opts.event_dictionary,
+            &mut slice_opts.leak_report,
+        ] {
+            if let Some(path) = path.as_mut() {
+                *path = slice_path(path, index);
+            }
+        }
+        // Keep the slices on the trace's timeline, a clock_sync rule finds each slice's offset
+        // and zero-based slices start at 0
+        if !opts.zero_base_time && (config.clock_sync.is_none() || opts.clock_offset_ns.is_some()) {
+            let offset_ns = match opts.tick_rate_correction {
+                Some(c) => {
+                    (i128::from(slice.offset_ns) * i128::from(c.declared) / i128::from(c.observed))
+                        as i64
+                }
+                None => slice.offset_ns,
+            };
+            slice_opts.clock_offset_ns = Some(opts.clock_offset_ns.unwrap_or(0) + offset_ns);
+        }
+
+        let mut clock_sync = config.clock_sync.as_ref().map(ClockSync::new).transpose()?;
+        if let Some(clock_sync) = clock_sync.as_mut() {
+            clock_sync.find_offset(Cursor::new(&slice.data), opts.zero_base_time)?;
+        }
+        let stats = SharedStats::default();
+        {
+            let mut stats = stats.lock().unwrap();
+            stats.worst_offenders = opts.worst_offenders;
+            stats.input_size = Some(slice.data.len() as u64);
+        }
+        info!(
+            output = %slice_opts.output.display(),
+            events = slice.events,
+            "Converting slice"
+        );
+        convert(
+            &slice_opts,
+            config.clone(),
+            clock_sync,
+            Box::new(Cursor::new(slice.data)),
+            stats,
+            warnings.clone(),
+            intr.clone(),
+        )?;
+    }
 
-    let state_inner: Box<dyn SourcePluginHandler> =
-        Box::new(TrcPluginState::new(intr, reader, trd, &opts)?);
+    Ok(())
+}
+
+/// The path as a C string for babeltrace, passed through as-is on unix where paths
+/// needn't be UTF-8
+#[cfg(unix)]
+fn path_cstring(path: &Path) -> Result<CString, Box<dyn std::error::Error>> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+#[cfg(not(unix))]
+fn path_cstring(path: &Path) -> Result<CString, Box<dyn std::error::Error>> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("The output path '{}' isn't valid UTF-8", path.display()))?;
+    Ok(CString::new(path_str)?)
+}
+
+/// The path with the slice index appended to the file stem, e.g. markers-0003.json
+fn slice_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{index:04}"));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+fn convert(
+    opts: &Opts,
+    config: Config,
+    clock_sync: Option<ClockSync>,
+    input: Box<dyn Read>,
+    stats: SharedStats,
+    warnings: WarningCounter,
+    intr: Interruptor,
+) -> Result<(), Box<dyn std::error::Error>> {
+    stats.lock().unwrap().task_groups = TaskGroups::new(&config.task_groups)?;
+    let jsonl_to_stdout = opts.format == OutputFormat::Jsonl && opts.output == Path::new("-");
+    let mut reader = CountingReader::new(input);
+
+    if opts.format == OutputFormat::Jsonl {
+        if opts.experiment_with.is_some() {
+            return Err("--experiment-with needs the CTF output format".into());
+        }
+        if opts.tui && jsonl_to_stdout {
+            return Err("--tui can't be used with JSON Lines written to stdout".into());
+        }
+        if opts.compress {
+            return Err("--compress needs the CTF output format".into());
+        }
+    }
+
+    let trd =
+        RecorderData::find(&mut reader).map_err(|e| probe::header_error(opts.input_path(), e))?;
+    probe::check_format_version(trd.header.format_version)?;
+
+    // The CTF sink is always part of the pipeline, it writes to a scratch directory
+    // when the events are written as JSON Lines, removed however the conversion ends
+    let scratch_dir = match opts.format {
+        OutputFormat::Ctf => None,
+        OutputFormat::Jsonl => Some(ScratchDir::new()?),
+    };
+    let ctf_dir = match scratch_dir.as_ref() {
+        Some(dir) => dir.path().to_path_buf(),
+        None => opts.output.clone(),
+    };
+    let mut sink_params = CtfSinkParams {
+        assume_single_trace: Some(opts.sink_assume_single_trace),
+        ignore_discarded_events: opts.sink_ignore_discarded_events.then_some(true),
+        ignore_discarded_packets: opts.sink_ignore_discarded_packets.then_some(true),
+        quiet: Some(opts.sink_quiet),
+        path_cstring: path_cstring(&ctf_dir)?,
+        path: ctf_dir.clone(),
+    };
+    for param in opts.sink_param.iter() {
+        sink_params.set(param)?;
+    }
+    let params = sink_params.init_params()?;
+
+    let mut tui = if opts.tui {
+        Some(Tui::new(stats.clone(), warnings, intr.clone())?)
+    } else {
+        None
+    };
+
+    let clock_offset_ns = opts
+        .clock_offset_ns
+        .or_else(|| clock_sync.as_ref().and_then(|c| c.offset_ns));
+    let state_inner: Box<dyn SourcePluginHandler> = Box::new(TrcPluginState::new(
+        intr,
+        reader,
+        trd,
+        stats.clone(),
+        opts,
+        config,
+        clock_sync,
+    )?);
     let state = Box::new(state_inner);
 
+    if opts.print_pipeline {
+        // stderr, stdout may be the JSON Lines output
+        eprintln!(
+            "{}",
+            PipelineDescription {
+                log_level: opts.log_level,
+                source: TrcPlugin::graph_node_name(),
+                sink: &sink_params,
+            }
+        );
+    }
+
     let mut pipeline = EncoderPipeline::new::<TrcPlugin>(opts.log_level, state, &params)?;
 
     loop {
         let run_status = pipeline.graph.run_once()?;
+        if let Some(tui) = tui.as_mut() {
+            tui.update()?;
+        }
         if RunStatus::End == run_status {
             break;
         }
     }
 
+    if let Some(tui) = tui.take() {
+        tui.finish()?;
+    }
+
+    drop(scratch_dir);
+
+    if let Some(host_trace) = &opts.experiment_with {
+        experiment::create(&opts.output, host_trace, &opts.trace_name, clock_offset_ns)?;
+    }
+
+    if opts.compress {
+        compress::compress_streams(&ctf_dir, opts.compress_level)?;
+    }
+
+    for (name, size) in stats
+        .lock()
+        .unwrap()
+        .largest_event_classes(LARGEST_EVENT_CLASSES)
+    {
+        info!(
+            event_class = name,
+            events = size.events,
+            estimated_bytes = size.bytes,
+            "CTF size contributor"
+        );
+    }
+
+    {
+        let stats = stats.lock().unwrap();
+        for bucket in stats.alloc_size_histogram() {
+            info!(
+                min_size = bucket.min_size,
+                max_size = bucket.max_size,
+                count = bucket.count,
+                "Allocation sizes"
+            );
+        }
+        for group in stats.task_group_summaries() {
+            info!(
+                group = group.group,
+                tasks = group.tasks,
+                activations = group.activations,
+                cpu_share = group.cpu_share,
+                "Task group"
+            );
+        }
+        let worst_offenders = stats.worst_offenders();
+        for isr in worst_offenders.longest_isrs.iter() {
+            info!(
+                isr = isr.name,
+                start_ns = isr.start_ns,
+                duration_ns = isr.duration_ns,
+                "Long ISR"
+            );
+        }
+        for lock in worst_offenders.longest_scheduler_locks.iter() {
+            info!(
+                task = lock.name,
+                start_ns = lock.start_ns,
+                duration_ns = lock.duration_ns,
+                "Scheduler locked"
+            );
+        }
+        if stats.malloc_failures != 0 {
+            warn!(
+                malloc_failures = stats.malloc_failures,
+                "Allocations failed"
+            );
+        }
+    }
+
     info!("Done");
 
     Ok(())
 }
 
+/// Open the input file, positioned after the skipped bytes.
+/// FIFOs can't seek, the skipped bytes are read and discarded instead.
+fn open_input(opts: &Opts, skip_bytes: u64) -> io::Result<BufReader<File>> {
+    let mut file = File::open(opts.input_path())?;
+    if probe::is_fifo(opts.input_path()) {
+        io::copy(&mut (&mut file).take(skip_bytes), &mut io::sink())?;
+    } else {
+        file.seek(SeekFrom::Start(skip_bytes))?;
+    }
+    Ok(BufReader::new(file))
+}
+
+/// Messages pushed by TrcPluginState::push_end_messages, at most
+const MAX_END_MESSAGES: usize = 7;
+
 struct TrcPluginState {
     interruptor: Interruptor,
-    reader: BufReader<File>,
+    reader: CountingReader<Box<dyn Read>>,
     clock_name: CString,
     trace_name: CString,
+    tracer_name: CString,
+    tracer_version: (u32, u32, u32),
+    trace_buffering_scheme: CString,
     input_file_name: CString,
     trace_creation_time: DateTime<Utc>,
+    task_state_intervals: Option<PathBuf>,
+    leak_report: Option<PathBuf>,
+    event_dictionary: Option<PathBuf>,
+    error_snapshot: Option<PathBuf>,
+    recent_events: RecentEvents,
+    jsonl: Option<JsonLinesWriter>,
+    influx: Option<InfluxExport>,
     trd: RecorderData,
     first_event_observed: bool,
+    pre_start: PreStartPolicy,
+    trace_start_observed: bool,
+    /// Converting a slice continuing a trace
+    continued: bool,
+    /// Set by --midrun-capture, the missing TRACE_START is expected
+    midrun_capture: bool,
+    /// The first event isn't TRACE_START, recorded in the environment
+    started_midrun: bool,
+    /// The first event, read before the conversion starts to set up the environment
+    peeked_event: Option<(EventCode, Event)>,
+    /// Events converted per iterator call, see --batch-size
+    batch_size: u64,
+    /// Messages that didn't fit in the message array, handed over before anything else
+    pending_messages: Vec<*const ffi::bt_message>,
+    /// No event was read to continue a batch, handled as the end of the input (or a restart)
+    /// on the next iteration
+    batch_input_ended: bool,
+    /// Events held back until TRACE_START, see PreStartPolicy::BufferUntilStart
+    pre_start_events: VecDeque<(EventCode, Event)>,
+    /// Timer wraparounds the trackers start from, when the held back events are re-based onto
+    /// the TRACE_START that followed them
+    first_event_wraparounds: Option<u32>,
+    pre_start_dropped: u64,
+    /// Raw event counter of the last converted event, for duplicate detection
+    last_event_count: u16,
+    in_duplicate_run: bool,
+    /// Conversion error to report once the trace has been finalized
+    pending_error: Option<Error>,
     eof_reached: bool,
     stream_is_open: bool,
-    time_rollover_tracker: StreamingInstant,
-    event_counter_tracker: TrackingEventCounter,
+    time_rollover_tracker: Box<dyn TimestampTracker>,
+    zero_base_time: bool,
+    /// Tracked timestamp of the first event, subtracted from every timestamp when zero_base_time is set
+    time_base: Option<u64>,
+    /// Set by --trust-ticks when the timer frequency doesn't match the OS tick rate
+    timestamp_scale: Option<TimestampScale>,
+    tick_rate_check: Option<TickRateCheck>,
+    event_counter_tracker: Box<dyn EventCountTracker>,
+    counters: ThroughputCounters,
+    heartbeat: Heartbeat,
+    anomaly_detector: Option<AnomalyDetector>,
+    memory_monitor: Option<MemoryMonitor>,
+    markers: Option<Markers>,
+    latency_checker: Option<LatencyChecker>,
+    filter: Option<Filter>,
+    sampler: Sampler,
+    replay: Option<Replay>,
+    clock_sync: Option<ClockSync>,
+    /// Unix time of timestamp 0, the clock class offset
+    clock_offset_ns: Option<i64>,
+    stats: SharedStats,
     stream: *mut ffi::bt_stream,
     packet: *mut ffi::bt_packet,
+    stream_per_isr: bool,
+    /// ISR-context stream and packet, when stream_per_isr is set
+    isr_stream: *mut ffi::bt_stream,
+    isr_packet: *mut ffi::bt_packet,
+    os_tick_clock: Option<OsTickClock>,
+    /// Stream of the tick-referenced events, null unless there's a secondary clock
+    tick_stream: *mut ffi::bt_stream,
+    tick_packet: *mut ffi::bt_packet,
+    /// Constant packet context members following cpu_id
+    packet_context: Vec<(CString, PacketContextValue)>,
     converter: TrcCtfConverter,
 }
 
 impl TrcPluginState {
     fn new(
         interruptor: Interruptor,
-        reader: BufReader<File>,
+        reader: CountingReader<Box<dyn Read>>,
         trd: RecorderData,
+        stats: SharedStats,
         opts: &Opts,
+        config: Config,
+        clock_sync: Option<ClockSync>,
     ) -> Result<Self, Error> {
         let clock_name = CString::new(opts.clock_name.as_str())?;
         let trace_name = CString::new(opts.trace_name.as_str())?;
-        let input_file_name = CString::new(opts.input.file_name().unwrap().to_str().unwrap())?;
+        let tracer_name = CString::new(opts.tracer_name.as_str())?;
+        let trace_buffering_scheme = CString::new(opts.trace_buffering_scheme.as_str())?;
+        // Only informational, non-UTF-8 names are written lossily
+        let input_path = opts.input_path();
+        let input_file_name = CString::new(
+            input_path
+                .file_name()
+                .unwrap_or(input_path.as_os_str())
+                .to_string_lossy()
+                .as_ref(),
+        )?;
+        stats.lock().unwrap().timer_frequency = trd.timestamp_info.timer_frequency.get_raw();
+        for (task, core) in config.task_affinity.iter() {
+            if *core >= trd.header.num_cores {
+                warn!(
+                    task,
+                    core,
+                    num_cores = trd.header.num_cores,
+                    "Task affinity core is beyond the number of cores"
+                );
+            }
+        }
+        let symbols = opts
+            .elf
+            .as_deref()
+            .map(Symbols::load)
+            .transpose()
+            .map_err(|e| Error::PluginError(e.to_string()))?;
+        let mut os_tick_clock = match opts
+            .os_tick_clock
+            .then(|| OsTickClock::new(&trd.timestamp_info))
+        {
+            Some(None) => {
+                warn!("The header doesn't declare the OS tick rate, not adding the os_ticks clock");
+                None
+            }
+            clock => clock.flatten(),
+        };
+        let timestamp_scale = opts.tick_rate_correction.as_ref().map(TimestampScale::new);
+        if let Some(estimate) = opts.tick_rate_correction.as_ref() {
+            stats.lock().unwrap().observed_timer_frequency = Some(estimate.observed);
+        }
+        if let (Some(clock), Some(scale)) = (os_tick_clock.as_mut(), timestamp_scale) {
+            clock.anchor_timestamp = scale.apply(Timestamp::from(clock.anchor_timestamp)).ticks();
+        }
+        // --trust-ticks already checked the frequency before the conversion
+        let tick_rate_check = (!opts.trust_ticks)
+            .then(|| TickRateCheck::new(trd.timestamp_info.os_tick_rate_hz.get_raw()))
+            .flatten();
+        let converter = TrcCtfConverter::new(opts, &config, symbols, os_tick_clock);
+        let packet_context = config
+            .packet_context
+            .iter()
+            .map(|(name, value)| Ok((CString::new(name.as_str())?, value.clone())))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let jsonl = (opts.format == OutputFormat::Jsonl)
+            .then(|| JsonLinesWriter::new(&opts.output, trd.timestamp_info.timer_frequency))
+            .transpose()
+            .map_err(|e| Error::PluginError(e.to_string()))?;
+        let clock_offset_ns = opts
+            .clock_offset_ns
+            .or_else(|| clock_sync.as_ref().and_then(|c| c.offset_ns));
+        let influx = opts
+            .influx
+            .as_ref()
+            .map(|path| {
+                InfluxExport::new(
+                    path,
+                    &opts.trace_name,
+                    trd.timestamp_info.timer_frequency,
+                    clock_offset_ns.unwrap_or(0),
+                    opts.influx_interval_ms,
+                )
+            })
+            .transpose()
+            .map_err(|e| Error::PluginError(e.to_string()))?;
+        let markers = (!config.markers.is_empty()).then(|| {
+            let path = opts.markers.clone().unwrap_or_else(|| {
+                let mut path = opts.output.clone().into_os_string();
+                path.push(".markers.json");
+                path.into()
+            });
+            Markers::new(path, config.markers)
+        });
+        let latency_checker = (!config.latency_budgets.is_empty()).then(|| {
+            let path = opts.latency_report.clone().unwrap_or_else(|| {
+                let mut path = opts.output.clone().into_os_string();
+                path.push(".latency.json");
+                path.into()
+            });
+            LatencyChecker::new(
+                path,
+                trd.timestamp_info.timer_frequency,
+                config.latency_budgets,
+            )
+        });
+        let anomaly_detector = opts.anomaly_window_ms.map(|window_ms| {
+            AnomalyDetector::new(
+                trd.timestamp_info.timer_frequency.get_raw(),
+                window_ms,
+                opts.anomaly_burst_factor,
+                opts.anomaly_silence_factor,
+            )
+        });
+        let replay = opts
+            .replay_realtime
+            .then(|| Replay::new(opts.speed, trd.timestamp_info.timer_frequency));
         Ok(Self {
             interruptor,
             reader,
             clock_name,
             trace_name,
+            tracer_name,
+            tracer_version: (opts.tracer_major, opts.tracer_minor, opts.tracer_patchlevel),
+            trace_buffering_scheme,
             input_file_name,
             trace_creation_time: Utc::now(),
+            task_state_intervals: opts.task_state_intervals.clone(),
+            leak_report: opts.leak_report.clone(),
+            event_dictionary: opts.event_dictionary.clone(),
+            error_snapshot: opts.error_snapshot.clone(),
+            recent_events: Default::default(),
+            jsonl,
+            influx,
             trd,
             first_event_observed: false,
+            pre_start: opts.pre_start,
+            trace_start_observed: opts.continued,
+            continued: opts.continued,
+            midrun_capture: opts.midrun_capture,
+            started_midrun: false,
+            peeked_event: None,
+            batch_size: opts.batch_size,
+            pending_messages: Vec::new(),
+            batch_input_ended: false,
+            pre_start_events: Default::default(),
+            first_event_wraparounds: None,
+            pre_start_dropped: 0,
+            last_event_count: 0,
+            in_duplicate_run: false,
+            pending_error: None,
             eof_reached: false,
             stream_is_open: false,
             // NOTE: timestamp/event trackers get re-initialized on the first event
-            time_rollover_tracker: StreamingInstant::zero(),
-            event_counter_tracker: TrackingEventCounter::zero(),
+            time_rollover_tracker: opts.timestamp_tracker.tracker(),
+            zero_base_time: opts.zero_base_time,
+            time_base: None,
+            timestamp_scale,
+            tick_rate_check,
+            event_counter_tracker: opts.event_count_tracker.tracker(),
+            counters: Default::default(),
+            heartbeat: Heartbeat::new(
+                opts.heartbeat_events,
+                opts.heartbeat_megabytes.map(|mb| mb * 1_000_000),
+            ),
+            anomaly_detector,
+            memory_monitor: opts.memory_budget_mb.map(MemoryMonitor::new),
+            markers,
+            latency_checker,
+            filter: opts.filter.clone(),
+            sampler: Sampler::new(&opts.sample),
+            replay,
+            converter,
+            clock_offset_ns,
+            clock_sync,
+            stats,
             stream: ptr::null_mut(),
             packet: ptr::null_mut(),
-            converter: TrcCtfConverter::new(),
+            stream_per_isr: opts.stream_per_isr,
+            isr_stream: ptr::null_mut(),
+            isr_packet: ptr::null_mut(),
+            os_tick_clock,
+            tick_stream: ptr::null_mut(),
+            tick_packet: ptr::null_mut(),
+            packet_context,
         })
     }
 
@@ -182,37 +1421,13 @@ impl TrcPluginState {
             let ret =
                 ffi::bt_clock_class_set_name(clock_class, self.clock_name.as_c_str().as_ptr());
             ret.capi_result()?;
-            ffi::bt_clock_class_set_frequency(
-                clock_class,
-                self.trd.timestamp_info.timer_frequency.get_raw() as _,
-            );
-            ffi::bt_clock_class_set_origin_is_unix_epoch(clock_class, 0);
+            let freq = u64::from(self.trd.timestamp_info.timer_frequency.get_raw());
+            ffi::bt_clock_class_set_frequency(clock_class, freq);
+            set_clock_offset(clock_class, freq, self.clock_offset_ns);
 
-            let stream_class = ffi::bt_stream_class_create(trace_class);
-            ffi::bt_stream_class_set_default_clock_class(stream_class, clock_class);
-            ffi::bt_stream_class_set_supports_packets(
-                stream_class,
-                1, //supports_packets
-                0, // with_beginning_default_clock_snapshot
-                0, // with_end_default_clock_snapshot
-            );
-            ffi::bt_stream_class_set_supports_discarded_packets(
-                stream_class,
-                0, // supports_discarded_packets
-                0, // with_default_clock_snapshots
-            );
-            ffi::bt_stream_class_set_supports_discarded_events(
-                stream_class,
-                1, // supports_discarded_events
-                0, // with_default_clock_snapshots
-            );
-            let ret = ffi::bt_stream_class_set_event_common_context_field_class(
-                stream_class,
-                base_event_context,
-            );
-            ret.capi_result()?;
+            let stream_class = create_stream_class(trace_class, clock_class, base_event_context)?;
 
-            // Add cpu_id packet context
+            // Add cpu_id and the configured packet context
             let packet_context_fc = ffi::bt_field_class_structure_create(trace_class);
             let cpu_id_fc = ffi::bt_field_class_integer_unsigned_create(trace_class);
             let ret = ffi::bt_field_class_structure_append_member(
@@ -221,11 +1436,52 @@ impl TrcPluginState {
                 cpu_id_fc,
             );
             ret.capi_result()?;
+            for (name, value) in self.packet_context.iter() {
+                let fc = match value {
+                    PacketContextValue::Unsigned(_) => {
+                        ffi::bt_field_class_integer_unsigned_create(trace_class)
+                    }
+                    PacketContextValue::Signed(_) => {
+                        ffi::bt_field_class_integer_signed_create(trace_class)
+                    }
+                    PacketContextValue::String(_) => ffi::bt_field_class_string_create(trace_class),
+                };
+                let ret = ffi::bt_field_class_structure_append_member(
+                    packet_context_fc,
+                    name.as_ptr(),
+                    fc,
+                );
+                ret.capi_result()?;
+                ffi::bt_field_class_put_ref(fc);
+            }
             let ret = ffi::bt_stream_class_set_packet_context_field_class(
                 stream_class,
                 packet_context_fc,
             );
             ret.capi_result()?;
+
+            // The secondary clock, with a stream class of its own for the tick-referenced events
+            let mut tick_stream_class = ptr::null_mut();
+            if let Some(os_tick_clock) = self.os_tick_clock {
+                let tick_clock_class = ffi::bt_clock_class_create(component.inner_mut());
+                let ret =
+                    ffi::bt_clock_class_set_name(tick_clock_class, b"os_ticks\0".as_ptr() as _);
+                ret.capi_result()?;
+                ffi::bt_clock_class_set_frequency(tick_clock_class, os_tick_clock.tick_rate_hz);
+                set_clock_offset(
+                    tick_clock_class,
+                    os_tick_clock.tick_rate_hz,
+                    self.clock_offset_ns.map(|o| os_tick_clock.offset_ns(o)),
+                );
+                tick_stream_class =
+                    create_stream_class(trace_class, tick_clock_class, base_event_context)?;
+                let ret = ffi::bt_stream_class_set_packet_context_field_class(
+                    tick_stream_class,
+                    packet_context_fc,
+                );
+                ret.capi_result()?;
+                ffi::bt_clock_class_put_ref(tick_clock_class);
+            }
             ffi::bt_field_class_put_ref(cpu_id_fc);
             ffi::bt_field_class_put_ref(packet_context_fc);
 
@@ -233,6 +1489,13 @@ impl TrcPluginState {
             ffi::bt_trace_set_name(trace, self.trace_name.as_c_str().as_ptr());
 
             self.stream = ffi::bt_stream_create(stream_class, trace);
+            if self.stream_per_isr {
+                self.isr_stream = ffi::bt_stream_create(stream_class, trace);
+            }
+            if !tick_stream_class.is_null() {
+                self.tick_stream = ffi::bt_stream_create(tick_stream_class, trace);
+                ffi::bt_stream_class_put_ref(tick_stream_class);
+            }
             self.create_new_packet()?;
 
             // Put the references we don't need anymore
@@ -264,31 +1527,31 @@ impl TrcPluginState {
             let ret = ffi::bt_trace_set_environment_entry_string(
                 trace,
                 b"tracer_name\0".as_ptr() as _,
-                b"lttng-modules\0".as_ptr() as _,
+                self.tracer_name.as_c_str().as_ptr(),
             );
             ret.capi_result()?;
             let ret = ffi::bt_trace_set_environment_entry_integer(
                 trace,
                 b"tracer_major\0".as_ptr() as _,
-                2,
+                self.tracer_version.0.into(),
             );
             ret.capi_result()?;
             let ret = ffi::bt_trace_set_environment_entry_integer(
                 trace,
                 b"tracer_minor\0".as_ptr() as _,
-                12,
+                self.tracer_version.1.into(),
             );
             ret.capi_result()?;
             let ret = ffi::bt_trace_set_environment_entry_integer(
                 trace,
                 b"tracer_patchlevel\0".as_ptr() as _,
-                5,
+                self.tracer_version.2.into(),
             );
             ret.capi_result()?;
             let ret = ffi::bt_trace_set_environment_entry_string(
                 trace,
                 b"trace_buffering_scheme\0".as_ptr() as _,
-                b"global\0".as_ptr() as _,
+                self.trace_buffering_scheme.as_c_str().as_ptr(),
             );
             ret.capi_result()?;
             let val = CString::new(self.trd.header.endianness.to_string())?;
@@ -304,6 +1567,24 @@ impl TrcPluginState {
                 self.trd.header.format_version.into(),
             );
             ret.capi_result()?;
+            let val = CString::new(probe::PARSER_VERSION)?;
+            let ret = ffi::bt_trace_set_environment_entry_string(
+                trace,
+                b"trc_parser_version\0".as_ptr() as _,
+                val.as_c_str().as_ptr(),
+            );
+            ret.capi_result()?;
+            let val = CString::new(format!(
+                "{}-{}",
+                probe::SUPPORTED_FORMAT_VERSIONS.start(),
+                probe::SUPPORTED_FORMAT_VERSIONS.end()
+            ))?;
+            let ret = ffi::bt_trace_set_environment_entry_string(
+                trace,
+                b"trc_supported_format_versions\0".as_ptr() as _,
+                val.as_c_str().as_ptr(),
+            );
+            ret.capi_result()?;
             let val = CString::new(format!("{:X?}", self.trd.header.kernel_version))?;
             let ret = ffi::bt_trace_set_environment_entry_string(
                 trace,
@@ -355,6 +1636,38 @@ impl TrcPluginState {
                 val.as_c_str().as_ptr(),
             );
             ret.capi_result()?;
+            let val = if self.started_midrun {
+                b"true\0".as_ptr()
+            } else {
+                b"false\0".as_ptr()
+            };
+            let ret = ffi::bt_trace_set_environment_entry_string(
+                trace,
+                b"trc_capture_started_midrun\0".as_ptr() as _,
+                val as _,
+            );
+            ret.capi_result()?;
+            // Correlation point of the os_ticks clock with the timer clock
+            if let Some(os_tick_clock) = self.os_tick_clock {
+                let ret = ffi::bt_trace_set_environment_entry_integer(
+                    trace,
+                    b"os_tick_rate_hz\0".as_ptr() as _,
+                    os_tick_clock.tick_rate_hz as _,
+                );
+                ret.capi_result()?;
+                let ret = ffi::bt_trace_set_environment_entry_integer(
+                    trace,
+                    b"os_tick_anchor_count\0".as_ptr() as _,
+                    os_tick_clock.anchor_ticks as _,
+                );
+                ret.capi_result()?;
+                let ret = ffi::bt_trace_set_environment_entry_integer(
+                    trace,
+                    b"os_tick_anchor_timestamp\0".as_ptr() as _,
+                    os_tick_clock.anchor_timestamp as _,
+                );
+                ret.capi_result()?;
+            }
         }
         Ok(())
     }
@@ -364,24 +1677,315 @@ impl TrcPluginState {
             if !self.packet.is_null() {
                 ffi::bt_packet_put_ref(self.packet);
             }
+            self.packet = create_packet(self.stream, &self.packet_context)?;
 
-            self.packet = ffi::bt_packet_create(self.stream);
+            if !self.isr_stream.is_null() {
+                if !self.isr_packet.is_null() {
+                    ffi::bt_packet_put_ref(self.isr_packet);
+                }
+                self.isr_packet = create_packet(self.isr_stream, &self.packet_context)?;
+            }
 
-            let packet_ctx_f = ffi::bt_packet_borrow_context_field(self.packet);
-            let cpu_id_f = ffi::bt_field_structure_borrow_member_field_by_index(packet_ctx_f, 0);
-            ffi::bt_field_integer_unsigned_set_value(cpu_id_f, 0);
+            if !self.tick_stream.is_null() {
+                if !self.tick_packet.is_null() {
+                    ffi::bt_packet_put_ref(self.tick_packet);
+                }
+                self.tick_packet = create_packet(self.tick_stream, &self.packet_context)?;
+            }
         }
         Ok(())
     }
 
+    /// Called once when the end of the input is reached, or on early shutdown
+    fn end_of_stream(&mut self) -> Result<(), Error> {
+        if let Some(path) = self.task_state_intervals.as_ref() {
+            info!(path = %path.display(), "Writing task state intervals");
+            let task_groups = self.stats.lock().unwrap().task_groups.clone();
+            self.converter
+                .write_task_state_intervals(
+                    path,
+                    self.trd.timestamp_info.timer_frequency,
+                    &task_groups,
+                )
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        if let Some(path) = self.leak_report.as_ref() {
+            info!(path = %path.display(), "Writing leak report");
+            self.converter
+                .write_leak_report(path, self.trd.timestamp_info.timer_frequency)
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        if let Some(estimate) = self
+            .tick_rate_check
+            .as_ref()
+            .and_then(|c| c.estimate(self.trd.timestamp_info.timer_frequency.get_raw()))
+        {
+            self.stats.lock().unwrap().observed_timer_frequency = Some(estimate.observed);
+            if estimate.is_mismatch() {
+                warn!(
+                    declared = estimate.declared,
+                    observed = estimate.observed,
+                    samples = estimate.samples,
+                    "The declared timer frequency doesn't match the OS tick rate, \
+                    see --trust-ticks"
+                );
+            }
+        }
+        if let Some(influx) = self.influx.as_mut() {
+            let last_timestamp = self.stats.lock().unwrap().last_timestamp;
+            influx
+                .finish(last_timestamp)
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        if let Some(path) = self.event_dictionary.as_ref() {
+            info!(path = %path.display(), "Writing event dictionary");
+            let mut stream_classes =
+                vec![unsafe { ffi::bt_stream_borrow_class_const(self.stream) }];
+            if !self.tick_stream.is_null() {
+                stream_classes.push(unsafe { ffi::bt_stream_borrow_class_const(self.tick_stream) });
+            }
+            unsafe { dictionary::write(path, &stream_classes) }
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        if let Some(markers) = self.markers.as_ref() {
+            info!(path = %markers.path().display(), "Writing markers");
+            markers
+                .write(self.trd.timestamp_info.timer_frequency)
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        if let Some(latency_checker) = self.latency_checker.as_ref() {
+            info!(path = %latency_checker.path().display(), "Writing latency report");
+            latency_checker
+                .write()
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            stats: self.stats.lock().unwrap().estimated_size(),
+            pre_start_events: self.pre_start_events.len() * mem::size_of::<(EventCode, Event)>(),
+            ..Default::default()
+        };
+        self.converter.memory_usage(&mut usage);
+        usage
+    }
+
+    /// Report the memory usage, and drop the state that can be dropped when it's over the
+    /// --memory-budget-mb budget
+    fn check_memory(&mut self) {
+        if !self
+            .memory_monitor
+            .as_mut()
+            .is_some_and(|m| m.is_check_due())
+        {
+            return;
+        }
+        let usage = self.memory_usage();
+        let Some(monitor) = self.memory_monitor.as_mut() else {
+            return;
+        };
+        let budget_bytes = monitor.budget_bytes();
+        if monitor.is_report_due() {
+            info!(
+                total_bytes = usage.total(),
+                budget_bytes,
+                %usage,
+                "Converter memory usage"
+            );
+        }
+        if !monitor.is_over_budget(&usage) {
+            return;
+        }
+
+        warn!(
+            total_bytes = usage.total(),
+            budget_bytes,
+            %usage,
+            "Converter memory usage is over the budget, dropping state"
+        );
+        self.converter.shed_memory();
+        let dropped = self.pre_start_events.len() / 2;
+        if dropped != 0 {
+            self.pre_start_events.drain(..dropped);
+            self.pre_start_events.shrink_to_fit();
+            self.pre_start_dropped += dropped as u64;
+            warn!(
+                events = dropped,
+                "Discarded the oldest events held back before TRACE_START to stay within \
+                the memory budget"
+            );
+        }
+
+        let usage = self.memory_usage();
+        if let Some(monitor) = self.memory_monitor.as_mut() {
+            if monitor.is_over_budget(&usage) && monitor.warn_still_over_budget() {
+                warn!(
+                    total_bytes = usage.total(),
+                    budget_bytes,
+                    %usage,
+                    "Converter memory usage is still over the budget, the rest can't be dropped"
+                );
+            }
+        }
+    }
+
+    fn write_error_snapshot(&self, error: &Error) {
+        let Some(path) = self.error_snapshot.as_ref() else {
+            return;
+        };
+        let (active_context, pending_isrs) = self.converter.contexts();
+        let snapshot = StateSnapshot {
+            error: error.to_string(),
+            events: self.counters.events,
+            event_count: self.event_counter_tracker.count(),
+            last_event_count: self.last_event_count,
+            timestamp: self.stats.lock().unwrap().last_timestamp.ticks(),
+            active_context: ContextSnapshot::from(active_context),
+            isr_stack: pending_isrs.iter().map(ContextSnapshot::from).collect(),
+            recent_events: self.recent_events.snapshot(),
+        };
+        info!(path = %path.display(), "Writing error snapshot");
+        if let Err(e) = snapshot.write(path) {
+            warn!(%e, "Failed to write the error snapshot");
+        }
+    }
+
+    /// Hand the messages over, writing the events out as JSON Lines first if enabled
+    fn release_messages(
+        &mut self,
+        ctf_state: BorrowedCtfState,
+    ) -> Result<MessageIteratorStatus, Error> {
+        if let Some(jsonl) = self.jsonl.as_mut() {
+            jsonl
+                .write_messages(ctf_state.ready_messages())
+                .and_then(|_| {
+                    if self.eof_reached {
+                        jsonl.flush()
+                    } else {
+                        Ok(())
+                    }
+                })
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+        let (status, pending_messages) = ctf_state.release();
+        self.pending_messages = pending_messages;
+        Ok(status)
+    }
+
+    /// Pushes at most MAX_END_MESSAGES messages
+    fn push_end_messages(&mut self, ctf_state: &mut BorrowedCtfState) -> Result<(), Error> {
+        self.converter.emit_coalesced_events(None, ctf_state)?;
+
+        if !self.isr_stream.is_null() {
+            let msg = unsafe {
+                ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.isr_packet)
+            };
+            ctf_state.push_message(msg)?;
+            let msg = unsafe {
+                ffi::bt_message_stream_end_create(ctf_state.message_iter_mut(), self.isr_stream)
+            };
+            ctf_state.push_message(msg)?;
+        }
+
+        if !self.tick_stream.is_null() {
+            let msg = unsafe {
+                ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.tick_packet)
+            };
+            ctf_state.push_message(msg)?;
+            let msg = unsafe {
+                ffi::bt_message_stream_end_create(ctf_state.message_iter_mut(), self.tick_stream)
+            };
+            ctf_state.push_message(msg)?;
+        }
+
+        // Add packet end message
+        let msg =
+            unsafe { ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.packet) };
+        ctf_state.push_message(msg)?;
+
+        // Add stream end message
+        let msg =
+            unsafe { ffi::bt_message_stream_end_create(ctf_state.message_iter_mut(), self.stream) };
+        ctf_state.push_message(msg)?;
+
+        Ok(())
+    }
+
+    /// Returns false when the conversion failed, the trace is finalized and the error
+    /// is returned on the next iteration
+    fn convert_event(
+        &mut self,
+        event_code: EventCode,
+        event: Event,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<bool, Error> {
+        // TODO need to put_ref(msg) on this and/or all of the msgs?
+        let msg_count = ctf_state.message_count();
+        match self.process_event(event_code, event, ctf_state) {
+            Ok(()) => {
+                self.stats
+                    .lock()
+                    .unwrap()
+                    .add_event_messages(ctf_state.messages_after(msg_count));
+                self.check_memory();
+                Ok(true)
+            }
+            Err(e) => {
+                self.write_error_snapshot(&e);
+                // Finalize the packet and stream so the trace written so far stays readable,
+                // the error is returned on the next iteration
+                error!(%e, "Conversion failed, finalizing the trace");
+                ctf_state.discard_messages_after(msg_count);
+                self.eof_reached = true;
+                if let Err(e) = self.end_of_stream() {
+                    warn!(%e, "Failed to finish the end of stream outputs");
+                }
+                self.push_end_messages(ctf_state)?;
+                self.pending_error = Some(e);
+                Ok(false)
+            }
+        }
+    }
+
     fn read_event(&mut self) -> Result<Option<(EventCode, Event)>, Error> {
         if self.eof_reached {
             return Ok(None);
         }
 
+        if let Some(ev) = self.peeked_event.take() {
+            return Ok(Some(ev));
+        }
+
+        // Buffered events are only left over once TRACE_START (queued behind them) is observed,
+        // or when they're being flushed at the end of the input
+        if self.trace_start_observed {
+            if let Some(ev) = self.pre_start_events.pop_front() {
+                return Ok(Some(ev));
+            }
+        }
+
         match self.trd.read_event(&mut self.reader) {
             Ok(Some(ev)) => Ok(Some(ev)),
-            Ok(None) => Ok(None),
+            Ok(None) if !self.pre_start_events.is_empty() => {
+                warn!(
+                    events = self.pre_start_events.len(),
+                    "No TRACE_START found, converting the buffered events"
+                );
+                self.trace_start_observed = true;
+                Ok(self.pre_start_events.pop_front())
+            }
+            Ok(None) => {
+                if self.pre_start_dropped != 0 {
+                    warn!(
+                        events = self.pre_start_dropped,
+                        "No TRACE_START found, all events were dropped"
+                    );
+                    self.pre_start_dropped = 0;
+                }
+                Ok(None)
+            }
             Err(e) => {
                 use trace_recorder_parser::streaming::Error as TrcError;
 
@@ -395,6 +1999,7 @@ impl TrcPluginState {
                         )
                         .map_err(|e| Error::PluginError(e.to_string()))?;
                         self.first_event_observed = false;
+                        self.trace_start_observed = false;
                         Ok(None)
                     }
                     _ => {
@@ -414,26 +2019,91 @@ impl TrcPluginState {
     ) -> Result<(), Error> {
         let event_type = event_code.event_type();
 
+        if self.pre_start != PreStartPolicy::Keep && !self.trace_start_observed {
+            if event_type == EventType::TraceStart {
+                self.trace_start_observed = true;
+                if self.pre_start_dropped != 0 {
+                    warn!(
+                        events = self.pre_start_dropped,
+                        "Discarded the events before TRACE_START"
+                    );
+                }
+                self.pre_start_dropped = 0;
+                if let Some((_, first)) = self.pre_start_events.front() {
+                    // The held back events are converted first (read_event hands them out once
+                    // TRACE_START is observed), followed by TRACE_START itself. The trackers
+                    // start from the first of them on the timeline of TRACE_START: the header's
+                    // timer wraparounds are anchored at TRACE_START rather than the first event.
+                    let wraparounds = self.trd.timestamp_info.timer_wraparounds;
+                    self.first_event_wraparounds =
+                        Some(if first.timestamp().ticks() > event.timestamp().ticks() {
+                            wraparounds.saturating_sub(1)
+                        } else {
+                            wraparounds
+                        });
+                    debug!(
+                        events = self.pre_start_events.len(),
+                        "Converting the events held back until TRACE_START"
+                    );
+                    self.pre_start_events.push_back((event_code, event));
+                    self.counters.bytes = self.reader.bytes_read();
+                    self.stats.lock().unwrap().counters = self.counters;
+                    return Ok(());
+                }
+            } else {
+                match self.pre_start {
+                    PreStartPolicy::Drop => self.pre_start_dropped += 1,
+                    _ => self.pre_start_events.push_back((event_code, event)),
+                }
+                self.counters.bytes = self.reader.bytes_read();
+                self.stats.lock().unwrap().counters = self.counters;
+                return Ok(());
+            }
+        }
+
         let dropped_events = if !self.first_event_observed {
             self.first_event_observed = true;
 
-            if event_type != EventType::TraceStart {
+            if event_type != EventType::TraceStart && !self.continued && !self.midrun_capture {
                 warn!(%event_type, "First event should be TRACE_START");
             }
 
-            self.event_counter_tracker
-                .set_initial_count(event.event_count());
-            self.time_rollover_tracker = StreamingInstant::new(
-                event.timestamp().ticks() as u32,
-                self.trd.timestamp_info.timer_wraparounds,
-            );
+            self.event_counter_tracker.init(event.event_count());
+            let wraparounds = self
+                .first_event_wraparounds
+                .take()
+                .unwrap_or(self.trd.timestamp_info.timer_wraparounds);
+            self.time_rollover_tracker
+                .init(event.timestamp(), wraparounds);
 
             None
         } else {
+            let event_count = u16::from(event.event_count());
+            if is_duplicate_event_count(self.last_event_count, event_count) {
+                if !self.in_duplicate_run {
+                    self.in_duplicate_run = true;
+                    warn!(
+                        %event_count,
+                        last_event_count = self.last_event_count,
+                        "Skipping duplicate events"
+                    );
+                }
+                self.counters.duplicate_events += 1;
+                self.counters.bytes = self.reader.bytes_read();
+                self.stats.lock().unwrap().counters = self.counters;
+                return Ok(());
+            }
+            self.in_duplicate_run = false;
             self.event_counter_tracker.update(event.event_count())
         };
+        self.last_event_count = event.event_count().into();
+
+        self.counters.events += 1;
+        self.counters.bytes = self.reader.bytes_read();
+        self.recent_events.push(event_code);
 
         if let Some(dropped_events) = dropped_events {
+            self.counters.dropped_events += dropped_events;
             warn!(
                 event_count = %event.event_count(),
                 dropped_events, "Detected dropped events"
@@ -450,18 +2120,254 @@ impl TrcPluginState {
 
         // Update timer/counter rollover trackers
         let event_count = self.event_counter_tracker.count();
-        let timestamp = self.time_rollover_tracker.elapsed(event.timestamp());
+        let raw_timestamp = event.timestamp();
+        let mut timestamp = self.time_rollover_tracker.elapsed(raw_timestamp);
+        if let Some(scale) = self.timestamp_scale {
+            timestamp = scale.apply(timestamp);
+        }
+        if self.zero_base_time {
+            let time_base = *self.time_base.get_or_insert(timestamp.ticks());
+            timestamp = Timestamp::from(timestamp.ticks().saturating_sub(time_base));
+        }
+
+        if let Some(replay) = self.replay.as_mut() {
+            replay.wait(timestamp, &self.interruptor);
+        }
 
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.counters = self.counters;
+            stats.last_timestamp = timestamp;
+            *stats.events_by_type.entry(event_type).or_default() += 1;
+            match &event {
+                Event::TaskResume(ev) | Event::TaskActivate(ev) => {
+                    match stats.task_activations.get_mut(ev.name.as_ref()) {
+                        Some(n) => *n += 1,
+                        None => {
+                            stats.task_activations.insert(ev.name.to_string(), 1);
+                        }
+                    }
+                    stats.task_switched(&ev.name, timestamp);
+                }
+                Event::MemoryAlloc(ev) => stats.add_alloc_size(ev.size),
+                Event::Unknown(_) if event_type == EventType::MallocFailed => {
+                    stats.malloc_failures += 1
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(markers) = self.markers.as_mut() {
+            markers.observe(event_type, &event, event_count, timestamp);
+        }
+        if let Some(check) = self.tick_rate_check.as_mut() {
+            check.observe(event_type, &event, timestamp);
+        }
+        if let Some(influx) = self.influx.as_mut() {
+            influx
+                .observe(&event, timestamp)
+                .map_err(|e| Error::PluginError(e.to_string()))?;
+        }
+
+        let latency_violations = self
+            .latency_checker
+            .as_mut()
+            .map(|c| c.observe(event_type, &event, timestamp))
+            .unwrap_or_default();
+
+        // Filtered and sampled out events are still converted to keep the converter state
+        // consistent, only their messages are discarded
+        let keep = self
+            .filter
+            .as_ref()
+            .map(|f| f.matches(event_type, &event))
+            .unwrap_or(true);
+        let sample_weight = if self.sampler.is_enabled() {
+            self.sampler.sample(event_type, &event)
+        } else {
+            Some(1)
+        };
+        let host_time_ns = self
+            .clock_sync
+            .as_ref()
+            .and_then(|c| c.host_time_ns(&event));
+        // Coalesced runs aren't subject to filtering and sampling
         self.converter
-            .convert(event_code, event_count, timestamp, event, ctf_state)?;
+            .emit_coalesced_events(Some(event_type), ctf_state)?;
+        let msg_count = ctf_state.message_count();
+        self.converter.convert(
+            event_code,
+            event_count,
+            timestamp,
+            event,
+            sample_weight.unwrap_or(1),
+            ctf_state,
+        )?;
+        if !keep || sample_weight.is_none() {
+            ctf_state.discard_messages_after(msg_count);
+        }
+        if let Some(isr) = self.converter.take_isr_execution() {
+            self.stats.lock().unwrap().add_isr_execution(isr);
+        }
+        if let Some(lock) = self.converter.take_scheduler_lock() {
+            self.stats.lock().unwrap().add_scheduler_lock(lock);
+        }
+
+        for violation in latency_violations.iter() {
+            warn!(
+                budget = violation.budget,
+                latency_ns = violation.latency_ns,
+                budget_ns = violation.budget_ns,
+                "Latency budget exceeded"
+            );
+            self.converter.emit_latency_violation(
+                event_code,
+                event_count,
+                timestamp,
+                raw_timestamp,
+                violation,
+                ctf_state,
+            )?;
+        }
+
+        if let Some(host_time_ns) = host_time_ns {
+            let offset_ns = clock_sync::offset_ns(
+                host_time_ns,
+                timestamp,
+                self.trd.timestamp_info.timer_frequency,
+            );
+            self.converter.emit_clock_correlation(
+                event_code,
+                event_count,
+                timestamp,
+                raw_timestamp,
+                host_time_ns,
+                offset_ns,
+                ctf_state,
+            )?;
+        }
+
+        if self.heartbeat.is_enabled() && self.heartbeat.is_due(&self.counters) {
+            self.converter.emit_heartbeat(
+                event_code,
+                event_count,
+                timestamp,
+                raw_timestamp,
+                &self.counters,
+                ctf_state,
+            )?;
+        }
+
+        if let Some(detector) = self.anomaly_detector.as_mut() {
+            for anomaly in detector.observe(event_type, timestamp) {
+                warn!(
+                    kind = ?anomaly.kind,
+                    %event_type,
+                    count = anomaly.count,
+                    expected = anomaly.expected,
+                    duration_ns = anomaly.duration_ns,
+                    "Detected an event rate anomaly"
+                );
+                self.converter.emit_anomaly(
+                    event_code,
+                    event_count,
+                    timestamp,
+                    raw_timestamp,
+                    &anomaly,
+                    ctf_state,
+                )?;
+            }
+        }
 
         Ok(())
     }
 }
 
+/// Align the clock to the host wall-clock time when there's an offset
+unsafe fn set_clock_offset(
+    clock_class: *mut ffi::bt_clock_class,
+    freq: u64,
+    offset_ns: Option<i64>,
+) {
+    match offset_ns {
+        Some(offset_ns) => {
+            let seconds = offset_ns.div_euclid(1_000_000_000);
+            let nanos = offset_ns.rem_euclid(1_000_000_000) as u64;
+            let cycles = (u128::from(nanos) * u128::from(freq) / 1_000_000_000) as u64;
+            ffi::bt_clock_class_set_offset(clock_class, seconds, cycles);
+            ffi::bt_clock_class_set_origin_is_unix_epoch(clock_class, 1);
+        }
+        None => ffi::bt_clock_class_set_origin_is_unix_epoch(clock_class, 0),
+    }
+}
+
+unsafe fn create_stream_class(
+    trace_class: *mut ffi::bt_trace_class,
+    clock_class: *mut ffi::bt_clock_class,
+    base_event_context: *mut ffi::bt_field_class,
+) -> Result<*mut ffi::bt_stream_class, Error> {
+    let stream_class = ffi::bt_stream_class_create(trace_class);
+    // Event class IDs are assigned explicitly, see events.rs
+    ffi::bt_stream_class_set_assigns_automatic_event_class_id(stream_class, 0);
+    ffi::bt_stream_class_set_default_clock_class(stream_class, clock_class);
+    ffi::bt_stream_class_set_supports_packets(
+        stream_class,
+        1, //supports_packets
+        0, // with_beginning_default_clock_snapshot
+        0, // with_end_default_clock_snapshot
+    );
+    ffi::bt_stream_class_set_supports_discarded_packets(
+        stream_class,
+        0, // supports_discarded_packets
+        0, // with_default_clock_snapshots
+    );
+    ffi::bt_stream_class_set_supports_discarded_events(
+        stream_class,
+        1, // supports_discarded_events
+        0, // with_default_clock_snapshots
+    );
+    let ret =
+        ffi::bt_stream_class_set_event_common_context_field_class(stream_class, base_event_context);
+    ret.capi_result()?;
+    Ok(stream_class)
+}
+
+unsafe fn create_packet(
+    stream: *mut ffi::bt_stream,
+    packet_context: &[(CString, PacketContextValue)],
+) -> Result<*mut ffi::bt_packet, Error> {
+    let packet = ffi::bt_packet_create(stream);
+    let packet_ctx_f = ffi::bt_packet_borrow_context_field(packet);
+    let cpu_id_f = ffi::bt_field_structure_borrow_member_field_by_index(packet_ctx_f, 0);
+    ffi::bt_field_integer_unsigned_set_value(cpu_id_f, 0);
+    for (index, (_, value)) in packet_context.iter().enumerate() {
+        let f =
+            ffi::bt_field_structure_borrow_member_field_by_index(packet_ctx_f, index as u64 + 1);
+        match value {
+            PacketContextValue::Unsigned(v) => ffi::bt_field_integer_unsigned_set_value(f, *v),
+            PacketContextValue::Signed(v) => ffi::bt_field_integer_signed_set_value(f, *v),
+            PacketContextValue::String(v) => {
+                let v = CString::new(v.as_str())?;
+                let ret = ffi::bt_field_string_set_value(f, v.as_ptr());
+                ret.capi_result()?;
+            }
+        }
+    }
+    Ok(packet)
+}
+
 impl SourcePluginHandler for TrcPluginState {
     fn initialize(&mut self, component: SelfComponent) -> Result<(), Error> {
         self.create_metadata_and_stream_objects(component)?;
+
+        // The environment is frozen once the stream starts, peek at the first event to
+        // record whether the capture started mid-run
+        self.peeked_event = self.read_event()?;
+        self.started_midrun = !self.continued
+            && self
+                .peeked_event
+                .as_ref()
+                .is_some_and(|(code, _)| code.event_type() != EventType::TraceStart);
         self.set_trace_env()?;
 
         assert!(!self.stream.is_null());
@@ -472,6 +2378,11 @@ impl SourcePluginHandler for TrcPluginState {
 
     fn finalize(&mut self, _component: SelfComponent) -> Result<(), Error> {
         unsafe {
+            // Left over when the graph is torn down early
+            for msg in self.pending_messages.drain(..) {
+                ffi::bt_message_put_ref(msg);
+            }
+
             assert!(!self.packet.is_null());
             ffi::bt_packet_put_ref(self.packet);
             self.packet = ptr::null_mut();
@@ -479,6 +2390,20 @@ impl SourcePluginHandler for TrcPluginState {
             assert!(!self.stream.is_null());
             ffi::bt_stream_put_ref(self.stream);
             self.stream = ptr::null_mut();
+
+            if !self.isr_stream.is_null() {
+                ffi::bt_packet_put_ref(self.isr_packet);
+                self.isr_packet = ptr::null_mut();
+                ffi::bt_stream_put_ref(self.isr_stream);
+                self.isr_stream = ptr::null_mut();
+            }
+
+            if !self.tick_stream.is_null() {
+                ffi::bt_packet_put_ref(self.tick_packet);
+                self.tick_packet = ptr::null_mut();
+                ffi::bt_stream_put_ref(self.tick_stream);
+                self.tick_stream = ptr::null_mut();
+            }
         }
 
         Ok(())
@@ -491,28 +2416,40 @@ impl SourcePluginHandler for TrcPluginState {
     ) -> Result<MessageIteratorStatus, Error> {
         assert!(!self.stream.is_null());
 
-        let mut ctf_state = BorrowedCtfState::new(self.stream, self.packet, msg_iter, messages);
+        let mut ctf_state = BorrowedCtfState::new(
+            self.stream,
+            self.packet,
+            self.isr_packet,
+            self.tick_packet,
+            msg_iter,
+            messages,
+            mem::take(&mut self.pending_messages),
+        );
+
+        // Hand over what didn't fit last time before converting (or finishing) anything else
+        if ctf_state.has_pending() {
+            return self.release_messages(ctf_state);
+        }
+
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
 
         if self.interruptor.is_set() & !self.eof_reached {
             debug!("Early shutdown");
             self.eof_reached = true;
+            self.end_of_stream()?;
+            self.push_end_messages(&mut ctf_state)?;
 
-            // Add packet end message
-            let msg = unsafe {
-                ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.packet)
-            };
-            ctf_state.push_message(msg)?;
-
-            // Add stream end message
-            let msg = unsafe {
-                ffi::bt_message_stream_end_create(ctf_state.message_iter_mut(), self.stream)
-            };
-            ctf_state.push_message(msg)?;
-
-            return Ok(ctf_state.release());
+            return self.release_messages(ctf_state);
         }
 
-        match self.read_event()? {
+        let next_event = if mem::take(&mut self.batch_input_ended) {
+            None
+        } else {
+            self.read_event()?
+        };
+        match next_event {
             Some((event_code, event)) => {
                 if !self.stream_is_open {
                     debug!("Opening stream");
@@ -535,12 +2472,61 @@ impl SourcePluginHandler for TrcPluginState {
                         )
                     };
                     ctf_state.push_message(msg)?;
+
+                    if !self.isr_stream.is_null() {
+                        let msg = unsafe {
+                            ffi::bt_message_stream_beginning_create(
+                                ctf_state.message_iter_mut(),
+                                self.isr_stream,
+                            )
+                        };
+                        ctf_state.push_message(msg)?;
+                        let msg = unsafe {
+                            ffi::bt_message_packet_beginning_create(
+                                ctf_state.message_iter_mut(),
+                                self.isr_packet,
+                            )
+                        };
+                        ctf_state.push_message(msg)?;
+                    }
+
+                    if !self.tick_stream.is_null() {
+                        let msg = unsafe {
+                            ffi::bt_message_stream_beginning_create(
+                                ctf_state.message_iter_mut(),
+                                self.tick_stream,
+                            )
+                        };
+                        ctf_state.push_message(msg)?;
+                        let msg = unsafe {
+                            ffi::bt_message_packet_beginning_create(
+                                ctf_state.message_iter_mut(),
+                                self.tick_packet,
+                            )
+                        };
+                        ctf_state.push_message(msg)?;
+                    }
+
+                    self.converter.emit_selftest(&mut ctf_state)?;
                 }
 
-                // TODO need to put_ref(msg) on this and/or all of the msgs?
-                self.process_event(event_code, event, &mut ctf_state)?;
+                let mut next_event = Some((event_code, event));
+                let mut converted = 0;
+                while let Some((event_code, event)) = next_event.take() {
+                    if !self.convert_event(event_code, event, &mut ctf_state)? {
+                        break;
+                    }
+                    converted += 1;
+                    if converted < self.batch_size
+                        && ctf_state.capacity_left() > 0
+                        && !self.interruptor.is_set()
+                    {
+                        next_event = self.read_event()?;
+                        self.batch_input_ended = next_event.is_none();
+                    }
+                }
 
-                Ok(ctf_state.release())
+                self.release_messages(ctf_state)
             }
             None => {
                 if self.stream_is_open && !self.first_event_observed {
@@ -550,22 +2536,22 @@ impl SourcePluginHandler for TrcPluginState {
                     // Last iteration can't have messages
                     Ok(MessageIteratorStatus::Done)
                 } else {
+                    if !self
+                        .converter
+                        .emit_leak_candidates(MAX_END_MESSAGES, &mut ctf_state)?
+                        || !self
+                            .converter
+                            .emit_handle_table(MAX_END_MESSAGES, &mut ctf_state)?
+                    {
+                        // The rest are emitted on the following iterations
+                        return self.release_messages(ctf_state);
+                    }
                     debug!("End of file reached");
                     self.eof_reached = true;
+                    self.end_of_stream()?;
+                    self.push_end_messages(&mut ctf_state)?;
 
-                    // Add packet end message
-                    let msg = unsafe {
-                        ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.packet)
-                    };
-                    ctf_state.push_message(msg)?;
-
-                    // Add stream end message
-                    let msg = unsafe {
-                        ffi::bt_message_stream_end_create(ctf_state.message_iter_mut(), self.stream)
-                    };
-                    ctf_state.push_message(msg)?;
-
-                    Ok(ctf_state.release())
+                    self.release_messages(ctf_state)
                 }
             }
         }
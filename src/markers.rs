@@ -0,0 +1,103 @@
+use crate::event_fields::{event_name, fields_match};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+use trace_recorder_parser::{
+    streaming::event::{Event, EventType},
+    time::{Frequency, Timestamp},
+};
+
+/// Marks events of a type whose payload fields equal the given values
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkerRule {
+    pub name: String,
+    /// Trace recorder event type name, e.g. USER_EVENT
+    pub event: String,
+    /// CTF payload field name to value
+    #[serde(default)]
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl MarkerRule {
+    fn matches(&self, name: &str, event: &Event) -> bool {
+        self.event == name && fields_match(event, &self.fields)
+    }
+}
+
+/// Collects the events matched by the marker rules and writes them
+/// to a markers file alongside the CTF trace
+#[derive(Debug)]
+pub struct Markers {
+    path: PathBuf,
+    rules: Vec<MarkerRule>,
+    markers: Vec<(usize, u64, Timestamp)>,
+}
+
+impl Markers {
+    pub fn new(path: PathBuf, rules: Vec<MarkerRule>) -> Self {
+        Self {
+            path,
+            rules,
+            markers: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn observe(
+        &mut self,
+        event_type: EventType,
+        event: &Event,
+        event_count: u64,
+        timestamp: Timestamp,
+    ) {
+        let name = event_name(event_type, event);
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if rule.matches(&name, event) {
+                self.markers.push((idx, event_count, timestamp));
+            }
+        }
+    }
+
+    pub fn write(&self, timer_frequency: Frequency) -> io::Result<()> {
+        let freq = u128::from(timer_frequency.get_raw()).max(1);
+        let markers = self
+            .markers
+            .iter()
+            .map(|(idx, event_count, timestamp)| MarkerDump {
+                name: &self.rules[*idx].name,
+                event_type: &self.rules[*idx].event,
+                event_count: *event_count,
+                time_ns: (u128::from(timestamp.ticks()) * 1_000_000_000 / freq) as u64,
+            })
+            .collect();
+        let dump = MarkersDump {
+            timer_frequency: timer_frequency.get_raw(),
+            markers,
+        };
+        let writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(writer, &dump)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct MarkersDump<'a> {
+    timer_frequency: u32,
+    markers: Vec<MarkerDump<'a>>,
+}
+
+#[derive(Serialize)]
+struct MarkerDump<'a> {
+    name: &'a str,
+    event_type: &'a str,
+    event_count: u64,
+    time_ns: u64,
+}
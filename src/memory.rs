@@ -0,0 +1,107 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Events between memory usage checks
+const CHECK_INTERVAL_EVENTS: u64 = 100_000;
+
+/// Minimum time between memory usage reports
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Estimated heap bytes of the converter state that grows with the trace
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemoryUsage {
+    pub string_cache: usize,
+    pub isr_stack: usize,
+    pub stats: usize,
+    pub leaks: usize,
+    pub task_state_intervals: usize,
+    pub pre_start_events: usize,
+    pub object_names: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.string_cache
+            + self.isr_stack
+            + self.stats
+            + self.leaks
+            + self.task_state_intervals
+            + self.pre_start_events
+            + self.object_names
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "string_cache={} isr_stack={} stats={} leaks={} task_state_intervals={} pre_start_events={} object_names={}",
+            self.string_cache,
+            self.isr_stack,
+            self.stats,
+            self.leaks,
+            self.task_state_intervals,
+            self.pre_start_events,
+            self.object_names
+        )
+    }
+}
+
+/// Decides when the memory usage is checked and reported, and whether it's over the budget
+#[derive(Debug)]
+pub struct MemoryMonitor {
+    budget_bytes: usize,
+    /// Events read, including the ones not converted (yet)
+    events: u64,
+    next_check: u64,
+    last_report: Option<Instant>,
+    /// Over budget even after shedding, only warned about once
+    over_budget_warned: bool,
+}
+
+impl MemoryMonitor {
+    pub fn new(budget_mb: u64) -> Self {
+        Self {
+            budget_bytes: (budget_mb as usize).saturating_mul(1_000_000),
+            events: 0,
+            next_check: CHECK_INTERVAL_EVENTS,
+            last_report: None,
+            over_budget_warned: false,
+        }
+    }
+
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// Called for each event read, returns true when a check is due
+    pub fn is_check_due(&mut self) -> bool {
+        self.events += 1;
+        if self.events < self.next_check {
+            return false;
+        }
+        self.next_check = self.events.saturating_add(CHECK_INTERVAL_EVENTS);
+        true
+    }
+
+    /// Returns true when the usage should be reported
+    pub fn is_report_due(&mut self) -> bool {
+        let now = Instant::now();
+        match self.last_report {
+            Some(last) if now.duration_since(last) < REPORT_INTERVAL => false,
+            _ => {
+                self.last_report = Some(now);
+                true
+            }
+        }
+    }
+
+    pub fn is_over_budget(&self, usage: &MemoryUsage) -> bool {
+        usage.total() > self.budget_bytes
+    }
+
+    /// Returns true the first time the state can't be shed below the budget
+    pub fn warn_still_over_budget(&mut self) -> bool {
+        !std::mem::replace(&mut self.over_budget_warned, true)
+    }
+}
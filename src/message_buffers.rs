@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use trace_recorder_parser::types::ObjectHandle;
+
+/// Fill level of a message buffer after an operation, the derived fields are 0 when unknown
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MessageBufferUsage {
+    pub bytes_in_buffer: u32,
+    /// Bytes sent, received or discarded (reset), from the previous fill level
+    pub bytes_transferred: u32,
+    /// Space left, from the buffer size
+    pub bytes_available: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Level {
+    size: Option<u32>,
+    bytes_in_buffer: Option<u32>,
+}
+
+/// Tracks the fill level of each message buffer, the events only carry the bytes in the buffer
+#[derive(Debug, Default)]
+pub struct MessageBufferTracker {
+    buffers: HashMap<ObjectHandle, Level>,
+}
+
+impl MessageBufferTracker {
+    /// The buffer was created (MESSAGEBUFFER_CREATE)
+    pub fn created(&mut self, buffer: ObjectHandle, size: u32) -> MessageBufferUsage {
+        self.buffers.insert(
+            buffer,
+            Level {
+                size: Some(size),
+                bytes_in_buffer: Some(0),
+            },
+        );
+        MessageBufferUsage {
+            bytes_in_buffer: 0,
+            bytes_transferred: 0,
+            bytes_available: size,
+        }
+    }
+
+    /// The send, receive and reset events, with the bytes in the buffer after the operation.
+    /// The size and previous level of buffers created before the trace started are unknown.
+    pub fn updated(&mut self, buffer: ObjectHandle, bytes_in_buffer: u32) -> MessageBufferUsage {
+        let level = self.buffers.entry(buffer).or_default();
+        let usage = MessageBufferUsage {
+            bytes_in_buffer,
+            bytes_transferred: level
+                .bytes_in_buffer
+                .map(|prev| prev.abs_diff(bytes_in_buffer))
+                .unwrap_or(0),
+            bytes_available: level
+                .size
+                .map(|size| size.saturating_sub(bytes_in_buffer))
+                .unwrap_or(0),
+        };
+        level.bytes_in_buffer = Some(bytes_in_buffer);
+        usage
+    }
+}
@@ -0,0 +1,83 @@
+use crate::types::Context;
+use std::collections::{hash_map::Entry, HashMap};
+use trace_recorder_parser::types::ObjectHandle;
+
+/// A task blocked on a mutex held by a lower priority task, which inherits its priority
+#[derive(Clone, Debug)]
+pub struct Inheritance {
+    pub mutex: ObjectHandle,
+    pub owner: Context,
+    pub blocked: Context,
+}
+
+#[derive(Clone, Debug)]
+struct Owner {
+    task: Context,
+    /// Recursive takes not given back yet
+    depth: u32,
+    /// Priority of the highest priority task blocked on the mutex, when above the owner's
+    inherited_priority: Option<u32>,
+}
+
+/// Tracks the owner of each mutex from the take and give events
+#[derive(Debug, Default)]
+pub struct MutexTracker {
+    owners: HashMap<ObjectHandle, Owner>,
+}
+
+impl MutexTracker {
+    /// The task took the mutex (MUTEX_TAKE, MUTEX_TAKE_RECURSIVE)
+    pub fn taken(&mut self, mutex: ObjectHandle, task: &Context) {
+        match self.owners.get_mut(&mutex) {
+            Some(owner) if owner.task.handle == task.handle => owner.depth += 1,
+            // Either free or the give wasn't traced
+            _ => {
+                self.owners.insert(
+                    mutex,
+                    Owner {
+                        task: task.clone(),
+                        depth: 1,
+                        inherited_priority: None,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The task gave the mutex back (MUTEX_GIVE, MUTEX_GIVE_RECURSIVE), ignored unless it's the
+    /// owner, e.g. taken before the trace started
+    pub fn given(&mut self, mutex: ObjectHandle, task: &Context) {
+        if let Entry::Occupied(mut owner) = self.owners.entry(mutex) {
+            if owner.get().task.handle == task.handle {
+                owner.get_mut().depth -= 1;
+                if owner.get().depth == 0 {
+                    owner.remove();
+                }
+            }
+        }
+    }
+
+    /// The task blocked taking the mutex (MUTEX_TAKE_BLOCK, MUTEX_TAKE_RECURSIVE_BLOCK).
+    /// Returns the priority inheritance when the owner runs at a lower priority than the task.
+    pub fn blocked(&mut self, mutex: ObjectHandle, task: &Context) -> Option<Inheritance> {
+        let owner = self.owners.get_mut(&mutex)?;
+        let priority = u32::from(task.priority);
+        let owner_priority = owner
+            .inherited_priority
+            .unwrap_or_else(|| u32::from(owner.task.priority));
+        if owner.task.handle == task.handle || priority <= owner_priority {
+            return None;
+        }
+        owner.inherited_priority = Some(priority);
+        Some(Inheritance {
+            mutex,
+            owner: owner.task.clone(),
+            blocked: task.clone(),
+        })
+    }
+
+    /// The task holding the mutex
+    pub fn owner(&self, mutex: ObjectHandle) -> Option<&Context> {
+        self.owners.get(&mutex).map(|o| &o.task)
+    }
+}
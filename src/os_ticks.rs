@@ -0,0 +1,46 @@
+use trace_recorder_parser::{streaming::TimestampInfo, time::Timestamp};
+
+/// Maps the timer (e.g. cycle counter) timeline onto the OS tick count, for the secondary
+/// os_ticks clock class
+///
+/// The header's latest timestamp and OS tick count are the correlation point.
+#[derive(Copy, Clone, Debug)]
+pub struct OsTickClock {
+    pub tick_rate_hz: u64,
+    pub timer_frequency: u64,
+    /// OS tick count at the anchor timestamp
+    pub anchor_ticks: u64,
+    /// Timer timestamp the OS tick count was sampled at
+    pub anchor_timestamp: u64,
+}
+
+impl OsTickClock {
+    /// None when the header doesn't declare the OS tick rate
+    pub fn new(info: &TimestampInfo) -> Option<Self> {
+        let tick_rate_hz = u64::from(info.os_tick_rate_hz.get_raw());
+        let timer_frequency = u64::from(info.timer_frequency.get_raw());
+        (tick_rate_hz != 0 && timer_frequency != 0).then_some(Self {
+            tick_rate_hz,
+            timer_frequency,
+            anchor_ticks: info.os_tick_count.into(),
+            anchor_timestamp: info.latest_timestamp.ticks(),
+        })
+    }
+
+    /// The OS tick count at the timer timestamp
+    pub fn os_ticks(&self, timestamp: Timestamp) -> Timestamp {
+        let delta = i128::from(timestamp.ticks()) - i128::from(self.anchor_timestamp);
+        let ticks = i128::from(self.anchor_ticks)
+            + delta * i128::from(self.tick_rate_hz) / i128::from(self.timer_frequency);
+        Timestamp::from(ticks.max(0) as u64)
+    }
+
+    /// The os_ticks clock offset aligning it with the timer clock offset (both in nanoseconds)
+    pub fn offset_ns(&self, timer_offset_ns: i64) -> i64 {
+        let anchor_timer_ns =
+            i128::from(self.anchor_timestamp) * 1_000_000_000 / i128::from(self.timer_frequency);
+        let anchor_tick_ns =
+            i128::from(self.anchor_ticks) * 1_000_000_000 / i128::from(self.tick_rate_hz);
+        (i128::from(timer_offset_ns) + anchor_timer_ns - anchor_tick_ns) as i64
+    }
+}
@@ -0,0 +1,152 @@
+use babeltrace2_sys::{CtfPluginSinkFsInitParams, Error, LoggingLevel};
+use std::collections::hash_map::RandomState;
+use std::ffi::{CStr, CString};
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, io, process};
+
+/// Graph node name of the sink component, the only sink class linked into the converter
+pub const SINK_NODE_NAME: &str = "sink.ctf.fs";
+
+/// Output port of the source component
+const SOURCE_OUTPUT_PORT: &str = "out";
+
+/// Input port of the sink component
+const SINK_INPUT_PORT: &str = "in";
+
+/// Parameters of the sink.ctf.fs component
+#[derive(Clone, Debug)]
+pub struct CtfSinkParams {
+    pub assume_single_trace: Option<bool>,
+    pub ignore_discarded_events: Option<bool>,
+    pub ignore_discarded_packets: Option<bool>,
+    pub quiet: Option<bool>,
+    pub path: PathBuf,
+    /// The path as given to babeltrace
+    pub path_cstring: CString,
+}
+
+/// Uniquely named directory the CTF sink writes to when the events are written as JSON Lines,
+/// removed when dropped
+#[derive(Debug)]
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    pub fn new() -> io::Result<Self> {
+        loop {
+            // Seeded randomly per process
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(process::id());
+            let path = env::temp_dir().join(format!("trc-ctf-{:016x}", hasher.finish()));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A sink component parameter given with --sink-param
+#[derive(Clone, Debug)]
+pub struct SinkParam {
+    pub key: String,
+    pub value: String,
+}
+
+pub fn parse_sink_param(s: &str) -> Result<SinkParam, String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok(SinkParam {
+            key: key.trim().to_owned(),
+            value: value.trim().to_owned(),
+        }),
+        _ => Err(format!("Invalid sink parameter '{s}', expected KEY=VALUE")),
+    }
+}
+
+impl CtfSinkParams {
+    /// Override a parameter, the path is set by --output
+    pub fn set(&mut self, param: &SinkParam) -> Result<(), String> {
+        let flag = match param.key.as_str() {
+            "assume-single-trace" => &mut self.assume_single_trace,
+            "ignore-discarded-events" => &mut self.ignore_discarded_events,
+            "ignore-discarded-packets" => &mut self.ignore_discarded_packets,
+            "quiet" => &mut self.quiet,
+            "path" => return Err("The sink path is set with --output".to_owned()),
+            key => return Err(format!("Unknown {SINK_NODE_NAME} parameter '{key}'")),
+        };
+        *flag = Some(match param.value.as_str() {
+            "true" | "yes" => true,
+            "false" | "no" => false,
+            value => {
+                return Err(format!(
+                    "Invalid {SINK_NODE_NAME} parameter value '{value}' for '{}', expected true or false",
+                    param.key
+                ))
+            }
+        });
+        Ok(())
+    }
+
+    pub fn init_params(&self) -> Result<CtfPluginSinkFsInitParams, Error> {
+        CtfPluginSinkFsInitParams::new(
+            self.assume_single_trace,
+            self.ignore_discarded_events,
+            self.ignore_discarded_packets,
+            self.quiet,
+            &self.path_cstring,
+        )
+    }
+}
+
+/// The babeltrace component graph of the conversion, printed by --print-pipeline
+#[derive(Debug)]
+pub struct PipelineDescription<'a> {
+    pub log_level: LoggingLevel,
+    /// Graph node name of the source component, e.g. source.trace-recorder.output
+    pub source: &'a CStr,
+    pub sink: &'a CtfSinkParams,
+}
+
+impl fmt::Display for PipelineDescription<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = self.source.to_string_lossy();
+        writeln!(f, "Graph (log level {:?})", self.log_level)?;
+        writeln!(f, "Components:")?;
+        writeln!(f, "  {source}")?;
+        writeln!(f, "  {SINK_NODE_NAME}")?;
+        writeln!(f, "    path = {:?}", self.sink.path.display().to_string())?;
+        let flags = [
+            ("assume-single-trace", self.sink.assume_single_trace),
+            ("ignore-discarded-events", self.sink.ignore_discarded_events),
+            (
+                "ignore-discarded-packets",
+                self.sink.ignore_discarded_packets,
+            ),
+            ("quiet", self.sink.quiet),
+        ];
+        for (name, value) in flags {
+            match value {
+                Some(value) => writeln!(f, "    {name} = {value}")?,
+                None => writeln!(f, "    {name} = (default)")?,
+            }
+        }
+        writeln!(f, "Connections:")?;
+        write!(
+            f,
+            "  {source}:{SOURCE_OUTPUT_PORT} -> {SINK_NODE_NAME}:{SINK_INPUT_PORT}"
+        )
+    }
+}
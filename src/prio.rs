@@ -0,0 +1,54 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How trace recorder priorities are written to the prio fields
+///
+/// FreeRTOS priorities increase with importance, Linux priorities (and nice values)
+/// decrease with it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PrioMapping {
+    /// As recorded
+    #[default]
+    Raw,
+    /// Negated, more important sorts lower
+    Invert,
+    /// Recorded priority plus N
+    Offset(i64),
+}
+
+impl PrioMapping {
+    pub fn apply(&self, prio: i64) -> i64 {
+        match self {
+            PrioMapping::Raw => prio,
+            PrioMapping::Invert => -prio,
+            PrioMapping::Offset(n) => prio.saturating_add(*n),
+        }
+    }
+}
+
+impl FromStr for PrioMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(PrioMapping::Raw),
+            "invert" => Ok(PrioMapping::Invert),
+            _ => match s.strip_prefix("offset=").map(str::parse::<i64>) {
+                Some(Ok(n)) => Ok(PrioMapping::Offset(n)),
+                _ => Err(format!(
+                    "Invalid priority mapping '{s}', must be raw, invert or offset=N"
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for PrioMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrioMapping::Raw => f.write_str("raw"),
+            PrioMapping::Invert => f.write_str("invert"),
+            PrioMapping::Offset(n) => write!(f, "offset={n}"),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs,
+    io::{self, Read},
+    ops::RangeInclusive,
+    path::Path,
+};
+use trace_recorder_parser::streaming::Error;
+
+/// PSF start word (0x50534600) as written by little-endian targets
+const PSF_START_WORD_LE: [u8; 4] = [0x00, 0x46, 0x53, 0x50];
+
+/// PSF start word as written by big-endian targets
+const PSF_START_WORD_BE: [u8; 4] = [0x50, 0x53, 0x46, 0x00];
+
+/// Start markers of a snapshot-mode recorder data buffer
+const SNAPSHOT_START_MARKERS: [u8; 12] = [
+    0x01, 0x02, 0x03, 0x04, 0x71, 0x72, 0x73, 0x74, 0xF1, 0xF2, 0xF3, 0xF4,
+];
+
+/// Version of the trace-recorder-parser dependency, keep in sync with Cargo.toml
+pub const PARSER_VERSION: &str = "0.19";
+
+/// PSF format versions the parser dependency supports
+pub const SUPPORTED_FORMAT_VERSIONS: RangeInclusive<u16> = 10..=14;
+
+/// The --version output, keep in sync with PARSER_VERSION and SUPPORTED_FORMAT_VERSIONS
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ntrace-recorder-parser 0.19",
+    "\nsupported TRC format versions 10-14"
+);
+
+/// Rejects captures newer than the parser understands, rather than failing on
+/// (or misreading) the first event whose layout changed
+pub fn check_format_version(format_version: u16) -> Result<(), String> {
+    if format_version > *SUPPORTED_FORMAT_VERSIONS.end() {
+        return Err(format!(
+            "The trace format version {format_version} is newer than the supported versions \
+            ({}-{}, trace-recorder-parser {PARSER_VERSION}), the capture is likely from a newer \
+            trace recorder release",
+            SUPPORTED_FORMAT_VERSIONS.start(),
+            SUPPORTED_FORMAT_VERSIONS.end()
+        ));
+    }
+    Ok(())
+}
+
+/// Explain a failure to find the PSF header in the input with the likely cause
+pub fn header_error(path: &Path, e: Error) -> Box<dyn std::error::Error> {
+    // Reading a FIFO again would consume (or wait for) data that isn't the failed input
+    if is_fifo(path) {
+        return e.into();
+    }
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(_) => return e.into(),
+    };
+    format!(
+        "Failed to read the trace header from '{}' ({e}). {}",
+        path.display(),
+        diagnose(&data)
+    )
+    .into()
+}
+
+fn diagnose(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "The file is empty".to_owned();
+    }
+    if let Some(offset) = find(data, &SNAPSHOT_START_MARKERS) {
+        return format!(
+            "Found a snapshot-mode recorder buffer at byte offset {offset}, \
+            only streaming-mode (PSF) traces are supported"
+        );
+    }
+    let le = find(data, &PSF_START_WORD_LE).map(|o| (o, "little-endian"));
+    let be = find(data, &PSF_START_WORD_BE).map(|o| (o, "big-endian"));
+    match [le, be].into_iter().flatten().min() {
+        Some((0, endianness)) => format!(
+            "Found a {endianness} PSF start word at byte offset 0, the header is likely corrupt or \
+            from an unsupported trace recorder version"
+        ),
+        Some((offset, endianness)) => format!(
+            "Found a {endianness} PSF start word at byte offset {offset}, the capture likely has \
+            leading garbage (e.g. a boot log before the trace), try --seek-header or --skip-bytes {offset}"
+        ),
+        None => "No PSF start word found in either byte order, \
+            this is likely not a trace recorder streaming-mode trace"
+            .to_owned(),
+    }
+}
+
+/// Returns the offset of the first PSF start word, in either byte order
+pub fn seek_header<R: Read>(r: R) -> io::Result<Option<u64>> {
+    Ok(read_to_header(r)?.map(|(offset, _)| offset))
+}
+
+/// Reads up to the first PSF start word, in either byte order, without seeking.
+/// Returns its offset and the bytes read from it onwards, which precede the rest of the reader.
+pub fn read_to_header<R: Read>(mut r: R) -> io::Result<Option<(u64, Vec<u8>)>> {
+    let mut buf = vec![0; 64 * 1024];
+    // Carry the last bytes over so start words spanning reads are found
+    let carry = PSF_START_WORD_LE.len() - 1;
+    let mut len = 0;
+    let mut offset = 0_u64;
+    loop {
+        let n = r.read(&mut buf[len..])?;
+        if n == 0 {
+            return Ok(None);
+        }
+        len += n;
+        let window = &buf[..len];
+        let found = [
+            find(window, &PSF_START_WORD_LE),
+            find(window, &PSF_START_WORD_BE),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        if let Some(pos) = found {
+            return Ok(Some((offset + pos as u64, buf[pos..len].to_vec())));
+        }
+        if len > carry {
+            buf.copy_within(len - carry..len, 0);
+            offset += (len - carry) as u64;
+            len = carry;
+        }
+    }
+}
+
+/// Whether the input is a named pipe (FIFO), which can only be read once and can't seek
+#[cfg(unix)]
+pub fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    fs::metadata(path)
+        .map(|m| m.file_type().is_fifo())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+pub fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|w| w == pattern)
+}
@@ -0,0 +1,69 @@
+//! Conversion profiles, presets of the conversion options selected with --profile
+
+use crate::filter::Filter;
+use crate::Opts;
+use clap::ValueEnum;
+
+/// Leaves out the user and memory events
+const SCHEDULING_FILTER: &str =
+    r#"!(event == "USER_EVENT" || event == "MEMORY_ALLOC" || event == "MEMORY_FREE")"#;
+
+/// Keeps only the user and memory events
+const TELEMETRY_FILTER: &str =
+    r#"event == "USER_EVENT" || event == "MEMORY_ALLOC" || event == "MEMORY_FREE""#;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// The smallest output, user events without the redundant formatted string
+    Minimal,
+    /// Scheduling analysis: task states, lifecycle and ISR context events, in a stream
+    /// per context, without the user and memory events
+    Scheduling,
+    /// Every synthesized event and context field
+    Full,
+    /// User events with named arguments and the memory events, for charting
+    Telemetry,
+}
+
+impl Profile {
+    /// Turns on the options of the profile. A profile only turns options on, and its filter
+    /// only applies when no --filter is given
+    pub fn apply(self, opts: &mut Opts) -> Result<(), String> {
+        let filter = match self {
+            Profile::Minimal => {
+                opts.omit_formatted_string = true;
+                None
+            }
+            Profile::Scheduling => {
+                opts.task_state_events = true;
+                opts.task_newtask_events = true;
+                opts.process_lifecycle_events = true;
+                opts.softirq_events = true;
+                opts.irq_context = true;
+                opts.stream_per_isr = true;
+                Some(SCHEDULING_FILTER)
+            }
+            Profile::Full => {
+                opts.task_state_events = true;
+                opts.task_newtask_events = true;
+                opts.process_lifecycle_events = true;
+                opts.softirq_events = true;
+                opts.irq_context = true;
+                opts.stream_per_isr = true;
+                opts.named_user_args = true;
+                opts.handle_table_events = true;
+                opts.leak_candidate_events = true;
+                None
+            }
+            Profile::Telemetry => {
+                opts.named_user_args = true;
+                opts.omit_formatted_string = true;
+                Some(TELEMETRY_FILTER)
+            }
+        };
+        if let (Some(filter), None) = (filter, &opts.filter) {
+            opts.filter = Some(filter.parse::<Filter>()?);
+        }
+        Ok(())
+    }
+}
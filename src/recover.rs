@@ -0,0 +1,176 @@
+use std::io::{self, Cursor, Write};
+use std::ops::Range;
+use std::{fs::File, io::BufWriter, path::Path};
+use trace_recorder_parser::{
+    streaming::{Error, RecorderData},
+    time::Timestamp,
+};
+use tracing::{debug, info, warn};
+
+/// Number of consecutive events that must parse, with sequential event counters,
+/// for a position to be considered the start of a clean segment
+const RESYNC_EVENTS: usize = 16;
+
+/// Events are 32-bit aligned
+const ALIGNMENT: u64 = 4;
+
+/// A cleanly parseable range of events
+#[derive(Debug)]
+pub struct Segment {
+    pub bytes: Range<usize>,
+    pub events: u64,
+    /// Raw timestamps of the first and last events
+    pub timestamps: Option<(Timestamp, Timestamp)>,
+}
+
+impl Segment {
+    fn empty(offset: usize) -> Self {
+        Self {
+            bytes: offset..offset,
+            events: 0,
+            timestamps: None,
+        }
+    }
+}
+
+/// Scan a truncated or corrupted trace for its cleanly parseable segments of events.
+///
+/// Returns the header and segments stitched back together into a single trace, where the
+/// gaps between segments show up as jumps in the event counter (converted to discarded
+/// events), along with the segments found.
+pub fn recover(data: &[u8]) -> Result<(Vec<u8>, Vec<Segment>), Error> {
+    scan(data, ALIGNMENT)
+}
+
+/// Split a capture where the trace data is interleaved with other data (e.g. console logs
+/// sharing a UART) into the trace and the non-trace byte spans between its segments
+///
+/// The other data doesn't keep the events aligned, so this resynchronizes at any byte offset.
+pub fn demux(data: &[u8]) -> Result<(Vec<u8>, Vec<Segment>), Error> {
+    scan(data, 1)
+}
+
+fn scan(data: &[u8], alignment: u64) -> Result<(Vec<u8>, Vec<Segment>), Error> {
+    let mut cursor = Cursor::new(data);
+    let mut trd = RecorderData::find(&mut cursor)?;
+    let header_end = cursor.position() as usize;
+
+    let mut segments = Vec::new();
+    let mut segment = Segment::empty(header_end);
+    loop {
+        match trd.read_event(&mut cursor) {
+            Ok(Some((_, event))) => {
+                segment.events += 1;
+                segment.bytes.end = cursor.position() as usize;
+                let ts = event.timestamp();
+                segment.timestamps = Some(match segment.timestamps {
+                    Some((first, _)) => (first, ts),
+                    None => (ts, ts),
+                });
+            }
+            Ok(None) => break,
+            Err(e) => {
+                warn!(offset = segment.bytes.end, %e, "Data error, resynchronizing");
+                let resync_from = segment.bytes.end as u64 + alignment;
+                if segment.events != 0 {
+                    segments.push(segment);
+                }
+                match resync(&trd, data, resync_from, alignment) {
+                    Some(offset) => {
+                        debug!(offset, "Resynchronized");
+                        cursor.set_position(offset);
+                        segment = Segment::empty(offset as usize);
+                    }
+                    None => {
+                        segment = Segment::empty(data.len());
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    if segment.events != 0 {
+        segments.push(segment);
+    }
+
+    let mut recovered = data[..header_end].to_vec();
+    for s in segments.iter() {
+        info!(
+            start = s.bytes.start,
+            end = s.bytes.end,
+            events = s.events,
+            "Recovered segment"
+        );
+        recovered.extend_from_slice(&data[s.bytes.clone()]);
+    }
+
+    Ok((recovered, segments))
+}
+
+/// Find the next aligned offset where events parse cleanly again
+fn resync(trd: &RecorderData, data: &[u8], from: u64, alignment: u64) -> Option<u64> {
+    // Trial parses can pick up junk state (e.g. object names), keep it out of the real parser
+    let mut scratch = trd.clone();
+    let mut offset = from;
+    while offset < data.len() as u64 {
+        if parses_cleanly(&mut scratch, data, offset) {
+            return Some(offset);
+        }
+        offset += alignment;
+    }
+    None
+}
+
+fn parses_cleanly(trd: &mut RecorderData, data: &[u8], offset: u64) -> bool {
+    let mut cursor = Cursor::new(data);
+    cursor.set_position(offset);
+    let mut prev_count: Option<u16> = None;
+    for _ in 0..RESYNC_EVENTS {
+        let event_count = match trd.read_event(&mut cursor) {
+            Ok(Some((_, event))) => u16::from(event.event_count()),
+            // A clean end of the data counts too
+            Ok(None) => return prev_count.is_some(),
+            Err(_) => return false,
+        };
+        if let Some(prev) = prev_count {
+            if event_count != prev.wrapping_add(1) {
+                return false;
+            }
+        }
+        prev_count = Some(event_count);
+    }
+    true
+}
+
+/// Write the non-trace byte spans around the segments as text, each preceded by
+/// its byte range and the raw timestamps of the surrounding events
+pub fn write_passthrough(path: &Path, data: &[u8], segments: &[Segment]) -> io::Result<()> {
+    let mut cursor = Cursor::new(data);
+    RecorderData::find(&mut cursor).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut gap_start = cursor.position() as usize;
+    let mut prev_timestamps: Option<(Timestamp, Timestamp)> = None;
+    let mut w = BufWriter::new(File::create(path)?);
+    for s in segments.iter().map(Some).chain(std::iter::once(None)) {
+        let gap = gap_start..s.map(|s| s.bytes.start).unwrap_or(data.len());
+        if !gap.is_empty() {
+            let after = prev_timestamps.map(|(_, last)| last.ticks().to_string());
+            let before = s
+                .and_then(|s| s.timestamps)
+                .map(|(first, _)| first.ticks().to_string());
+            writeln!(
+                w,
+                "--- bytes {}..{}, after timer {}, before timer {}",
+                gap.start,
+                gap.end,
+                after.as_deref().unwrap_or("-"),
+                before.as_deref().unwrap_or("-"),
+            )?;
+            writeln!(w, "{}", String::from_utf8_lossy(&data[gap]))?;
+        }
+        if let Some(s) = s {
+            gap_start = s.bytes.end;
+            prev_timestamps = s.timestamps;
+        }
+    }
+    w.flush()
+}
@@ -0,0 +1,51 @@
+use crate::interruptor::Interruptor;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use trace_recorder_parser::time::{Frequency, Timestamp};
+
+/// Longest single sleep, keeps Ctrl-C responsive through long gaps between events
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => Err(format!("Invalid speed '{s}', must be a positive number")),
+    }
+}
+
+/// Paces the conversion to the trace's original event timing
+#[derive(Debug)]
+pub struct Replay {
+    speed: f64,
+    timer_frequency: Frequency,
+    /// Wall-clock instant and trace time (nanoseconds) of the first event
+    start: Option<(Instant, u64)>,
+}
+
+impl Replay {
+    pub fn new(speed: f64, timer_frequency: Frequency) -> Self {
+        Self {
+            speed,
+            timer_frequency,
+            start: None,
+        }
+    }
+
+    /// Sleep until the event at the given timestamp is due, relative to the first event
+    pub fn wait(&mut self, timestamp: Timestamp, interruptor: &Interruptor) {
+        let freq = u128::from(self.timer_frequency.get_raw()).max(1);
+        let time_ns = (u128::from(timestamp.ticks()) * 1_000_000_000 / freq) as u64;
+        let (started_at, start_ns) = *self.start.get_or_insert((Instant::now(), time_ns));
+        let offset = Duration::from_nanos(time_ns.saturating_sub(start_ns)).div_f64(self.speed);
+        let due = started_at + offset;
+        while !interruptor.is_set() {
+            let now = Instant::now();
+            if now >= due {
+                break;
+            }
+            thread::sleep((due - now).min(MAX_SLEEP));
+        }
+    }
+}
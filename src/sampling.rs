@@ -0,0 +1,81 @@
+use crate::event_fields::{event_field, event_name, FieldValue};
+use std::collections::HashMap;
+use std::str::FromStr;
+use trace_recorder_parser::streaming::event::{Event, EventType};
+
+/// Emit every Nth occurrence of an event type, `<EVENT_TYPE>=N`
+///
+/// User events can be sampled per channel with `USER_EVENT:<channel>=N`.
+#[derive(Clone, Debug)]
+pub struct SampleRate {
+    pub event: String,
+    pub every: u64,
+}
+
+impl FromStr for SampleRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (event, every) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected <EVENT_TYPE>=N, got '{s}'"))?;
+        let every: u64 = every
+            .parse()
+            .map_err(|_| format!("Invalid sample rate '{every}'"))?;
+        if event.is_empty() || every == 0 {
+            return Err(format!("Expected <EVENT_TYPE>=N with N > 0, got '{s}'"));
+        }
+        Ok(Self {
+            event: event.to_owned(),
+            every,
+        })
+    }
+}
+
+/// Down-samples the configured event types
+#[derive(Debug, Default)]
+pub struct Sampler {
+    /// Sample rate and occurrences since the last emitted event, by event key
+    rates: HashMap<String, (u64, u64)>,
+}
+
+impl Sampler {
+    pub fn new(rates: &[SampleRate]) -> Self {
+        Self {
+            rates: rates
+                .iter()
+                .map(|r| (r.event.clone(), (r.every, 0)))
+                .collect(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.rates.is_empty()
+    }
+
+    /// Returns the number of occurrences the event stands for when it should be emitted,
+    /// or None when it's sampled out
+    pub fn sample(&mut self, event_type: EventType, event: &Event) -> Option<u64> {
+        let name = event_name(event_type, event);
+        let channel_key = match event_field(event, "channel") {
+            Some(FieldValue::Str(channel)) => Some(format!("{name}:{channel}")),
+            _ => None,
+        };
+        let key = channel_key
+            .filter(|k| self.rates.contains_key(k))
+            .unwrap_or_else(|| name.into_owned());
+        match self.rates.get_mut(&key) {
+            None => Some(1),
+            Some((every, seen)) => {
+                *seen += 1;
+                if *seen >= *every {
+                    let weight = *seen;
+                    *seen = 0;
+                    Some(weight)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
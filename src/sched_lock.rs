@@ -0,0 +1,45 @@
+use crate::types::Interval;
+use trace_recorder_parser::{time::Timestamp, types::ObjectName};
+
+/// Pairs the (nestable) scheduler suspend and resume calls
+#[derive(Debug, Default)]
+pub struct SchedulerLockTracker {
+    depth: u32,
+    /// Outermost suspend
+    start: Option<(Timestamp, ObjectName)>,
+    /// Scheduler-locked interval of the task that suspended the scheduler, from the outermost
+    /// SCHEDULER_SUSPEND to the matching SCHEDULER_RESUME
+    finished: Option<Interval>,
+}
+
+impl SchedulerLockTracker {
+    /// Returns the nesting depth
+    pub fn suspend(&mut self, timestamp: Timestamp, task: &ObjectName) -> u32 {
+        if self.start.is_none() {
+            self.start = Some((timestamp, task.clone()));
+        }
+        self.depth += 1;
+        self.depth
+    }
+
+    /// Returns the nesting depth left and the outermost suspend's timestamp,
+    /// None for a resume without a suspend (e.g. suspended before the trace started)
+    pub fn resume(&mut self, timestamp: Timestamp) -> Option<(u32, Timestamp)> {
+        let start = self.start.as_ref()?.0;
+        self.depth -= 1;
+        if self.depth == 0 {
+            let (start, task) = self.start.take()?;
+            self.finished = Some(Interval {
+                name: task,
+                start,
+                end: timestamp,
+            });
+        }
+        Some((self.depth, start))
+    }
+
+    /// The last scheduler-locked interval, once it ends
+    pub fn take_finished(&mut self) -> Option<Interval> {
+        self.finished.take()
+    }
+}
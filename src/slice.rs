@@ -0,0 +1,113 @@
+use std::io::Cursor;
+use std::ops::Range;
+use std::time::Duration;
+use trace_recorder_parser::streaming::{Error, RecorderData};
+use trace_recorder_to_ctf::trackers::TimestampStrategy;
+use tracing::warn;
+
+/// Parses a duration like 500ms, 60s, 10m or 1h (seconds when there's no unit)
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value.parse::<u64>().ok();
+    let duration = match (value, unit) {
+        (Some(v), "ms") => Duration::from_millis(v),
+        (Some(v), "" | "s") => Duration::from_secs(v),
+        (Some(v), "m") => Duration::from_secs(v * 60),
+        (Some(v), "h") => Duration::from_secs(v * 60 * 60),
+        _ => {
+            return Err(format!(
+                "Invalid duration '{s}', e.g. 500ms, 60s, 10m or 1h"
+            ))
+        }
+    };
+    if duration.is_zero() {
+        return Err(format!("Invalid duration '{s}', must be non-zero"));
+    }
+    Ok(duration)
+}
+
+/// The events of one time window, as a trace of its own
+#[derive(Debug)]
+pub struct Slice {
+    /// The header followed by the window's events
+    pub data: Vec<u8>,
+    pub events: u64,
+    /// Tracked time of the slice's first event in the whole trace, minus the time it gets
+    /// when the slice is converted on its own (the timer rollovers before the slice)
+    pub offset_ns: i64,
+}
+
+/// Split the trace into slices of consecutive events, one per time window from the first event.
+/// Windows without events don't get a slice, and splitting stops at the first data error.
+pub fn split(
+    data: &[u8],
+    every: Duration,
+    strategy: TimestampStrategy,
+) -> Result<Vec<Slice>, Error> {
+    let mut cursor = Cursor::new(data);
+    let mut trd = RecorderData::find(&mut cursor)?;
+    let header = &data[..cursor.position() as usize];
+    let freq = u128::from(trd.timestamp_info.timer_frequency.get_raw()).max(1);
+    let every_ticks = ((every.as_nanos() * freq / 1_000_000_000) as u64).max(1);
+    let wraparounds = trd.timestamp_info.timer_wraparounds;
+
+    let mut tracker = strategy.tracker();
+    let mut first_ticks = None;
+    // Window index, byte range, event count and offset of the slice being built
+    let mut current: Option<(u64, Range<usize>, u64, i64)> = None;
+    let mut slices = Vec::new();
+    let mut finish = |(_, bytes, events, offset_ns): (u64, Range<usize>, u64, i64)| {
+        let mut slice_data = header.to_vec();
+        slice_data.extend_from_slice(&data[bytes]);
+        slices.push(Slice {
+            data: slice_data,
+            events,
+            offset_ns,
+        });
+    };
+    loop {
+        let start = cursor.position() as usize;
+        let event = match trd.read_event(&mut cursor) {
+            Ok(Some((_, event))) => event,
+            Ok(None) => break,
+            Err(e) => {
+                warn!(offset = start, %e, "Data error, the remaining events aren't sliced");
+                break;
+            }
+        };
+        let end = cursor.position() as usize;
+        let raw = event.timestamp();
+        let ticks = match first_ticks {
+            Some(_) => tracker.elapsed(raw).ticks(),
+            None => {
+                tracker.init(raw, wraparounds);
+                let ticks = tracker.elapsed(raw).ticks();
+                first_ticks = Some(ticks);
+                ticks
+            }
+        };
+        let window = ticks.saturating_sub(first_ticks.unwrap_or(ticks)) / every_ticks;
+        match current.as_mut() {
+            Some((w, bytes, events, _)) if *w == window => {
+                bytes.end = end;
+                *events += 1;
+            }
+            _ => {
+                // The time the slice's first event gets from a tracker starting at it
+                let mut slice_tracker = strategy.tracker();
+                slice_tracker.init(raw, wraparounds);
+                let slice_ticks = slice_tracker.elapsed(raw).ticks();
+                let offset_ticks = i128::from(ticks) - i128::from(slice_ticks);
+                let offset_ns = (offset_ticks * 1_000_000_000 / freq as i128) as i64;
+                if let Some(slice) = current.replace((window, start..end, 1, offset_ns)) {
+                    finish(slice);
+                }
+            }
+        }
+    }
+    if let Some(slice) = current.take() {
+        finish(slice);
+    }
+    Ok(slices)
+}
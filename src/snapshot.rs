@@ -0,0 +1,82 @@
+use crate::types::Context;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
+use trace_recorder_parser::streaming::event::EventCode;
+
+/// Number of event codes kept for the snapshot
+const RECENT_EVENTS: usize = 32;
+
+/// The last event codes processed
+#[derive(Debug, Default)]
+pub struct RecentEvents(VecDeque<EventCode>);
+
+impl RecentEvents {
+    pub fn push(&mut self, event_code: EventCode) {
+        if self.0.len() == RECENT_EVENTS {
+            self.0.pop_front();
+        }
+        self.0.push_back(event_code);
+    }
+
+    pub fn snapshot(&self) -> Vec<EventCodeSnapshot> {
+        self.0
+            .iter()
+            .map(|c| EventCodeSnapshot {
+                event_id: c.event_id().0,
+                event_type: c.event_type().to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Converter state at the time of a fatal error, to attach to bug reports.
+/// Task and ISR names are left out, only their handles and priorities are kept.
+#[derive(Debug, Serialize)]
+pub struct StateSnapshot {
+    pub error: String,
+    pub events: u64,
+    /// Tracked (rollover-extended) event counter and the raw counter of the last event
+    pub event_count: u64,
+    pub last_event_count: u16,
+    /// Tracked timestamp of the last converted event
+    pub timestamp: u64,
+    pub active_context: ContextSnapshot,
+    /// Pending ISRs, innermost last
+    pub isr_stack: Vec<ContextSnapshot>,
+    /// Oldest first
+    pub recent_events: Vec<EventCodeSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContextSnapshot {
+    pub handle: u32,
+    pub priority: u32,
+}
+
+impl From<&Context> for ContextSnapshot {
+    fn from(value: &Context) -> Self {
+        Self {
+            handle: value.handle.into(),
+            priority: value.priority.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventCodeSnapshot {
+    pub event_id: u16,
+    pub event_type: String,
+}
+
+impl StateSnapshot {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use trace_recorder_parser::types::ObjectHandle;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SoftIrq {
+    Raise(u64),
+    Entry(u64),
+    Exit(u64),
+}
+
+/// Detects ISRs deferring their work to a handler task
+///
+/// An ISR waking up a task that gets switched in as soon as the ISR ends is modeled
+/// as a softirq: raised by the wakeup, entered on the switch to the handler task and
+/// exited once the handler task gets switched out. The softirq vector is the ISR's handle.
+#[derive(Debug, Default)]
+pub struct SoftIrqTracker {
+    /// Handler task woken by an ISR and the ISR's handle
+    raised: Option<(ObjectHandle, u64)>,
+    /// Handler task running the deferred work
+    active: Option<(ObjectHandle, u64)>,
+}
+
+impl SoftIrqTracker {
+    /// A task was woken up from ISR context
+    pub fn woken_from_isr(&mut self, task: ObjectHandle, isr: ObjectHandle) -> SoftIrq {
+        let vec = u32::from(isr).into();
+        self.raised = Some((task, vec));
+        SoftIrq::Raise(vec)
+    }
+
+    /// A task was switched in
+    pub fn switched(&mut self, next: ObjectHandle) -> Vec<SoftIrq> {
+        let mut softirqs = Vec::new();
+        if let Some((task, vec)) = self.active {
+            if task != next {
+                self.active = None;
+                softirqs.push(SoftIrq::Exit(vec));
+            }
+        }
+        // Only an immediate switch to the woken task counts as deferred work
+        if let Some((task, vec)) = self.raised.take() {
+            if task == next && self.active.is_none() {
+                self.active = Some((task, vec));
+                softirqs.push(SoftIrq::Entry(vec));
+            }
+        }
+        softirqs
+    }
+}
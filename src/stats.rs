@@ -0,0 +1,426 @@
+use crate::counters::ThroughputCounters;
+use crate::task_groups::TaskGroups;
+use crate::types::Interval;
+use crate::Opts;
+use babeltrace2_sys::ffi;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CStr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering::Relaxed},
+    Arc, Mutex,
+};
+use std::{
+    fs::File,
+    io::{self, Write},
+    mem::size_of,
+    path::Path,
+    time::Duration,
+};
+use trace_recorder_parser::{streaming::event::EventType, time::Timestamp};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+pub type SharedStats = Arc<Mutex<ConversionStats>>;
+
+/// Statistics gathered during conversion
+#[derive(Clone, Debug, Default)]
+pub struct ConversionStats {
+    pub counters: ThroughputCounters,
+    /// Size of the input in bytes, if known
+    pub input_size: Option<u64>,
+    pub timer_frequency: u32,
+    /// Timer frequency observed from the OS tick rate, if enough tick-referenced wake-ups
+    /// were found
+    pub observed_timer_frequency: Option<u64>,
+    /// Last (rollover-tracked) timestamp observed
+    pub last_timestamp: Timestamp,
+    pub events_by_type: HashMap<EventType, u64>,
+    /// Number of times each task was switched in
+    pub task_activations: HashMap<String, u64>,
+    /// Ticks each task was switched in for
+    pub task_running_ticks: HashMap<String, u64>,
+    /// Currently switched in task and when
+    running_task: Option<(String, Timestamp)>,
+    pub task_groups: TaskGroups,
+    /// Emitted events and estimated CTF bytes per event class
+    pub event_class_sizes: HashMap<String, EventClassSize>,
+    /// MEMORY_ALLOC events per power-of-two size bucket, keyed by the bucket's smallest size
+    pub alloc_sizes: BTreeMap<u32, u64>,
+    pub malloc_failures: u64,
+    /// Number of the longest ISR executions and scheduler-locked intervals kept
+    pub worst_offenders: usize,
+    /// Longest ISR executions, longest first
+    pub longest_isrs: Vec<Interval>,
+    /// Longest scheduler-locked intervals, longest first
+    pub longest_scheduler_locks: Vec<Interval>,
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+pub struct EventClassSize {
+    pub events: u64,
+    pub bytes: u64,
+}
+
+/// Event header (id and timestamp) bytes in the CTF output
+const EVENT_HEADER_SIZE: u64 = 16;
+
+/// Integer and enumeration field bytes in the CTF output
+const INTEGER_FIELD_SIZE: u64 = 8;
+
+impl ConversionStats {
+    /// Estimated heap bytes of the per-task and per-type tables
+    pub fn estimated_size(&self) -> usize {
+        let per_task = |m: &HashMap<String, u64>| {
+            m.keys()
+                .map(|k| size_of::<(String, u64)>() + k.len())
+                .sum::<usize>()
+        };
+        per_task(&self.task_activations)
+            + per_task(&self.task_running_ticks)
+            + self.events_by_type.len() * size_of::<(EventType, u64)>()
+            + self
+                .event_class_sizes
+                .keys()
+                .map(|k| size_of::<(String, EventClassSize)>() + k.len())
+                .sum::<usize>()
+            + self.alloc_sizes.len() * size_of::<(u32, u64)>()
+            + (self.longest_isrs.capacity() + self.longest_scheduler_locks.capacity())
+                * size_of::<Interval>()
+    }
+
+    /// Duration of the trace converted so far, in seconds
+    pub fn trace_duration_secs(&self) -> f64 {
+        if self.timer_frequency == 0 {
+            0.0
+        } else {
+            self.last_timestamp.ticks() as f64 / f64::from(self.timer_frequency)
+        }
+    }
+
+    /// Account the switched in time of the previous task
+    pub fn task_switched(&mut self, task: &str, timestamp: Timestamp) {
+        if let Some((prev, start)) = self.running_task.take() {
+            *self.task_running_ticks.entry(prev).or_default() +=
+                timestamp.ticks().saturating_sub(start.ticks());
+        }
+        self.running_task = Some((task.to_owned(), timestamp));
+    }
+
+    /// Activations and share of the trace duration the tasks of each group were switched in for
+    pub fn task_group_summaries(&self) -> Vec<TaskGroupSummary> {
+        let mut summaries: Vec<TaskGroupSummary> = Vec::new();
+        for group in self.task_groups.groups() {
+            if summaries.iter().all(|s| s.group != group) {
+                summaries.push(TaskGroupSummary {
+                    group: group.to_owned(),
+                    tasks: 0,
+                    activations: 0,
+                    running_ns: 0,
+                    cpu_share: 0.0,
+                });
+            }
+        }
+        let running = self.running_task.as_ref().map(|(task, start)| {
+            (
+                task.as_str(),
+                self.last_timestamp.ticks().saturating_sub(start.ticks()),
+            )
+        });
+        for (task, activations) in self.task_activations.iter() {
+            let summary = match self
+                .task_groups
+                .group(task)
+                .and_then(|g| summaries.iter_mut().find(|s| s.group == g))
+            {
+                Some(summary) => summary,
+                None => continue,
+            };
+            let mut ticks = self.task_running_ticks.get(task).copied().unwrap_or(0);
+            if let Some((_, open_ticks)) = running.filter(|(t, _)| t == task) {
+                ticks += open_ticks;
+            }
+            summary.tasks += 1;
+            summary.activations += activations;
+            summary.running_ns += self.ticks_to_ns(ticks);
+        }
+        let duration_ns = self.ticks_to_ns(self.last_timestamp.ticks());
+        for summary in summaries.iter_mut() {
+            if duration_ns != 0 {
+                summary.cpu_share = summary.running_ns as f64 / duration_ns as f64;
+            }
+        }
+        summaries
+    }
+
+    pub fn ticks_to_ns(&self, ticks: u64) -> u64 {
+        let freq = u128::from(self.timer_frequency).max(1);
+        (u128::from(ticks) * 1_000_000_000 / freq) as u64
+    }
+
+    pub fn add_isr_execution(&mut self, isr: Interval) {
+        add_longest(&mut self.longest_isrs, isr, self.worst_offenders);
+    }
+
+    pub fn add_scheduler_lock(&mut self, lock: Interval) {
+        add_longest(
+            &mut self.longest_scheduler_locks,
+            lock,
+            self.worst_offenders,
+        );
+    }
+
+    /// Longest ISR executions and scheduler-locked intervals
+    pub fn worst_offenders(&self) -> WorstOffenders {
+        let summaries = |intervals: &[Interval]| {
+            intervals
+                .iter()
+                .map(|i| IntervalSummary {
+                    name: i.name.to_string(),
+                    start_ns: self.ticks_to_ns(i.start.ticks()),
+                    duration_ns: self.ticks_to_ns(i.duration_ticks()),
+                })
+                .collect()
+        };
+        WorstOffenders {
+            longest_isrs: summaries(&self.longest_isrs),
+            longest_scheduler_locks: summaries(&self.longest_scheduler_locks),
+        }
+    }
+
+    pub fn add_alloc_size(&mut self, size: u32) {
+        let bucket = match size {
+            0 => 0,
+            _ => 1 << (31 - size.leading_zeros()),
+        };
+        *self.alloc_sizes.entry(bucket).or_default() += 1;
+    }
+
+    /// Allocation size buckets, smallest first
+    pub fn alloc_size_histogram(&self) -> Vec<AllocSizeBucket> {
+        self.alloc_sizes
+            .iter()
+            .map(|(min_size, count)| AllocSizeBucket {
+                min_size: *min_size,
+                max_size: match min_size {
+                    0 => 0,
+                    _ => min_size | (min_size - 1),
+                },
+                count: *count,
+            })
+            .collect()
+    }
+
+    /// Account the estimated CTF size of the event messages
+    pub fn add_event_messages(&mut self, msgs: &[*const ffi::bt_message]) {
+        for msg in msgs.iter().copied() {
+            if unsafe { ffi::bt_message_get_type(msg) }
+                != ffi::bt_message_type::BT_MESSAGE_TYPE_EVENT
+            {
+                continue;
+            }
+            let (name, bytes) = unsafe { event_size(msg) };
+            let size = match self.event_class_sizes.get_mut(name) {
+                Some(size) => size,
+                None => self.event_class_sizes.entry(name.to_owned()).or_default(),
+            };
+            size.events += 1;
+            size.bytes += bytes;
+        }
+    }
+
+    /// Event classes by estimated CTF bytes, largest first
+    pub fn largest_event_classes(&self, n: usize) -> Vec<(&str, EventClassSize)> {
+        let mut sizes: Vec<_> = self
+            .event_class_sizes
+            .iter()
+            .map(|(name, size)| (name.as_str(), *size))
+            .collect();
+        sizes.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(a.0.cmp(b.0)));
+        sizes.truncate(n);
+        sizes
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct AllocSizeBucket {
+    pub min_size: u32,
+    pub max_size: u32,
+    pub count: u64,
+}
+
+/// Insert the interval into the intervals (longest first), keeping at most max
+fn add_longest(intervals: &mut Vec<Interval>, interval: Interval, max: usize) {
+    let idx = intervals.partition_point(|i| i.duration_ticks() >= interval.duration_ticks());
+    if idx < max {
+        intervals.insert(idx, interval);
+        intervals.truncate(max);
+    }
+}
+
+/// Event class name and estimated CTF size of an event message
+///
+/// Integers are counted at their full 64 bits, the CTF sink may pack them smaller.
+unsafe fn event_size<'a>(msg: *const ffi::bt_message) -> (&'a str, u64) {
+    let event = ffi::bt_message_event_borrow_event_const(msg);
+    let event_class = ffi::bt_event_borrow_class_const(event);
+    let name = CStr::from_ptr(ffi::bt_event_class_get_name(event_class))
+        .to_str()
+        .unwrap_or("<invalid>");
+    let bytes = EVENT_HEADER_SIZE
+        + struct_size(ffi::bt_event_borrow_common_context_field_const(event))
+        + struct_size(ffi::bt_event_borrow_payload_field_const(event));
+    (name, bytes)
+}
+
+unsafe fn struct_size(field: *const ffi::bt_field) -> u64 {
+    if field.is_null() {
+        return 0;
+    }
+    let members =
+        ffi::bt_field_class_structure_get_member_count(ffi::bt_field_borrow_class_const(field));
+    (0..members)
+        .map(|i| {
+            let member = ffi::bt_field_structure_borrow_member_field_by_index_const(field, i);
+            if ffi::bt_field_get_class_type(member)
+                == ffi::bt_field_class_type::BT_FIELD_CLASS_TYPE_STRING
+            {
+                // Null terminated
+                ffi::bt_field_string_get_length(member) + 1
+            } else {
+                INTEGER_FIELD_SIZE
+            }
+        })
+        .sum()
+}
+
+/// Number of event classes reported as the largest CTF size contributors
+pub const LARGEST_EVENT_CLASSES: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct TaskGroupSummary {
+    pub group: String,
+    /// Tasks seen in the group
+    pub tasks: u64,
+    pub activations: u64,
+    /// Time the group's tasks were switched in for (including the ISRs interrupting them)
+    pub running_ns: u64,
+    /// running_ns share of the trace duration
+    pub cpu_share: f64,
+}
+
+/// Longest ISR executions and scheduler-locked intervals, longest first,
+/// where to start looking for latency culprits
+#[derive(Debug, Serialize)]
+pub struct WorstOffenders {
+    pub longest_isrs: Vec<IntervalSummary>,
+    pub longest_scheduler_locks: Vec<IntervalSummary>,
+}
+
+/// A reported interval, timestamps relative to the trace clock
+#[derive(Debug, Serialize)]
+pub struct IntervalSummary {
+    /// Task (or ISR) name
+    pub name: String,
+    pub start_ns: u64,
+    pub duration_ns: u64,
+}
+
+/// Machine-readable summary of a conversion run, written as a single JSON line
+#[derive(Debug, Serialize)]
+pub struct ConversionSummary {
+    pub success: bool,
+    pub error: Option<String>,
+    pub input: Option<String>,
+    pub output: String,
+    pub events: u64,
+    pub bytes: u64,
+    pub dropped_events: u64,
+    pub duplicate_events: u64,
+    pub warnings: u64,
+    pub trace_duration_secs: f64,
+    pub timer_frequency: u32,
+    /// Timer frequency observed from the OS tick rate
+    pub observed_timer_frequency: Option<u64>,
+    pub elapsed_secs: f64,
+    /// Event classes contributing the most to the CTF size
+    pub largest_event_classes: Vec<EventClassSummary>,
+    pub malloc_failures: u64,
+    /// MEMORY_ALLOC events per power-of-two size bucket
+    pub alloc_size_histogram: Vec<AllocSizeBucket>,
+    pub worst_offenders: WorstOffenders,
+    /// Per task group (see the task_groups config) activations and CPU share
+    pub task_groups: Vec<TaskGroupSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventClassSummary {
+    pub name: String,
+    pub events: u64,
+    /// Estimated CTF bytes
+    pub bytes: u64,
+}
+
+impl ConversionSummary {
+    pub fn new(
+        opts: &Opts,
+        stats: &ConversionStats,
+        warnings: u64,
+        elapsed: Duration,
+        error: Option<&dyn std::error::Error>,
+    ) -> Self {
+        Self {
+            success: error.is_none(),
+            error: error.map(|e| e.to_string()),
+            input: opts.input.as_ref().map(|p| p.display().to_string()),
+            output: opts.output.display().to_string(),
+            events: stats.counters.events,
+            bytes: stats.counters.bytes,
+            dropped_events: stats.counters.dropped_events,
+            duplicate_events: stats.counters.duplicate_events,
+            warnings,
+            trace_duration_secs: stats.trace_duration_secs(),
+            timer_frequency: stats.timer_frequency,
+            observed_timer_frequency: stats.observed_timer_frequency,
+            elapsed_secs: elapsed.as_secs_f64(),
+            largest_event_classes: stats
+                .largest_event_classes(LARGEST_EVENT_CLASSES)
+                .into_iter()
+                .map(|(name, size)| EventClassSummary {
+                    name: name.to_owned(),
+                    events: size.events,
+                    bytes: size.bytes,
+                })
+                .collect(),
+            malloc_failures: stats.malloc_failures,
+            alloc_size_histogram: stats.alloc_size_histogram(),
+            worst_offenders: stats.worst_offenders(),
+            task_groups: stats.task_group_summaries(),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut f = File::create(path)?;
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        f.write_all(&line)
+    }
+}
+
+/// A tracing layer that counts warnings, regardless of the log filter
+#[derive(Clone, Debug, Default)]
+pub struct WarningCounter(Arc<AtomicU64>);
+
+impl WarningCounter {
+    pub fn count(&self) -> u64 {
+        self.0.load(Relaxed)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for WarningCounter {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() == Level::WARN {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::{fs, path::Path};
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+#[derive(Debug)]
+struct Symbol {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+/// Function symbols of an ELF file, for symbolicating addresses recorded in the trace
+#[derive(Debug, Default)]
+pub struct Symbols {
+    /// Sorted by address
+    symbols: Vec<Symbol>,
+}
+
+impl Symbols {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read(path)?;
+        let elf = Elf::new(&data)
+            .ok_or_else(|| format!("Invalid ELF file '{}'. Bad header", path.display()))?;
+        let mut symbols = elf
+            .function_symbols()
+            .ok_or_else(|| format!("Invalid ELF file '{}'. Bad symbol table", path.display()))?;
+        if symbols.is_empty() {
+            return Err(format!(
+                "Invalid ELF file '{}'. No function symbols (stripped?)",
+                path.display()
+            )
+            .into());
+        }
+        symbols.sort_by_key(|s| s.address);
+        Ok(Self { symbols })
+    }
+
+    /// Name of the function containing the address
+    pub fn lookup(&self, address: u64) -> Option<&str> {
+        // Clear the Thumb bit of return addresses
+        let address = address & !1;
+        let idx = self.symbols.partition_point(|s| s.address <= address);
+        let symbol = self.symbols.get(idx.checked_sub(1)?)?;
+        (address < symbol.address + symbol.size.max(1)).then_some(symbol.name.as_str())
+    }
+}
+
+/// Just enough of an ELF32/ELF64 reader to get at the symbol table
+struct Elf<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+}
+
+impl<'a> Elf<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        if data.get(..4)? != b"\x7fELF" {
+            return None;
+        }
+        let is_64 = match data.get(4)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let is_le = match data.get(5)? {
+            1 => true,
+            2 => false,
+            _ => return None,
+        };
+        Some(Self { data, is_64, is_le })
+    }
+
+    fn bytes<const N: usize>(&self, offset: u64) -> Option<[u8; N]> {
+        let start = usize::try_from(offset).ok()?;
+        self.data.get(start..start.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u8(&self, offset: u64) -> Option<u8> {
+        Some(self.bytes::<1>(offset)?[0])
+    }
+
+    fn u16(&self, offset: u64) -> Option<u16> {
+        let b = self.bytes(offset)?;
+        Some(if self.is_le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Option<u32> {
+        let b = self.bytes(offset)?;
+        Some(if self.is_le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Option<u64> {
+        let b = self.bytes(offset)?;
+        Some(if self.is_le {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    /// Address sized word
+    fn word(&self, offset: u64) -> Option<u64> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    /// Offset, size and linked section index of each section
+    fn sections(&self) -> Option<Vec<(u32, u64, u64, u32)>> {
+        let (shoff, shentsize, shnum) = if self.is_64 {
+            (self.u64(0x28)?, self.u16(0x3A)?, self.u16(0x3C)?)
+        } else {
+            (u64::from(self.u32(0x20)?), self.u16(0x2E)?, self.u16(0x30)?)
+        };
+        (0..u64::from(shnum))
+            .map(|i| {
+                let sh = shoff + i * u64::from(shentsize);
+                if self.is_64 {
+                    Some((
+                        self.u32(sh + 0x4)?,
+                        self.u64(sh + 0x18)?,
+                        self.u64(sh + 0x20)?,
+                        self.u32(sh + 0x28)?,
+                    ))
+                } else {
+                    Some((
+                        self.u32(sh + 0x4)?,
+                        u64::from(self.u32(sh + 0x10)?),
+                        u64::from(self.u32(sh + 0x14)?),
+                        self.u32(sh + 0x18)?,
+                    ))
+                }
+            })
+            .collect()
+    }
+
+    fn str(&self, offset: u64) -> Option<&'a str> {
+        let start = usize::try_from(offset).ok()?;
+        let data = self.data.get(start..)?;
+        let len = data.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&data[..len]).ok()
+    }
+
+    fn function_symbols(&self) -> Option<Vec<Symbol>> {
+        let sections = self.sections()?;
+        let mut symbols = Vec::new();
+        for (sh_type, offset, size, link) in sections.iter().copied() {
+            if sh_type != SHT_SYMTAB {
+                continue;
+            }
+            let strtab = sections.get(usize::try_from(link).ok()?)?.1;
+            let entsize = if self.is_64 { 24 } else { 16 };
+            for i in 0..size / entsize {
+                let sym = offset + i * entsize;
+                let (name, info, value, size) = if self.is_64 {
+                    (
+                        self.u32(sym)?,
+                        self.u8(sym + 0x4)?,
+                        self.word(sym + 0x8)?,
+                        self.word(sym + 0x10)?,
+                    )
+                } else {
+                    (
+                        self.u32(sym)?,
+                        self.u8(sym + 0xC)?,
+                        self.word(sym + 0x4)?,
+                        self.word(sym + 0x8)?,
+                    )
+                };
+                if info & 0xF != STT_FUNC || value == 0 {
+                    continue;
+                }
+                let name = self.str(strtab + u64::from(name))?;
+                symbols.push(Symbol {
+                    // Clear the Thumb bit
+                    address: value & !1,
+                    size,
+                    name: name.to_owned(),
+                });
+            }
+        }
+        Some(symbols)
+    }
+}
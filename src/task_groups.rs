@@ -0,0 +1,40 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/// Groups the tasks whose name matches the pattern under a label, e.g. a subsystem
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskGroupRule {
+    pub group: String,
+    /// Regex matched against the task name
+    pub pattern: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TaskGroups {
+    rules: Vec<(String, Regex)>,
+}
+
+impl TaskGroups {
+    pub fn new(rules: &[TaskGroupRule]) -> Result<Self, regex::Error> {
+        Ok(Self {
+            rules: rules
+                .iter()
+                .map(|r| Ok((r.group.clone(), Regex::new(&r.pattern)?)))
+                .collect::<Result<_, regex::Error>>()?,
+        })
+    }
+
+    /// Group labels, in the config order
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|(group, _)| group.as_str())
+    }
+
+    /// Group of the task, the first matching rule wins
+    pub fn group(&self, task: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(_, pattern)| pattern.is_match(task))
+            .map(|(group, _)| group.as_str())
+    }
+}
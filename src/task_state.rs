@@ -0,0 +1,252 @@
+use crate::events::TaskLifecycleState;
+use crate::task_groups::TaskGroups;
+use crate::types::Context;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::{fs::File, io::BufWriter, mem::size_of, path::Path};
+use trace_recorder_parser::{
+    streaming::event::EventType,
+    time::{Frequency, Timestamp},
+    types::{ObjectHandle, ObjectName},
+};
+
+#[derive(Debug, PartialEq)]
+pub struct TaskTransition {
+    pub handle: ObjectHandle,
+    pub name: ObjectName,
+    pub prev_state: TaskLifecycleState,
+    pub next_state: TaskLifecycleState,
+}
+
+/// Tracks the lifecycle state of each task observed in the trace
+///
+/// Trace recorder doesn't record why a task was switched out, so the state
+/// a task moves to is inferred from the events it emitted while running:
+/// a blocking kernel call means blocked, a suspend means suspended, otherwise
+/// it was preempted and is still ready.
+#[derive(Debug, Default)]
+pub struct TaskStateTracker {
+    tasks: HashMap<ObjectHandle, (ObjectName, TaskLifecycleState)>,
+    /// State the active task moves to once it gets switched out
+    switch_out_state: Option<TaskLifecycleState>,
+}
+
+impl TaskStateTracker {
+    pub fn created(&mut self, handle: ObjectHandle, name: &ObjectName) -> Option<TaskTransition> {
+        self.transition(handle, Some(name), TaskLifecycleState::Created)
+    }
+
+    pub fn ready(&mut self, handle: ObjectHandle, name: &ObjectName) -> Option<TaskTransition> {
+        self.transition(handle, Some(name), TaskLifecycleState::Ready)
+    }
+
+    /// The active task issued a blocking kernel call
+    pub fn blocking_call(&mut self) {
+        self.switch_out_state = Some(TaskLifecycleState::Blocked);
+    }
+
+    pub fn suspended(
+        &mut self,
+        handle: ObjectHandle,
+        active_task: ObjectHandle,
+    ) -> Option<TaskTransition> {
+        if handle == active_task {
+            // Takes effect when the task gets switched out
+            self.switch_out_state = Some(TaskLifecycleState::Suspended);
+            None
+        } else {
+            self.transition(handle, None, TaskLifecycleState::Suspended)
+        }
+    }
+
+    pub fn deleted(&mut self, handle: ObjectHandle) -> Option<TaskTransition> {
+        self.transition(handle, None, TaskLifecycleState::Deleted)
+    }
+
+    pub fn switched(&mut self, prev: &Context, next: &Context) -> Vec<TaskTransition> {
+        let mut transitions = Vec::new();
+        if prev.handle != next.handle {
+            let switch_out_state = self
+                .switch_out_state
+                .take()
+                .unwrap_or(TaskLifecycleState::Ready);
+            transitions.extend(self.transition(prev.handle, Some(&prev.name), switch_out_state));
+        }
+        transitions.extend(self.transition(
+            next.handle,
+            Some(&next.name),
+            TaskLifecycleState::Running,
+        ));
+        transitions
+    }
+
+    fn transition(
+        &mut self,
+        handle: ObjectHandle,
+        name: Option<&ObjectName>,
+        next_state: TaskLifecycleState,
+    ) -> Option<TaskTransition> {
+        let (task_name, state) = match name {
+            Some(name) => {
+                let task = self
+                    .tasks
+                    .entry(handle)
+                    .or_insert_with(|| (name.clone(), TaskLifecycleState::Unknown));
+                if task.0 != *name {
+                    task.0 = name.clone();
+                }
+                task
+            }
+            // Tasks we only know the handle of aren't tracked until they're named
+            None => self.tasks.get_mut(&handle)?,
+        };
+        if *state == next_state {
+            return None;
+        }
+        let prev_state = *state;
+        *state = next_state;
+        Some(TaskTransition {
+            handle,
+            name: task_name.clone(),
+            prev_state,
+            next_state,
+        })
+    }
+}
+
+/// Records the per-task state intervals derived from the task state transitions
+#[derive(Debug, Default)]
+pub struct TaskStateIntervals {
+    tasks: BTreeMap<ObjectHandle, TaskIntervals>,
+    last_timestamp: Timestamp,
+}
+
+#[derive(Debug)]
+struct TaskIntervals {
+    name: ObjectName,
+    open: Option<(TaskLifecycleState, Timestamp)>,
+    intervals: Vec<(TaskLifecycleState, Timestamp, Timestamp)>,
+}
+
+impl TaskStateIntervals {
+    pub fn record(&mut self, transition: &TaskTransition, timestamp: Timestamp) {
+        let task = self
+            .tasks
+            .entry(transition.handle)
+            .or_insert_with(|| TaskIntervals {
+                name: transition.name.clone(),
+                open: None,
+                intervals: Vec::new(),
+            });
+        task.name = transition.name.clone();
+        if let Some((state, start)) = task.open.take() {
+            task.intervals.push((state, start, timestamp));
+        }
+        task.open = Some((transition.next_state, timestamp));
+        self.last_timestamp = timestamp;
+    }
+
+    /// Estimated heap bytes
+    pub fn estimated_size(&self) -> usize {
+        self.tasks
+            .values()
+            .map(|t| {
+                size_of::<(ObjectHandle, TaskIntervals)>()
+                    + t.name.len()
+                    + t.intervals.capacity()
+                        * size_of::<(TaskLifecycleState, Timestamp, Timestamp)>()
+            })
+            .sum()
+    }
+
+    /// Close any open intervals at the last observed timestamp and write
+    /// the intervals out as JSON
+    pub fn write(
+        &mut self,
+        path: &Path,
+        timer_frequency: Frequency,
+        task_groups: &TaskGroups,
+    ) -> std::io::Result<()> {
+        let freq = u128::from(timer_frequency.get_raw()).max(1);
+        let to_ns = |t: Timestamp| (u128::from(t.ticks()) * 1_000_000_000 / freq) as u64;
+
+        let mut tasks = Vec::with_capacity(self.tasks.len());
+        for (handle, task) in self.tasks.iter_mut() {
+            if let Some((state, start)) = task.open.take() {
+                task.intervals.push((state, start, self.last_timestamp));
+            }
+            tasks.push(TaskIntervalsDump {
+                name: &task.name,
+                tid: u32::from(*handle),
+                group: task_groups.group(&task.name),
+                intervals: task
+                    .intervals
+                    .iter()
+                    .map(|(state, start, end)| IntervalDump {
+                        state: *state,
+                        start_ns: to_ns(*start),
+                        end_ns: to_ns(*end),
+                    })
+                    .collect(),
+            });
+        }
+
+        let dump = StateIntervalsDump {
+            timer_frequency: timer_frequency.get_raw(),
+            tasks,
+        };
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &dump)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct StateIntervalsDump<'a> {
+    timer_frequency: u32,
+    tasks: Vec<TaskIntervalsDump<'a>>,
+}
+
+#[derive(Serialize)]
+struct TaskIntervalsDump<'a> {
+    name: &'a str,
+    tid: u32,
+    /// See the task_groups config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a str>,
+    intervals: Vec<IntervalDump>,
+}
+
+#[derive(Serialize)]
+struct IntervalDump {
+    state: TaskLifecycleState,
+    start_ns: u64,
+    end_ns: u64,
+}
+
+/// Kernel calls that block the calling task when they can't complete
+pub fn is_blocking_event(event_type: EventType) -> bool {
+    use EventType::*;
+    matches!(
+        event_type,
+        TaskDelay
+            | TaskDelayUntil
+            | TaskNotifyWaitBlock
+            | QueueSendBlock
+            | QueueSendFrontBlock
+            | QueueReceiveBlock
+            | QueuePeekBlock
+            | SemaphoreGiveBlock
+            | SemaphoreTakeBlock
+            | SemaphorePeekBlock
+            | MutexGiveBlock
+            | MutexTakeBlock
+            | MutexTakeRecursiveBlock
+            | EventGroupSyncBlock
+            | EventGroupWaitBitsBlock
+            | MessageBufferSendBlock
+            | MessageBufferReceiveBlock
+            | StreamBufferSendBlock
+            | StreamBufferReceiveBlock
+    )
+}
@@ -0,0 +1,69 @@
+//! Numeric telemetry user event channels, see the config's telemetry_channels
+//!
+//! The user events of a telemetry channel are converted to `<channel>_value` events with a
+//! single `value` payload field, instead of USER_EVENTs, for charting.
+
+use crate::config::TelemetryChannel;
+use crate::units;
+use babeltrace2_sys::{ffi, BtResultExt, Error};
+use std::ffi::CString;
+use trace_recorder_parser::types::Argument;
+
+/// Creates the `<channel>_value` event class
+pub fn event_class(
+    event_id: u64,
+    channel: &str,
+    telemetry_channel: &TelemetryChannel,
+    stream_class: *mut ffi::bt_stream_class,
+) -> Result<*mut ffi::bt_event_class, Error> {
+    let name = CString::new(format!("{channel}_value"))?;
+    unsafe {
+        let trace_class = ffi::bt_stream_class_borrow_trace_class(stream_class);
+
+        let event_class = ffi::bt_event_class_create_with_id(stream_class, event_id);
+        let ret = ffi::bt_event_class_set_name(event_class, name.as_ptr());
+        ret.capi_result()?;
+
+        let payload_fc = ffi::bt_field_class_structure_create(trace_class);
+        let value_fc = ffi::bt_field_class_real_double_precision_create(trace_class);
+        if let Some(unit) = &telemetry_channel.unit {
+            units::set_unit(value_fc, unit)?;
+        }
+        let ret = ffi::bt_field_class_structure_append_member(
+            payload_fc,
+            b"value\0".as_ptr() as _,
+            value_fc,
+        );
+        ret.capi_result()?;
+        ffi::bt_field_class_put_ref(value_fc);
+
+        let ret = ffi::bt_event_class_set_payload_field_class(event_class, payload_fc);
+        ret.capi_result()?;
+        ffi::bt_field_class_put_ref(payload_fc);
+
+        Ok(event_class)
+    }
+}
+
+/// The first numeric argument of the user event
+pub fn value(args: &[Argument]) -> Option<f64> {
+    args.iter().find_map(|arg| match arg {
+        Argument::I8(v) => Some((*v).into()),
+        Argument::U8(v) => Some((*v).into()),
+        Argument::I16(v) => Some((*v).into()),
+        Argument::U16(v) => Some((*v).into()),
+        Argument::I32(v) => Some((*v).into()),
+        Argument::U32(v) => Some((*v).into()),
+        Argument::F32(v) => Some((*v).into()),
+        Argument::F64(v) => Some(*v),
+        Argument::String(_) => None,
+    })
+}
+
+pub fn emit_value(ctf_event: *mut ffi::bt_event, value: f64) {
+    unsafe {
+        let payload_f = ffi::bt_event_borrow_payload_field(ctf_event);
+        let f = ffi::bt_field_structure_borrow_member_field_by_index(payload_f, 0);
+        ffi::bt_field_real_double_precision_set_value(f, value);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::io::Read;
+use trace_recorder_parser::{
+    streaming::{
+        event::{Event, EventType},
+        Error, RecorderData,
+    },
+    time::Timestamp,
+    types::ObjectHandle,
+};
+use trace_recorder_to_ctf::trackers::TimestampStrategy;
+
+/// Wake-ups needed before the observed frequency is trusted
+const MIN_SAMPLES: u64 = 4;
+
+/// Relative difference between the declared and observed timer frequency reported as a mismatch
+const MISMATCH_TOLERANCE: f64 = 0.01;
+
+/// Estimates the timer frequency from the OS tick rate and the timestamps of the
+/// tick-referenced wake-ups.
+///
+/// A task calling TASK_DELAY_UNTIL is made ready (TASK_READY) by the tick interrupt of its
+/// wake tick, so each wake-up pairs an OS tick count with a timer timestamp. The timer ticks
+/// per OS tick is the slope of a least squares fit over the pairs.
+#[derive(Debug)]
+pub struct TickRateCheck {
+    os_tick_rate_hz: u64,
+    /// The running task, the caller of TASK_DELAY_UNTIL
+    active: Option<ObjectHandle>,
+    /// Wake tick of the tasks blocked in TASK_DELAY_UNTIL
+    pending: HashMap<ObjectHandle, u64>,
+    /// The first wake-up, the fit is relative to it to keep the sums small
+    origin: Option<(u64, u64)>,
+    samples: u64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_xy: f64,
+}
+
+/// The result of the check, the frequencies are in Hz
+#[derive(Copy, Clone, Debug)]
+pub struct TickRateEstimate {
+    pub declared: u64,
+    pub observed: u64,
+    pub samples: u64,
+}
+
+impl TickRateEstimate {
+    /// The declared frequency differs from the observed one beyond the tolerance
+    pub fn is_mismatch(&self) -> bool {
+        let declared = self.declared as f64;
+        ((self.observed as f64 - declared) / declared).abs() > MISMATCH_TOLERANCE
+    }
+}
+
+impl TickRateCheck {
+    /// None when the OS tick rate isn't known
+    pub fn new(os_tick_rate_hz: u32) -> Option<Self> {
+        (os_tick_rate_hz != 0).then(|| Self {
+            os_tick_rate_hz: os_tick_rate_hz.into(),
+            active: None,
+            pending: HashMap::new(),
+            origin: None,
+            samples: 0,
+            sum_x: 0.0,
+            sum_y: 0.0,
+            sum_xx: 0.0,
+            sum_xy: 0.0,
+        })
+    }
+
+    pub fn observe(&mut self, event_type: EventType, event: &Event, timestamp: Timestamp) {
+        match event {
+            Event::TaskActivate(ev) | Event::TaskResume(ev) => self.active = Some(ev.handle),
+            Event::Unknown(ev)
+                if event_type == EventType::TaskDelayUntil && !ev.parameters().is_empty() =>
+            {
+                if let Some(task) = self.active {
+                    self.pending.insert(task, ev.parameters()[0].into());
+                }
+            }
+            Event::TaskReady(ev) => {
+                if let Some(wake_tick) = self.pending.remove(&ev.handle) {
+                    self.add_sample(wake_tick, timestamp.ticks());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn add_sample(&mut self, wake_tick: u64, timestamp: u64) {
+        let (tick0, timestamp0) = *self.origin.get_or_insert((wake_tick, timestamp));
+        let x = wake_tick as f64 - tick0 as f64;
+        let y = timestamp as f64 - timestamp0 as f64;
+        self.samples += 1;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_xy += x * y;
+    }
+
+    /// None until there are enough wake-ups spanning more than one tick
+    pub fn estimate(&self, declared: u32) -> Option<TickRateEstimate> {
+        if self.samples < MIN_SAMPLES || declared == 0 {
+            return None;
+        }
+        let n = self.samples as f64;
+        let denominator = n * self.sum_xx - self.sum_x * self.sum_x;
+        if denominator <= 0.0 {
+            return None;
+        }
+        let timer_ticks_per_os_tick = (n * self.sum_xy - self.sum_x * self.sum_y) / denominator;
+        let observed = timer_ticks_per_os_tick * self.os_tick_rate_hz as f64;
+        (observed >= 1.0).then_some(TickRateEstimate {
+            declared: declared.into(),
+            observed: observed.round() as u64,
+            samples: self.samples,
+        })
+    }
+
+    /// Scan the trace for the tick-referenced wake-ups, before the conversion
+    pub fn scan<R: Read>(
+        mut r: R,
+        strategy: TimestampStrategy,
+    ) -> Result<Option<TickRateEstimate>, Error> {
+        let mut trd = RecorderData::find(&mut r)?;
+        let declared = trd.timestamp_info.timer_frequency.get_raw();
+        let Some(mut check) = Self::new(trd.timestamp_info.os_tick_rate_hz.get_raw()) else {
+            return Ok(None);
+        };
+        let mut tracker = strategy.tracker();
+        let mut first = true;
+        while let Some((event_code, event)) = trd.read_event(&mut r)? {
+            if first {
+                first = false;
+                tracker.init(event.timestamp(), trd.timestamp_info.timer_wraparounds);
+            }
+            let timestamp = tracker.elapsed(event.timestamp());
+            check.observe(event_code.event_type(), &event, timestamp);
+        }
+        Ok(check.estimate(declared))
+    }
+}
+
+/// Rescales the timestamps of a timer running at the observed frequency onto the declared
+/// frequency, which the clock class and reports are based on
+#[derive(Copy, Clone, Debug)]
+pub struct TimestampScale {
+    declared: u64,
+    observed: u64,
+}
+
+impl TimestampScale {
+    pub fn new(estimate: &TickRateEstimate) -> Self {
+        Self {
+            declared: estimate.declared,
+            observed: estimate.observed,
+        }
+    }
+
+    pub fn apply(&self, timestamp: Timestamp) -> Timestamp {
+        let ticks =
+            u128::from(timestamp.ticks()) * u128::from(self.declared) / u128::from(self.observed);
+        Timestamp::from(ticks as u64)
+    }
+}
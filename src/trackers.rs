@@ -0,0 +1,168 @@
+use clap::ValueEnum;
+use trace_recorder_parser::{
+    streaming::event::{EventCount, TrackingEventCounter},
+    time::{StreamingInstant, Timestamp},
+};
+
+/// Extends the raw event timestamps into a monotonic timeline
+pub trait TimestampTracker: Send {
+    /// Called with the first event's timestamp
+    fn init(&mut self, first: Timestamp, timer_wraparounds: u32);
+
+    fn elapsed(&mut self, raw: Timestamp) -> Timestamp;
+}
+
+/// Extends the raw 16-bit event counters, detecting dropped events
+pub trait EventCountTracker: Send {
+    /// Called with the first event's counter
+    fn init(&mut self, first: EventCount);
+
+    /// Returns the number of dropped events, if any
+    fn update(&mut self, count: EventCount) -> Option<u64>;
+
+    fn count(&self) -> u64;
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimestampStrategy {
+    /// A timestamp going backwards is a timer rollover
+    #[default]
+    Rollover,
+    /// A timestamp going backwards is the timer restarting from zero (e.g. timers that
+    /// reset on sleep), time continues from the last timestamp
+    ResetOnSleep,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum EventCountStrategy {
+    /// The event counter is contiguous, a gap is events dropped by the recorder
+    #[default]
+    Contiguous,
+    /// The event counter has gaps that aren't dropped events (e.g. ports filtering events
+    /// after counting them), events are counted as they're converted and none are reported
+    /// as dropped
+    Unchecked,
+}
+
+impl EventCountStrategy {
+    pub fn tracker(self) -> Box<dyn EventCountTracker> {
+        match self {
+            EventCountStrategy::Contiguous => {
+                Box::new(ContiguousEventCountTracker(TrackingEventCounter::zero()))
+            }
+            EventCountStrategy::Unchecked => Box::<UncheckedEventCountTracker>::default(),
+        }
+    }
+}
+
+impl TimestampStrategy {
+    pub fn tracker(self) -> Box<dyn TimestampTracker> {
+        match self {
+            TimestampStrategy::Rollover => Box::new(RolloverTracker(StreamingInstant::zero())),
+            TimestampStrategy::ResetOnSleep => Box::<ResetOnSleepTracker>::default(),
+        }
+    }
+}
+
+struct RolloverTracker(StreamingInstant);
+
+impl TimestampTracker for RolloverTracker {
+    fn init(&mut self, first: Timestamp, timer_wraparounds: u32) {
+        self.0 = StreamingInstant::new(first.ticks() as u32, timer_wraparounds);
+    }
+
+    fn elapsed(&mut self, raw: Timestamp) -> Timestamp {
+        self.0.elapsed(raw)
+    }
+}
+
+#[derive(Default)]
+struct ResetOnSleepTracker {
+    last_raw: u64,
+    elapsed: u64,
+}
+
+/// Range of the raw (32-bit) event timestamps
+const TIMER_RANGE: u64 = 1 << 32;
+
+impl TimestampTracker for ResetOnSleepTracker {
+    fn init(&mut self, first: Timestamp, timer_wraparounds: u32) {
+        self.last_raw = first.ticks();
+        self.elapsed = (u64::from(timer_wraparounds) << 32) | first.ticks();
+    }
+
+    fn elapsed(&mut self, raw: Timestamp) -> Timestamp {
+        let raw = raw.ticks();
+        self.elapsed += match raw.checked_sub(self.last_raw) {
+            Some(delta) => delta,
+            None => {
+                // Events of a running target are close together, a short step forward across
+                // the end of the range is an ordinary timer wrap, else it restarted from zero
+                let wrapped = TIMER_RANGE - self.last_raw + raw;
+                if wrapped < TIMER_RANGE / 2 {
+                    wrapped
+                } else {
+                    raw
+                }
+            }
+        };
+        self.last_raw = raw;
+        Timestamp::from(self.elapsed)
+    }
+}
+
+/// The trace recorder parser's event counter tracking
+struct ContiguousEventCountTracker(TrackingEventCounter);
+
+impl EventCountTracker for ContiguousEventCountTracker {
+    fn init(&mut self, first: EventCount) {
+        self.0.set_initial_count(first);
+    }
+
+    fn update(&mut self, count: EventCount) -> Option<u64> {
+        self.0.update(count)
+    }
+
+    fn count(&self) -> u64 {
+        self.0.count()
+    }
+}
+
+#[derive(Default)]
+struct UncheckedEventCountTracker(u64);
+
+impl EventCountTracker for UncheckedEventCountTracker {
+    fn init(&mut self, first: EventCount) {
+        self.0 = u16::from(first).into();
+    }
+
+    fn update(&mut self, _count: EventCount) -> Option<u64> {
+        self.0 += 1;
+        None
+    }
+
+    fn count(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_on_sleep_timer_wrap() {
+        let mut tracker = TimestampStrategy::ResetOnSleep.tracker();
+        tracker.init(Timestamp::from(u64::from(u32::MAX) - 10), 1);
+        let elapsed = tracker.elapsed(Timestamp::from(5)).ticks();
+        assert_eq!(elapsed, (2 << 32) + 5);
+    }
+
+    #[test]
+    fn reset_on_sleep_timer_reset() {
+        let mut tracker = TimestampStrategy::ResetOnSleep.tracker();
+        tracker.init(Timestamp::from(1000), 0);
+        assert_eq!(tracker.elapsed(Timestamp::from(2000)).ticks(), 2000);
+        assert_eq!(tracker.elapsed(Timestamp::from(100)).ticks(), 2100);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::interruptor::Interruptor;
+use crate::stats::{ConversionStats, SharedStats, WarningCounter};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Gauge, Paragraph, Row, Table},
+    DefaultTerminal, Frame,
+};
+use std::io;
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_ROWS: usize = 32;
+
+/// Live progress/summary screen
+pub struct Tui {
+    terminal: DefaultTerminal,
+    stats: SharedStats,
+    warnings: WarningCounter,
+    interruptor: Interruptor,
+    started_at: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl Tui {
+    pub fn new(
+        stats: SharedStats,
+        warnings: WarningCounter,
+        interruptor: Interruptor,
+    ) -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+        Ok(Self {
+            terminal,
+            stats,
+            warnings,
+            interruptor,
+            started_at: Instant::now(),
+            last_draw: None,
+        })
+    }
+
+    /// Redraw the screen if it's due and handle key presses
+    pub fn update(&mut self) -> io::Result<()> {
+        if self
+            .last_draw
+            .map(|t| t.elapsed() < REDRAW_INTERVAL)
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        self.last_draw = Some(Instant::now());
+
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                // Raw mode swallows the control-c signal
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (key.code == KeyCode::Char('q') || ctrl_c) {
+                    self.interruptor.set();
+                }
+            }
+        }
+
+        self.draw(false)
+    }
+
+    /// Draw the final summary and wait for the user to dismiss it
+    pub fn finish(mut self) -> io::Result<()> {
+        self.draw(true)?;
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, done: bool) -> io::Result<()> {
+        let stats = self.stats.lock().unwrap().clone();
+        let warnings = self.warnings.count();
+        let elapsed = self.started_at.elapsed();
+        self.terminal
+            .draw(|frame| render(frame, &stats, warnings, elapsed, done))?;
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+fn render(
+    frame: &mut Frame,
+    stats: &ConversionStats,
+    warnings: u64,
+    elapsed: Duration,
+    done: bool,
+) {
+    let [progress_area, summary_area, tables_area, footer_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(6),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [events_area, tasks_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .areas(tables_area);
+
+    let bytes = stats.counters.bytes;
+    let ratio = match stats.input_size {
+        Some(size) if size != 0 => (bytes as f64 / size as f64).clamp(0.0, 1.0),
+        _ => 0.0,
+    };
+    let title = if done {
+        " Conversion complete "
+    } else {
+        " Converting "
+    };
+    let gauge = Gauge::default()
+        .block(Block::bordered().title(title))
+        .gauge_style(Style::new().green())
+        .ratio(if done { 1.0 } else { ratio })
+        .label(format!("{:.1} MB", bytes as f64 / 1_000_000.0));
+    frame.render_widget(gauge, progress_area);
+
+    let elapsed_secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    let summary = Paragraph::new(vec![
+        Line::from(format!(
+            "Events: {}  ({:.0} events/s)",
+            stats.counters.events,
+            stats.counters.events as f64 / elapsed_secs
+        )),
+        Line::from(format!(
+            "Dropped events: {}  Duplicate events: {}",
+            stats.counters.dropped_events, stats.counters.duplicate_events
+        )),
+        Line::from(format!("Warnings: {warnings}")),
+        Line::from(format!(
+            "Trace time: {:.6} s  Elapsed: {:.1} s",
+            stats.trace_duration_secs(),
+            elapsed.as_secs_f64()
+        )),
+    ])
+    .block(Block::bordered().title(" Summary "));
+    frame.render_widget(summary, summary_area);
+
+    let mut events: Vec<_> = stats.events_by_type.iter().collect();
+    events.sort_by(|a, b| b.1.cmp(a.1));
+    let rows = events
+        .into_iter()
+        .take(MAX_ROWS)
+        .map(|(t, n)| Row::new(vec![t.to_string(), n.to_string()]));
+    let table = Table::new(rows, [Constraint::Fill(1), Constraint::Length(12)])
+        .header(Row::new(vec!["Event", "Count"]).bold())
+        .block(Block::bordered().title(" Events by type "));
+    frame.render_widget(table, events_area);
+
+    let trace_secs = stats.trace_duration_secs();
+    let mut tasks: Vec<_> = stats.task_activations.iter().collect();
+    tasks.sort_by(|a, b| b.1.cmp(a.1));
+    let rows = tasks.into_iter().take(MAX_ROWS).map(|(name, n)| {
+        let rate = if trace_secs > 0.0 {
+            *n as f64 / trace_secs
+        } else {
+            0.0
+        };
+        Row::new(vec![name.clone(), n.to_string(), format!("{rate:.1}")])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(Row::new(vec!["Task", "Switches", "Switches/s"]).bold())
+    .block(Block::bordered().title(" Tasks "));
+    frame.render_widget(table, tasks_area);
+
+    let footer = if done {
+        "Press any key to exit"
+    } else {
+        "q: stop the conversion"
+    };
+    frame.render_widget(Line::from(footer).dim(), footer_area);
+}
@@ -1,36 +1,22 @@
+use crate::demangle::Demangler;
 use babeltrace2_sys::{ffi, Error, MessageIteratorStatus, SelfMessageIterator};
 use std::collections::{hash_map, HashMap};
 use std::ffi::{CStr, CString};
-use trace_recorder_parser::{
-    streaming::event::{EventType, IsrEvent, TaskEvent},
-    time::Timestamp,
-    types::{ObjectHandle, ObjectName, Priority},
-};
+use std::mem::size_of;
+use trace_recorder_parser::{streaming::event::EventType, time::Timestamp, types::ObjectName};
+pub use trace_recorder_to_ctf::contexts::Context;
 
-#[derive(Debug, PartialEq)]
-pub struct Context {
-    pub handle: ObjectHandle,
+/// A timed interval of a task or ISR, e.g. an ISR execution
+#[derive(Clone, Debug)]
+pub struct Interval {
     pub name: ObjectName,
-    pub priority: Priority,
+    pub start: Timestamp,
+    pub end: Timestamp,
 }
 
-impl From<TaskEvent> for Context {
-    fn from(value: TaskEvent) -> Self {
-        Self {
-            handle: value.handle,
-            name: value.name,
-            priority: value.priority,
-        }
-    }
-}
-
-impl From<IsrEvent> for Context {
-    fn from(value: IsrEvent) -> Self {
-        Self {
-            handle: value.handle,
-            name: value.name,
-            priority: value.priority,
-        }
+impl Interval {
+    pub fn duration_ticks(&self) -> u64 {
+        self.end.ticks().saturating_sub(self.start.ticks())
     }
 }
 
@@ -38,12 +24,25 @@ impl From<IsrEvent> for Context {
 pub struct StringCache {
     strings: HashMap<String, CString>,
     event_types: HashMap<EventType, CString>,
+    /// Applied to the strings as they're inserted, see --demangle
+    demangler: Option<Box<dyn Demangler>>,
 }
 
 impl StringCache {
+    pub fn with_demangler(demangler: Box<dyn Demangler>) -> Self {
+        Self {
+            demangler: Some(demangler),
+            ..Default::default()
+        }
+    }
+
     pub fn insert_str(&mut self, key: &str) -> Result<(), Error> {
         if !self.strings.contains_key(key) {
-            self.strings.insert(key.to_string(), CString::new(key)?);
+            let value = match self.demangler.as_ref().and_then(|d| d.demangle(key)) {
+                Some(demangled) => CString::new(demangled)?,
+                None => CString::new(key)?,
+            };
+            self.strings.insert(key.to_string(), value);
         }
         Ok(())
     }
@@ -66,23 +65,55 @@ impl StringCache {
             .get(key)
             .expect("String cache event type entry doesn't exist")
     }
+
+    /// Estimated heap bytes
+    pub fn estimated_size(&self) -> usize {
+        let strings: usize = self
+            .strings
+            .iter()
+            .map(|(k, v)| size_of::<(String, CString)>() + k.len() + v.as_bytes_with_nul().len())
+            .sum();
+        let event_types: usize = self
+            .event_types
+            .values()
+            .map(|v| size_of::<(EventType, CString)>() + v.as_bytes_with_nul().len())
+            .sum();
+        strings + event_types
+    }
+
+    /// Drop the cached strings, they're recreated on use
+    pub fn clear(&mut self) {
+        self.strings = HashMap::new();
+        self.event_types = HashMap::new();
+    }
 }
 
 // TODO split up the roles of this, currently just a catch all
 pub struct BorrowedCtfState<'a> {
     stream: *mut ffi::bt_stream,
     packet: *mut ffi::bt_packet,
+    /// Packet of the ISR-context stream, null unless ISR events get their own stream
+    isr_packet: *mut ffi::bt_packet,
+    /// Packet of the os_ticks clock stream, null unless there's a secondary clock
+    tick_packet: *mut ffi::bt_packet,
+    in_isr: bool,
     msg_iter: SelfMessageIterator,
     messages: &'a mut [*const ffi::bt_message],
-    msgs_len: usize,
+    /// Messages pushed and not handed over yet, including the ones that didn't fit in the
+    /// message array of an earlier call. The converter pushes any number of messages per event,
+    /// the rest are handed over on the following calls.
+    pending: Vec<*const ffi::bt_message>,
 }
 
 impl<'a> BorrowedCtfState<'a> {
     pub fn new(
         stream: *mut ffi::bt_stream,
         packet: *mut ffi::bt_packet,
+        isr_packet: *mut ffi::bt_packet,
+        tick_packet: *mut ffi::bt_packet,
         msg_iter: SelfMessageIterator,
         messages: &'a mut [*const ffi::bt_message],
+        pending: Vec<*const ffi::bt_message>,
     ) -> Self {
         assert!(!stream.is_null());
         assert!(!packet.is_null());
@@ -90,24 +121,49 @@ impl<'a> BorrowedCtfState<'a> {
         Self {
             stream,
             packet,
+            isr_packet,
+            tick_packet,
+            in_isr: false,
             msg_iter,
             messages,
-            msgs_len: 0,
+            pending,
         }
     }
 
-    pub fn release(self) -> MessageIteratorStatus {
-        if self.msgs_len == 0 {
+    /// Whether messages of an earlier call are still waiting to be handed over
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The messages handed over by release(), as many as fit in the message array
+    pub fn ready_messages(&self) -> &[*const ffi::bt_message] {
+        &self.pending[..self.pending.len().min(self.messages.len())]
+    }
+
+    /// Hands over as many messages as fit in the message array, returns the rest to pass
+    /// to the next call
+    pub fn release(mut self) -> (MessageIteratorStatus, Vec<*const ffi::bt_message>) {
+        let count = self.pending.len().min(self.messages.len());
+        let status = if count == 0 {
             MessageIteratorStatus::NoMessages
         } else {
-            MessageIteratorStatus::Messages(self.msgs_len as u64)
-        }
+            for (dst, msg) in self.messages.iter_mut().zip(self.pending.drain(..count)) {
+                *dst = msg;
+            }
+            MessageIteratorStatus::Messages(count as u64)
+        };
+        (status, self.pending)
     }
 
     pub fn stream_mut(&mut self) -> *mut ffi::bt_stream {
         self.stream
     }
 
+    /// Route the event messages created from here on to the ISR-context stream, if there is one
+    pub fn set_in_isr(&mut self, in_isr: bool) {
+        self.in_isr = in_isr;
+    }
+
     pub fn message_iter_mut(&mut self) -> *mut ffi::bt_self_message_iterator {
         self.msg_iter.inner_mut()
     }
@@ -117,25 +173,87 @@ impl<'a> BorrowedCtfState<'a> {
         event_class: *const ffi::bt_event_class,
         timestamp: Timestamp,
     ) -> *mut ffi::bt_message {
+        let packet = if self.in_isr && !self.isr_packet.is_null() {
+            self.isr_packet
+        } else {
+            self.packet
+        };
         unsafe {
             ffi::bt_message_event_create_with_packet_and_default_clock_snapshot(
                 self.msg_iter.inner_mut(),
                 event_class,
-                self.packet,
+                packet,
                 timestamp.ticks(),
             )
         }
     }
 
+    /// The stream class of the os_ticks clock stream, if there is one
+    pub fn tick_stream_class_mut(&mut self) -> Option<*mut ffi::bt_stream_class> {
+        (!self.tick_packet.is_null()).then(|| unsafe {
+            ffi::bt_stream_borrow_class(ffi::bt_packet_borrow_stream(self.tick_packet))
+        })
+    }
+
+    /// Create an event message on the os_ticks clock stream, the timestamp is the OS tick count
+    pub fn create_tick_message(
+        &mut self,
+        event_class: *const ffi::bt_event_class,
+        os_ticks: Timestamp,
+    ) -> *mut ffi::bt_message {
+        unsafe {
+            ffi::bt_message_event_create_with_packet_and_default_clock_snapshot(
+                self.msg_iter.inner_mut(),
+                event_class,
+                self.tick_packet,
+                os_ticks.ticks(),
+            )
+        }
+    }
+
     pub fn push_message(&mut self, msg: *const ffi::bt_message) -> Result<(), Error> {
         if msg.is_null() {
             Err(Error::PluginError("MessageVec: msg is NULL".to_owned()))
-        } else if self.msgs_len >= self.messages.len() {
-            Err(Error::PluginError("MessageVec: full".to_owned()))
         } else {
-            self.messages[self.msgs_len] = msg;
-            self.msgs_len += 1;
+            self.pending.push(msg);
             Ok(())
         }
     }
+
+    pub fn message_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Number of messages that can still be pushed without spilling over to the next call
+    pub fn capacity_left(&self) -> usize {
+        self.messages.len().saturating_sub(self.pending.len())
+    }
+
+    /// The messages pushed after the first `count` messages
+    pub fn messages_after(&self, count: usize) -> &[*const ffi::bt_message] {
+        &self.pending[count.min(self.pending.len())..]
+    }
+
+    /// Discard the messages pushed after the first `count` messages
+    pub fn discard_messages_after(&mut self, count: usize) {
+        if count >= self.pending.len() {
+            return;
+        }
+        for msg in self.pending.drain(count..) {
+            unsafe { ffi::bt_message_put_ref(msg) };
+        }
+    }
 }
+
+// The conversion state that isn't bound to babeltrace2 objects stays Send so it can move to
+// a worker thread, the FFI-bound state (TrcCtfConverter, TrcPluginState) holds raw babeltrace2
+// pointers and is single-threaded
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<StringCache>();
+    assert_send::<Context>();
+    assert_send::<crate::stats::SharedStats>();
+    assert_send::<crate::stats::WarningCounter>();
+    assert_send::<crate::task_state::TaskStateTracker>();
+    assert_send::<crate::softirq::SoftIrqTracker>();
+};
@@ -0,0 +1,21 @@
+//! Unit annotations, stored as user attributes of the field classes
+//!
+//! The converter's own fields use `ns`, `bytes`, `ticks` (OS ticks) and `timer_ticks`
+//! (the trace recorder timestamp timer), event fields are annotated with `#[unit = "..."]`.
+
+use babeltrace2_sys::{ffi, BtResultExt, Error};
+use std::ffi::CString;
+
+/// Sets the `unit` user attribute of the field class
+pub fn set_unit(fc: *mut ffi::bt_field_class, unit: &str) -> Result<(), Error> {
+    let unit = CString::new(unit)?;
+    unsafe {
+        let attrs = ffi::bt_value_map_create();
+        let ret =
+            ffi::bt_value_map_insert_string_entry(attrs, b"unit\0".as_ptr() as _, unit.as_ptr());
+        ret.capi_result()?;
+        ffi::bt_field_class_set_user_attributes(fc, attrs);
+        ffi::bt_value_put_ref(attrs);
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Named USER_EVENT arguments mined from the format string, used with --named-user-args
+//!
+//! A format string like "temp=%d rpm=%u" names its arguments `temp` and `rpm`, each USER_EVENT
+//! format string whose arguments are all named gets its own event class with a payload
+//! member per argument.
+
+use babeltrace2_sys::{ffi, BtResultExt, Error};
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::mem::{self, Discriminant};
+use trace_recorder_parser::types::Argument;
+
+/// Length modifiers that can sit between the '%' and the conversion specifier,
+/// 'b' is the TraceRecorder 8-bit modifier
+const LENGTH_MODIFIERS: &[char] = &['h', 'l', 'b', 'q', 'L', 'j', 'z', 't'];
+
+/// Payload members of the USER_EVENT class, the argument names can't reuse them
+const USER_EVENT_FIELDS: &[&str] = &[
+    "channel",
+    "format_string",
+    "formatted_string",
+    "args",
+    "comm",
+    "tid",
+    "loglevel",
+];
+
+/// Returns the argument names of a format string, or None when any argument isn't preceded
+/// by a `key=`, or the keys aren't unique among themselves and the USER_EVENT fields
+pub fn arg_names(format_string: &str) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    let mut chars = format_string.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        // Flags, width, precision and length modifiers, up to the conversion specifier
+        let (_, conversion) = chars.by_ref().find(|(_, c)| {
+            (c.is_ascii_alphabetic() && !LENGTH_MODIFIERS.contains(c)) || *c == '%'
+        })?;
+        if conversion == '%' {
+            continue;
+        }
+        names.push(key_before(&format_string[..idx])?);
+    }
+
+    let mut unique: HashSet<&str> = USER_EVENT_FIELDS.iter().copied().collect();
+    (!names.is_empty() && names.iter().all(|n| unique.insert(n.as_str()))).then_some(names)
+}
+
+fn key_before(prefix: &str) -> Option<String> {
+    let prefix = prefix.strip_suffix('=')?.trim_end();
+    let start = prefix
+        .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        .len();
+    let key = &prefix[start..];
+    (!key.is_empty() && !key.starts_with(|c: char| c.is_ascii_digit())).then(|| key.to_owned())
+}
+
+/// The named arguments of a USER_EVENT event class, appended after the USER_EVENT fields
+#[derive(Debug)]
+pub struct NamedArgs {
+    /// Index of the first named argument member in the payload
+    first_member: u64,
+    names: Vec<CString>,
+    /// Argument types the class was created with
+    kinds: Vec<Discriminant<Argument>>,
+}
+
+impl NamedArgs {
+    pub fn new(names: &[String]) -> Result<Self, Error> {
+        Ok(Self {
+            first_member: 0,
+            names: names
+                .iter()
+                .map(|n| CString::new(n.as_str()))
+                .collect::<Result<_, _>>()?,
+            kinds: Vec::new(),
+        })
+    }
+
+    /// Appends a payload member per argument, typed after the decoded arguments
+    pub fn append_members(
+        &mut self,
+        args: &[Argument],
+        trace_class: *mut ffi::bt_trace_class,
+        payload_fc: *mut ffi::bt_field_class,
+    ) -> Result<(), Error> {
+        unsafe {
+            self.first_member = ffi::bt_field_class_structure_get_member_count(payload_fc);
+            self.kinds = args.iter().map(mem::discriminant).collect();
+            for (name, arg) in self.names.iter().zip(args.iter()) {
+                let fc = match arg {
+                    Argument::I8(_) | Argument::I16(_) | Argument::I32(_) => {
+                        ffi::bt_field_class_integer_signed_create(trace_class)
+                    }
+                    Argument::U8(_) | Argument::U16(_) | Argument::U32(_) => {
+                        ffi::bt_field_class_integer_unsigned_create(trace_class)
+                    }
+                    Argument::F32(_) | Argument::F64(_) => {
+                        ffi::bt_field_class_real_double_precision_create(trace_class)
+                    }
+                    Argument::String(_) => ffi::bt_field_class_string_create(trace_class),
+                };
+                let ret =
+                    ffi::bt_field_class_structure_append_member(payload_fc, name.as_ptr() as _, fc);
+                ret.capi_result()?;
+                ffi::bt_field_class_put_ref(fc);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the named argument members, the arguments must match the ones the class
+    /// was created with
+    pub fn emit_args(&self, args: &[Argument], ctf_event: *mut ffi::bt_event) -> Result<(), Error> {
+        unsafe {
+            let payload_f = ffi::bt_event_borrow_payload_field(ctf_event);
+            for (idx, arg) in args.iter().enumerate() {
+                let f = ffi::bt_field_structure_borrow_member_field_by_index(
+                    payload_f,
+                    self.first_member + idx as u64,
+                );
+                match arg {
+                    Argument::I8(v) => ffi::bt_field_integer_signed_set_value(f, (*v).into()),
+                    Argument::I16(v) => ffi::bt_field_integer_signed_set_value(f, (*v).into()),
+                    Argument::I32(v) => ffi::bt_field_integer_signed_set_value(f, (*v).into()),
+                    Argument::U8(v) => ffi::bt_field_integer_unsigned_set_value(f, (*v).into()),
+                    Argument::U16(v) => ffi::bt_field_integer_unsigned_set_value(f, (*v).into()),
+                    Argument::U32(v) => ffi::bt_field_integer_unsigned_set_value(f, (*v).into()),
+                    Argument::F32(v) => {
+                        ffi::bt_field_real_double_precision_set_value(f, (*v).into())
+                    }
+                    Argument::F64(v) => ffi::bt_field_real_double_precision_set_value(f, *v),
+                    Argument::String(v) => {
+                        let s = CString::new(v.as_str())?;
+                        let ret = ffi::bt_field_string_set_value(f, s.as_ptr());
+                        ret.capi_result()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the decoded arguments have the types the class was created with
+    pub fn matches(&self, args: &[Argument]) -> bool {
+        args.len() == self.kinds.len()
+            && args
+                .iter()
+                .zip(self.kinds.iter())
+                .all(|(a, k)| mem::discriminant(a) == *k)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::events::SelfTest;
+use std::path::Path;
+use trace_recorder_to_ctf::{ConvertedEvent, JsonLinesReader};
+
+/// Check the selftest events of a JSON Lines output written with --inject-selftest
+/// against the fixed sequence
+pub fn run(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut events = Vec::new();
+    for event in JsonLinesReader::open(path)? {
+        let event = event?;
+        if event.name == "selftest" {
+            events.push(event);
+        }
+    }
+
+    let expected = SelfTest::sequence();
+    if events.len() != expected.len() {
+        return Err(format!(
+            "Expected {} selftest events, found {}",
+            expected.len(),
+            events.len()
+        )
+        .into());
+    }
+    for (event, expected) in events.iter().zip(expected.iter()) {
+        check(event, expected)?;
+    }
+    println!("{} selftest events OK", events.len());
+    Ok(())
+}
+
+fn check(event: &ConvertedEvent, expected: &SelfTest) -> Result<(), String> {
+    let string_value = expected.string_value.to_str().unwrap_or_default();
+    let mismatch = |field: &str| {
+        format!(
+            "selftest event {} has a wrong {field}, got {:?}",
+            expected.seq,
+            event.payload.get(field)
+        )
+    };
+    if event.payload_u64("seq") != Some(expected.seq) {
+        return Err(mismatch("seq"));
+    }
+    if event.payload_u64("unsigned_value") != Some(expected.unsigned_value) {
+        return Err(mismatch("unsigned_value"));
+    }
+    if event.payload_i64("signed_value") != Some(expected.signed_value) {
+        return Err(mismatch("signed_value"));
+    }
+    if event.payload_str("string_value") != Some(string_value) {
+        return Err(mismatch("string_value"));
+    }
+    Ok(())
+}